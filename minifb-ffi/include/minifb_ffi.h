@@ -8,6 +8,16 @@
 #include <stdint.h>
 #include <stdlib.h>
 
+/*
+ Maximum number of held keys reported in `MiniFBSurfaceInput`
+ */
+#define MINIFB_SURFACE_MAX_KEYS 16
+
+/*
+ Maximum number of keys in each list of `MiniFBInputSnapshot`
+ */
+#define MINIFB_SNAPSHOT_MAX_KEYS 16
+
 /*
  Keyboard key codes (mirrors minifb::Key)
  */
@@ -154,11 +164,267 @@ typedef enum MiniFBCursorStyle {
   ResizeAll = 7,
 } MiniFBCursorStyle;
 
+/*
+ How the accumulated sum is turned back into pixels
+ */
+typedef enum MiniFBAccumResolve {
+  /*
+   Divide by the total weight added so far
+   */
+  AccumAverage = 0,
+  /*
+   Use the weighted sum as is, clamped to white
+   */
+  AccumSum = 1,
+} MiniFBAccumResolve;
+
+/*
+ How a layer's color combines with what is already on the canvas
+ */
+typedef enum MiniFBBlendMode {
+  BlendNormal = 0,
+  BlendMultiply = 1,
+  BlendScreen = 2,
+} MiniFBBlendMode;
+
+/*
+ Horizontal alignment relative to the anchor point
+ */
+typedef enum MiniFBTextAlign {
+  AlignLeft = 0,
+  AlignCenter = 1,
+  AlignRight = 2,
+} MiniFBTextAlign;
+
+/*
+ Vertical alignment of the text block relative to the anchor point
+ */
+typedef enum MiniFBTextBaseline {
+  BaselineTop = 0,
+  BaselineMiddle = 1,
+  BaselineBottom = 2,
+} MiniFBTextBaseline;
+
+/*
+ Resampling filter used when blitting
+ */
+typedef enum MiniFBFilter {
+  FilterNearest = 0,
+  FilterBilinear = 1,
+} MiniFBFilter;
+
+/*
+ Layout of a source pixel buffer
+ */
+typedef enum MiniFBPixelFormat {
+  /*
+   One u8 luminance value per pixel
+   */
+  FormatGray8 = 0,
+  /*
+   Four u8 per pixel: red, green, blue, alpha (alpha ignored)
+   */
+  FormatRgba8 = 1,
+  /*
+   Four u8 per pixel: blue, green, red, alpha (alpha ignored)
+   */
+  FormatBgra8 = 2,
+  /*
+   One f32 per pixel, mapped linearly from [range_min, range_max] to black-white
+   */
+  FormatGrayF32 = 3,
+  /*
+   Three f32 per pixel (red, green, blue), exposure-scaled and tone mapped
+   */
+  FormatRgbF32 = 4,
+} MiniFBPixelFormat;
+
+/*
+ Tone mapping operator for `FormatRgbF32`
+ */
+typedef enum MiniFBToneMap {
+  /*
+   Clamp to [0, 1]
+   */
+  ToneMapClamp = 0,
+  /*
+   x / (1 + x)
+   */
+  ToneMapReinhard = 1,
+  /*
+   Narkowicz's fit of the ACES filmic curve
+   */
+  ToneMapAces = 2,
+} MiniFBToneMap;
+
+/*
+ Gradient rendering mode
+ */
+typedef enum MiniFBGradientMode {
+  /*
+   Hue encodes gradient direction, brightness its (clamped) magnitude
+   */
+  GradientHue = 0,
+  /*
+   Diverging map of `|grad| - 1`: white is exact, blue under, red over
+   */
+  GradientMagnitude = 1,
+  /*
+   Arrow glyphs on a sparse grid, drawn over the existing canvas contents
+   */
+  GradientArrows = 2,
+} MiniFBGradientMode;
+
+/*
+ Where a mapped mouse position lies relative to the image
+ */
+typedef enum MiniFBMouseBufferPos {
+  /*
+   No mouse position available
+   */
+  MouseUnavailable = 0,
+  /*
+   Position is over the image
+   */
+  MouseInside = 1,
+  /*
+   Position is over a bar or outside the window
+   */
+  MouseOutside = 2,
+} MiniFBMouseBufferPos;
+
+/*
+ Pattern drawn inside a polygon
+ */
+typedef enum MiniFBPatternKind {
+  /*
+   Parallel lines at `angle`
+   */
+  PatternHatch = 0,
+  /*
+   Two sets of lines, at `angle` and `angle + 90`
+   */
+  PatternCrossHatch = 1,
+  /*
+   Dots on a square grid rotated by `angle`
+   */
+  PatternDots = 2,
+  /*
+   `image` repeated on a grid rotated by `angle`, one tile `spacing` wide
+   */
+  PatternImage = 3,
+} MiniFBPatternKind;
+
+/*
+ Unit of stroke widths and dash lengths
+ */
+typedef enum MiniFBStrokeUnits {
+  /*
+   Screen pixels, independent of the camera zoom
+   */
+  UnitsPixels = 0,
+  /*
+   World units, scaled with the camera
+   */
+  UnitsWorld = 1,
+} MiniFBStrokeUnits;
+
+/*
+ Shape drawn where two segments meet
+ */
+typedef enum MiniFBLineJoin {
+  JoinMiter = 0,
+  JoinRound = 1,
+  JoinBevel = 2,
+} MiniFBLineJoin;
+
+/*
+ Shape drawn at the ends of open polylines and dashes
+ */
+typedef enum MiniFBLineCap {
+  CapButt = 0,
+  CapRound = 1,
+  CapSquare = 2,
+} MiniFBLineCap;
+
+/*
+ Terminal graphics mode
+ */
+typedef enum MiniFBTerminalMode {
+  /*
+   Sixel if the terminal reports support for it, half-blocks otherwise
+   */
+  TerminalAuto = 0,
+  /*
+   `▀` characters with 24-bit colors, two pixels per cell
+   */
+  TerminalHalfBlocks = 1,
+  /*
+   Sixel graphics at the terminal's pixel resolution
+   */
+  TerminalSixel = 2,
+} MiniFBTerminalMode;
+
+/*
+ Opaque accumulation buffer
+ */
+typedef struct MiniFBAccumulator MiniFBAccumulator;
+
+/*
+ Opaque list of screen-space primitives awaiting rasterization
+ */
+typedef struct MiniFBBatch MiniFBBatch;
+
+/*
+ Opaque camera handle
+ */
+typedef struct MiniFBCamera MiniFBCamera;
+
+/*
+ Opaque Rust-owned framebuffer in minifb's 0RGB u32 layout
+ */
+typedef struct MiniFBCanvas MiniFBCanvas;
+
+/*
+ Opaque retained list of world-space drawing items
+ */
+typedef struct MiniFBDisplayList MiniFBDisplayList;
+
+/*
+ Opaque font handle
+ */
+typedef struct MiniFBFont MiniFBFont;
+
+/*
+ Opaque frame client handle
+ */
+typedef struct MiniFBFrameClient MiniFBFrameClient;
+
+/*
+ Opaque frame server handle
+ */
+typedef struct MiniFBFrameServer MiniFBFrameServer;
+
+/*
+ Opaque Rust-owned image
+ */
+typedef struct MiniFBImage MiniFBImage;
+
+/*
+ Opaque, thread-safe handle to a render thread
+ */
+typedef struct MiniFBRenderThread MiniFBRenderThread;
+
 /*
  Opaque window handle
  */
 typedef struct MiniFBWindow MiniFBWindow;
 
+/*
+ Opaque, thread-safe handle for sending requests to a window
+ */
+typedef struct MiniFBWindowProxy MiniFBWindowProxy;
+
 /*
  Window creation options
  */
@@ -170,6 +436,484 @@ typedef struct MiniFBWindowOptions {
   bool transparency;
 } MiniFBWindowOptions;
 
+/*
+ Onion-skin drawing options
+ */
+typedef struct MiniFBOnionOptions {
+  /*
+   Number of previous frames to draw, at most the history length
+   */
+  uintptr_t frames;
+  /*
+   Opacity of the most recent previous frame
+   */
+  float opacity;
+  /*
+   Opacity multiplier for each step further into the past
+   */
+  float falloff;
+  /*
+   0RGB tint mixed into past frames
+   */
+  uint32_t tint;
+  /*
+   Amount of tint, 0.0 keeps the original colors
+   */
+  float tint_strength;
+  /*
+   Pixels of this color count as empty: they are not drawn from past
+   frames, and past frames only show where the current frame has it
+   */
+  uint32_t background;
+} MiniFBOnionOptions;
+
+/*
+ 2D affine transform, field-for-field compatible with `Matrix.t`
+ */
+typedef struct MiniFBTransform {
+  double m11;
+  double m12;
+  double m21;
+  double m22;
+  double m31;
+  double m32;
+} MiniFBTransform;
+
+/*
+ Straight (non-premultiplied) 8-bit sRGB color with alpha
+ */
+typedef struct MiniFBColor {
+  uint8_t r;
+  uint8_t g;
+  uint8_t b;
+  uint8_t a;
+} MiniFBColor;
+
+/*
+ Layer compositing options
+ */
+typedef struct MiniFBLayerOptions {
+  enum MiniFBBlendMode blend;
+  /*
+   Multiplied with the coverage and the color's own alpha
+   */
+  float opacity;
+  /*
+   Blend in linear light instead of sRGB
+   */
+  bool linear_light;
+} MiniFBLayerOptions;
+
+/*
+ Presentation counters for one window
+ */
+typedef struct MiniFBDirtyStats {
+  /*
+   Frames whose pixels were uploaded
+   */
+  uint64_t frames_presented;
+  /*
+   Canvas presents skipped because nothing changed
+   */
+  uint64_t frames_skipped;
+  /*
+   Damaged region of the most recent uploaded frame
+   */
+  uintptr_t last_x;
+  uintptr_t last_y;
+  uintptr_t last_width;
+  uintptr_t last_height;
+  /*
+   Sum of damaged pixels over all uploaded frames
+   */
+  uint64_t total_dirty_pixels;
+} MiniFBDirtyStats;
+
+/*
+ Text drawing style
+ */
+typedef struct MiniFBTextStyle {
+  /*
+   0RGB text color
+   */
+  uint32_t color;
+  /*
+   0RGB color of the box drawn behind the text
+   */
+  uint32_t background;
+  /*
+   Opacity of the background box, 0.0 disables it
+   */
+  float background_opacity;
+  /*
+   Padding around the text inside the background box, in pixels
+   */
+  int32_t padding;
+  enum MiniFBTextAlign align;
+  enum MiniFBTextBaseline baseline;
+} MiniFBTextStyle;
+
+/*
+ Image blit options
+ */
+typedef struct MiniFBBlitOptions {
+  enum MiniFBFilter filter;
+  /*
+   Multiplied with the image's own alpha
+   */
+  float opacity;
+  /*
+   Honor the image alpha channel; when false the image is treated as opaque
+   */
+  bool use_alpha;
+} MiniFBBlitOptions;
+
+/*
+ Source format description and conversion parameters
+ */
+typedef struct MiniFBPixelOptions {
+  enum MiniFBPixelFormat format;
+  /*
+   Value range for `FormatGrayF32`
+   */
+  float range_min;
+  float range_max;
+  /*
+   Multiplier applied to `FormatRgbF32` values before tone mapping
+   */
+  float exposure;
+  enum MiniFBToneMap tone_map;
+  /*
+   `FormatRgbF32` values are linear light and get sRGB-encoded
+   */
+  bool linear;
+} MiniFBPixelOptions;
+
+/*
+ Input state of a surface as seen by a client
+ */
+typedef struct MiniFBSurfaceInput {
+  /*
+   False once the user closed the surface's window
+   */
+  bool open;
+  bool has_mouse;
+  float mouse_x;
+  float mouse_y;
+  /*
+   Held buttons: bit 0 left, bit 1 middle, bit 2 right
+   */
+  uint32_t mouse_buttons;
+  /*
+   Scrolling since the previous input query
+   */
+  float scroll_x;
+  float scroll_y;
+  uint32_t key_count;
+  /*
+   Held keys as MiniFBKey codes
+   */
+  int32_t keys[MINIFB_SURFACE_MAX_KEYS];
+} MiniFBSurfaceInput;
+
+/*
+ Gradient rendering options
+ */
+typedef struct MiniFBGradientOptions {
+  enum MiniFBGradientMode mode;
+  /*
+   World-space distance between adjacent samples
+   */
+  float spacing;
+  /*
+   Deviation from 1.0 at which the magnitude map saturates
+   */
+  float tolerance;
+  /*
+   Distance between arrow glyphs, in pixels
+   */
+  uintptr_t arrow_spacing;
+  /*
+   0RGB color of arrow glyphs
+   */
+  uint32_t arrow_color;
+} MiniFBGradientOptions;
+
+/*
+ Grid overlay options
+ */
+typedef struct MiniFBGridOptions {
+  /*
+   Minimum distance between minor grid lines, in pixels
+   */
+  float min_spacing;
+  uint32_t minor_color;
+  uint32_t major_color;
+  uint32_t axis_color;
+  uint32_t label_color;
+  uint32_t ruler_color;
+  /*
+   Thickness of the rulers along the top and left edges, 0 hides them
+   */
+  uint32_t ruler_size;
+  bool show_grid;
+  bool show_axes;
+  bool show_labels;
+} MiniFBGridOptions;
+
+/*
+ Letterbox presentation settings
+ */
+typedef struct MiniFBLetterbox {
+  bool enabled;
+  /*
+   Only scale up by whole factors, keeping pixels square and sharp;
+   buffers larger than the window are still scaled down to fit
+   */
+  bool integer_scale;
+  /*
+   0RGB color of the bars around the image
+   */
+  uint32_t bar_color;
+} MiniFBLetterbox;
+
+/*
+ Pattern fill style
+ */
+typedef struct MiniFBPattern {
+  enum MiniFBPatternKind kind;
+  /*
+   0RGB color of lines and dots
+   */
+  uint32_t color;
+  float opacity;
+  /*
+   0RGB color laid under the pattern
+   */
+  uint32_t background;
+  /*
+   Opacity of the background, 0.0 leaves the canvas showing through
+   */
+  float background_opacity;
+  /*
+   Rotation in degrees
+   */
+  float angle;
+  /*
+   Distance between lines, dots or tiles, in world units
+   */
+  float spacing;
+  /*
+   Hatch line width, in world units
+   */
+  float line_width;
+  /*
+   Dot radius, in world units
+   */
+  float dot_radius;
+  /*
+   Tile for `PatternImage`; only read during the drawing call
+   */
+  const struct MiniFBImage *image;
+} MiniFBPattern;
+
+/*
+ Input state published by a render thread
+ */
+typedef struct MiniFBInputSnapshot {
+  /*
+   False once the window was closed, by the user or on request
+   */
+  bool open;
+  bool has_mouse;
+  float mouse_x;
+  float mouse_y;
+  /*
+   Held buttons: bit 0 left, bit 1 middle, bit 2 right
+   */
+  uint32_t mouse_buttons;
+  /*
+   Scrolling since the previous read
+   */
+  float scroll_x;
+  float scroll_y;
+  /*
+   Held keys as MiniFBKey codes
+   */
+  uint32_t key_count;
+  int32_t keys[MINIFB_SNAPSHOT_MAX_KEYS];
+  /*
+   Keys pressed since the previous read, not counting repeats
+   */
+  uint32_t pressed_count;
+  int32_t pressed[MINIFB_SNAPSHOT_MAX_KEYS];
+  /*
+   Keys released since the previous read
+   */
+  uint32_t released_count;
+  int32_t released[MINIFB_SNAPSHOT_MAX_KEYS];
+  /*
+   Window updates so far
+   */
+  uint64_t frame;
+  /*
+   Frames that were replaced in the mailbox before being shown
+   */
+  uint64_t frames_dropped;
+} MiniFBInputSnapshot;
+
+/*
+ Run loop settings
+ */
+typedef struct MiniFBRunConfig {
+  /*
+   Fixed update callbacks per second
+   */
+  double update_rate;
+  /*
+   Most render callbacks per second, 0 for no limit
+   */
+  double max_fps;
+  /*
+   Most update callbacks between two renders, at least 1; further
+   updates are skipped
+   */
+  uint32_t max_updates_per_frame;
+} MiniFBRunConfig;
+
+/*
+ Timing passed to run loop callbacks
+ */
+typedef struct MiniFBFrameTiming {
+  /*
+   Seconds to advance by: the fixed step in updates, the time since the
+   previous frame in renders
+   */
+  double dt;
+  /*
+   Seconds since the loop started: simulated time after this step in
+   updates, wall time in renders
+   */
+  double time;
+  /*
+   Update callbacks so far, counting the current one
+   */
+  uint64_t update_count;
+  /*
+   Frames so far, counting the current one
+   */
+  uint64_t frame_count;
+  /*
+   In renders, how far into the next update step the simulation is
+   (0 to 1), for interpolating between the last two states
+   */
+  double alpha;
+  /*
+   Updates skipped so far because the loop fell too far behind
+   */
+  uint64_t skipped_updates;
+} MiniFBFrameTiming;
+
+/*
+ Fixed-rate update callback; return false to stop the loop
+ */
+typedef bool (*MiniFBUpdateCallback)(struct MiniFBWindow *window,
+                                     const struct MiniFBFrameTiming *timing,
+                                     void *user_data);
+
+/*
+ Per-frame render callback; return false to stop the loop
+
+ It normally presents a frame (`minifb_window_update_with_buffer` and
+ friends); if it doesn't, the loop updates the window itself.
+ */
+typedef bool (*MiniFBRenderCallback)(struct MiniFBWindow *window,
+                                     const struct MiniFBFrameTiming *timing,
+                                     void *user_data);
+
+/*
+ Stroke style
+ */
+typedef struct MiniFBStrokeStyle {
+  uint32_t color;
+  float opacity;
+  float width;
+  enum MiniFBStrokeUnits units;
+  enum MiniFBLineJoin join;
+  enum MiniFBLineCap cap;
+  /*
+   Miter joins longer than `miter_limit * width` fall back to bevels
+   */
+  float miter_limit;
+  /*
+   Alternating dash and gap lengths (repeated twice when odd, as in SVG);
+   only read during the drawing call, may be null when `dash_count` is 0
+   */
+  const float *dash;
+  uintptr_t dash_count;
+  /*
+   Distance into the dash pattern at which the stroke starts
+   */
+  float dash_offset;
+  bool antialias;
+} MiniFBStrokeStyle;
+
+/*
+ SVG export options
+ */
+typedef struct MiniFBSvgOptions {
+  /*
+   viewBox in world units; a non-positive width or height, or any
+   non-finite value, fits the bounds of all items instead
+   */
+  double min_x;
+  double min_y;
+  double width;
+  double height;
+  /*
+   Font size of text elements, in world units
+   */
+  double font_size;
+  /*
+   Export items on hidden layers too (inside `display="none"` groups)
+   */
+  bool include_hidden;
+} MiniFBSvgOptions;
+
+/*
+ Terminal window options
+ */
+typedef struct MiniFBTerminalOptions {
+  enum MiniFBTerminalMode mode;
+  /*
+   Limit the image to this many columns and rows, 0 for the whole terminal
+   */
+  uintptr_t max_columns;
+  uintptr_t max_rows;
+  /*
+   Enable mouse reporting (motion, buttons and wheel)
+   */
+  bool mouse;
+} MiniFBTerminalOptions;
+
+/*
+ VNC server options
+ */
+typedef struct MiniFBVncOptions {
+  /*
+   TCP port, 0 to let the system pick one
+   */
+  uint16_t port;
+  /*
+   Listen on 127.0.0.1 only instead of all interfaces
+   */
+  bool localhost_only;
+  /*
+   Feed client key and pointer events into the window; false is view-only
+   */
+  bool allow_input;
+} MiniFBVncOptions;
+
 /*
  Get the last error message, or null if no error
  The returned pointer is valid until the next minifb call
@@ -184,6 +928,11 @@ struct MiniFBWindowOptions minifb_window_options_default(void);
 /*
  Create a new window
 
+ With the environment variable `MINIFB_FFI_BACKEND=terminal` the window is
+ drawn in the terminal instead (see `minifb_window_new_terminal`), and with
+ `MINIFB_FFI_BACKEND=headless` it has no display at all (see
+ `minifb_window_new_headless`).
+
  # Safety
  - `name` must be a valid null-terminated C string
  - `opts` must be a valid pointer to MiniFBWindowOptions
@@ -347,6 +1096,48 @@ bool minifb_window_get_mouse_down(const struct MiniFBWindow *window, enum MiniFB
  */
 bool minifb_window_get_scroll_wheel(const struct MiniFBWindow *window, float *out_x, float *out_y);
 
+/*
+ Inject a key press or release
+
+ # Safety
+ - `window` must be a valid pointer returned by one of the window constructors
+ */
+void minifb_window_inject_key(struct MiniFBWindow *window, enum MiniFBKey key, bool down);
+
+/*
+ Inject a mouse move to window coordinates
+
+ # Safety
+ - `window` must be a valid pointer returned by one of the window constructors
+ */
+void minifb_window_inject_mouse_move(struct MiniFBWindow *window, float x, float y);
+
+/*
+ Inject the mouse leaving the window, so no position is reported
+
+ # Safety
+ - `window` must be a valid pointer returned by one of the window constructors
+ */
+void minifb_window_inject_mouse_leave(struct MiniFBWindow *window);
+
+/*
+ Inject a mouse button press or release
+
+ # Safety
+ - `window` must be a valid pointer returned by one of the window constructors
+ */
+void minifb_window_inject_button(struct MiniFBWindow *window,
+                                 enum MiniFBMouseButton button,
+                                 bool down);
+
+/*
+ Inject scroll wheel movement, added to any real scrolling in the same frame
+
+ # Safety
+ - `window` must be a valid pointer returned by one of the window constructors
+ */
+void minifb_window_inject_scroll(struct MiniFBWindow *window, float dx, float dy);
+
 /*
  Set the window position
 
@@ -390,4 +1181,1483 @@ void minifb_window_set_cursor_visibility(struct MiniFBWindow *window, bool visib
  */
 void minifb_window_set_cursor_style(struct MiniFBWindow *window, enum MiniFBCursorStyle style);
 
+/*
+ Create default onion-skin options (4 frames, reddish tint, black background)
+ */
+struct MiniFBOnionOptions minifb_onion_options_default(void);
+
+/*
+ Create an accumulation buffer keeping up to `history_len` frames for onion skinning
+
+ Returns null if either dimension is zero or the buffer can't be allocated
+ */
+struct MiniFBAccumulator *minifb_accum_new(uintptr_t width,
+                                           uintptr_t height,
+                                           uintptr_t history_len);
+
+/*
+ Free an accumulation buffer
+
+ # Safety
+ - `accum` must be a valid pointer returned by `minifb_accum_new`, or null
+ - After calling this function, the pointer is invalid and must not be used
+ */
+void minifb_accum_free(struct MiniFBAccumulator *accum);
+
+/*
+ Reset the sum and total weight to zero and forget the frame history
+
+ # Safety
+ - `accum` must be a valid pointer returned by `minifb_accum_new`
+ */
+void minifb_accum_clear(struct MiniFBAccumulator *accum);
+
+/*
+ Add a canvas to the sum with a weight
+
+ # Safety
+ - `accum` must be a valid pointer returned by `minifb_accum_new`
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+
+ Returns 0 on success, -1 if the sizes differ
+ */
+int32_t minifb_accum_add(struct MiniFBAccumulator *accum,
+                         const struct MiniFBCanvas *canvas,
+                         float weight);
+
+/*
+ Multiply the sum and total weight by `factor`
+
+ Calling this before every `minifb_accum_add` turns the buffer into an
+ exponential moving average; 0.9 leaves a trail of roughly ten frames.
+
+ # Safety
+ - `accum` must be a valid pointer returned by `minifb_accum_new`
+ */
+void minifb_accum_decay(struct MiniFBAccumulator *accum, float factor);
+
+/*
+ Get the total weight added so far (after decay)
+
+ # Safety
+ - `accum` must be a valid pointer returned by `minifb_accum_new`
+ */
+float minifb_accum_get_weight(const struct MiniFBAccumulator *accum);
+
+/*
+ Write the accumulated image into a canvas
+
+ # Safety
+ - `accum` must be a valid pointer returned by `minifb_accum_new`
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+
+ Returns 0 on success, -1 if the sizes differ
+ */
+int32_t minifb_accum_resolve(const struct MiniFBAccumulator *accum,
+                             struct MiniFBCanvas *canvas,
+                             enum MiniFBAccumResolve mode);
+
+/*
+ Remember a copy of a canvas as the most recent onion-skin frame
+
+ # Safety
+ - `accum` must be a valid pointer returned by `minifb_accum_new`
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+
+ Returns 0 on success, -1 if the sizes differ
+ */
+int32_t minifb_accum_push_frame(struct MiniFBAccumulator *accum, const struct MiniFBCanvas *canvas);
+
+/*
+ Draw remembered frames behind the current canvas contents with fading tints
+
+ # Safety
+ - `accum` must be a valid pointer returned by `minifb_accum_new`
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+ - `opts` must be a valid pointer to MiniFBOnionOptions
+
+ Returns 0 on success, -1 if the sizes differ
+ */
+int32_t minifb_accum_draw_onion(const struct MiniFBAccumulator *accum,
+                                struct MiniFBCanvas *canvas,
+                                const struct MiniFBOnionOptions *opts);
+
+/*
+ Create a window without any display
+
+ Headless windows behave like native ones of a fixed size that never
+ close: frames can be presented and are shown to VNC clients (see
+ `minifb_window_serve_vnc`), and input comes from those clients. Setting
+ the environment variable `MINIFB_FFI_BACKEND=headless` makes
+ `minifb_window_new` create headless windows.
+
+ Returns null if either dimension is zero
+ */
+struct MiniFBWindow *minifb_window_new_headless(uintptr_t width, uintptr_t height);
+
+/*
+ Create a camera with the identity transform (world units are pixels)
+ */
+struct MiniFBCamera *minifb_camera_new(void);
+
+/*
+ Free a camera
+
+ # Safety
+ - `camera` must be a valid pointer returned by `minifb_camera_new`, or null
+ - After calling this function, the pointer is invalid and must not be used
+ */
+void minifb_camera_free(struct MiniFBCamera *camera);
+
+/*
+ Set the world-to-screen transform
+
+ # Safety
+ - `camera` must be a valid pointer returned by `minifb_camera_new`
+ - `transform` must be a valid pointer
+
+ Returns false (leaving the camera unchanged) if the transform is not invertible
+ */
+bool minifb_camera_set_transform(struct MiniFBCamera *camera,
+                                 const struct MiniFBTransform *transform);
+
+/*
+ Get the world-to-screen transform
+
+ # Safety
+ - `camera` must be a valid pointer returned by `minifb_camera_new`
+ - `out` must be a valid pointer
+ */
+void minifb_camera_get_transform(const struct MiniFBCamera *camera, struct MiniFBTransform *out);
+
+/*
+ Get the screen-to-world transform
+
+ # Safety
+ - `camera` must be a valid pointer returned by `minifb_camera_new`
+ - `out` must be a valid pointer
+ */
+void minifb_camera_get_inverse(const struct MiniFBCamera *camera, struct MiniFBTransform *out);
+
+/*
+ Map a world-space point to screen pixels
+
+ # Safety
+ - `camera` must be a valid pointer returned by `minifb_camera_new`
+ - `out_x` and `out_y` must be valid pointers
+ */
+void minifb_camera_world_to_screen(const struct MiniFBCamera *camera,
+                                   double x,
+                                   double y,
+                                   double *out_x,
+                                   double *out_y);
+
+/*
+ Map a screen pixel position to world space
+
+ # Safety
+ - `camera` must be a valid pointer returned by `minifb_camera_new`
+ - `out_x` and `out_y` must be valid pointers
+ */
+void minifb_camera_screen_to_world(const struct MiniFBCamera *camera,
+                                   double x,
+                                   double y,
+                                   double *out_x,
+                                   double *out_y);
+
+/*
+ Pan the view by a screen-space offset in pixels
+
+ # Safety
+ - `camera` must be a valid pointer returned by `minifb_camera_new`
+ */
+void minifb_camera_pan(struct MiniFBCamera *camera, double dx, double dy);
+
+/*
+ Zoom by `factor` around the screen point (x, y)
+
+ # Safety
+ - `camera` must be a valid pointer returned by `minifb_camera_new`
+ */
+void minifb_camera_zoom_at(struct MiniFBCamera *camera, double factor, double x, double y);
+
+/*
+ Set the zoom multiplier per scroll wheel unit used by `minifb_camera_handle_input`
+ (0.1 means each unit zooms by 10%)
+
+ # Safety
+ - `camera` must be a valid pointer returned by `minifb_camera_new`
+ */
+void minifb_camera_set_zoom_speed(struct MiniFBCamera *camera, float speed);
+
+/*
+ Fit the world rectangle (min_x, min_y)-(max_x, max_y) into a
+ `width` x `height` viewport, keeping `padding` pixels free on each side
+
+ # Safety
+ - `camera` must be a valid pointer returned by `minifb_camera_new`
+
+ Returns false (leaving the camera unchanged) for empty rectangles
+ */
+bool minifb_camera_fit(struct MiniFBCamera *camera,
+                       double min_x,
+                       double min_y,
+                       double max_x,
+                       double max_y,
+                       uintptr_t width,
+                       uintptr_t height,
+                       double padding);
+
+/*
+ Get the world-space bounding box visible in a `width` x `height` viewport
+
+ # Safety
+ - `camera` must be a valid pointer returned by `minifb_camera_new`
+ - all `out_*` pointers must be valid
+ */
+void minifb_camera_get_visible_rect(const struct MiniFBCamera *camera,
+                                    uintptr_t width,
+                                    uintptr_t height,
+                                    double *out_min_x,
+                                    double *out_min_y,
+                                    double *out_max_x,
+                                    double *out_max_y);
+
+/*
+ Apply this frame's window input to the camera: dragging with `pan_button`
+ held pans, the scroll wheel zooms around the cursor
+
+ Call once per frame after `minifb_window_update`.
+
+ # Safety
+ - `camera` must be a valid pointer returned by `minifb_camera_new`
+ - `window` must be a valid pointer returned by `minifb_window_new`
+
+ Returns true if the view changed
+ */
+bool minifb_camera_handle_input(struct MiniFBCamera *camera,
+                                const struct MiniFBWindow *window,
+                                enum MiniFBMouseButton pan_button);
+
+/*
+ Create a canvas of the given size, cleared to black
+
+ Returns null if either dimension is zero or the pixels can't be allocated
+ */
+struct MiniFBCanvas *minifb_canvas_new(uintptr_t width, uintptr_t height);
+
+/*
+ Free a canvas
+
+ # Safety
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`, or null
+ - After calling this function, the pointer is invalid and must not be used
+ */
+void minifb_canvas_free(struct MiniFBCanvas *canvas);
+
+/*
+ Get a pointer to the canvas pixels (`width * height` u32 values, row-major)
+
+ Writes through the pointer can't be tracked, so from now on every
+ present uploads the whole canvas, even if nothing seems to have changed.
+ `minifb_canvas_get_dirty` only reports them once marked with
+ `minifb_canvas_mark_dirty`.
+
+ # Safety
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+ - The returned pointer is valid until the canvas is freed
+ */
+uint32_t *minifb_canvas_get_buffer(struct MiniFBCanvas *canvas);
+
+/*
+ Get the canvas size
+
+ # Safety
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+ - `out_width` and `out_height` must be valid pointers
+ */
+void minifb_canvas_get_size(const struct MiniFBCanvas *canvas,
+                            uintptr_t *out_width,
+                            uintptr_t *out_height);
+
+/*
+ Fill the whole canvas with one 0RGB color
+
+ # Safety
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+ */
+void minifb_canvas_clear(struct MiniFBCanvas *canvas, uint32_t color);
+
+/*
+ Update the window with the contents of a canvas
+
+ If the window already shows this canvas and nothing was drawn since, the
+ upload is skipped and only window events are processed. Canvases whose
+ buffer was handed out by `minifb_canvas_get_buffer` are always uploaded.
+
+ # Safety
+ - `window` must be a valid pointer returned by `minifb_window_new`
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+
+ Returns 0 on success, -1 on error
+ */
+int32_t minifb_window_update_with_canvas(struct MiniFBWindow *window, struct MiniFBCanvas *canvas);
+
+/*
+ Parse a CSS color string
+
+ # Safety
+ - `s` must be a valid null-terminated C string
+ - `out` must be a valid pointer
+
+ Returns true on success; `out` is left untouched on failure
+ */
+bool minifb_color_parse(const char *s, struct MiniFBColor *out);
+
+/*
+ Create default layer options (normal blending, opaque, sRGB space)
+ */
+struct MiniFBLayerOptions minifb_layer_options_default(void);
+
+/*
+ Convert a sampled signed distance field into an anti-aliased coverage mask
+
+ Negative distances are inside. `spacing` is the world-space size of one
+ sample, so edges get roughly one pixel of anti-aliasing.
+
+ # Safety
+ - `field` and `out_coverage` must each point to at least `width * height` f32 values
+
+ Returns 0 on success, -1 on error (see `minifb_get_last_error`)
+ */
+int32_t minifb_field_to_coverage(const float *field,
+                                 uintptr_t width,
+                                 uintptr_t height,
+                                 float spacing,
+                                 float *out_coverage);
+
+/*
+ Composite a layer's coverage mask onto a canvas in a CSS color
+
+ # Safety
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+ - `coverage` must point to at least `width * height` f32 values in 0.0-1.0
+ - `color` must be a valid null-terminated C string
+ - `opts` must be a valid pointer to MiniFBLayerOptions
+
+ The mask dimensions must match the canvas. Returns 0 on success, -1 on error
+ */
+int32_t minifb_canvas_composite_layer(struct MiniFBCanvas *canvas,
+                                      const float *coverage,
+                                      uintptr_t width,
+                                      uintptr_t height,
+                                      const char *color,
+                                      const struct MiniFBLayerOptions *opts);
+
+/*
+ Mark a region of the canvas as changed
+
+ Lets `minifb_canvas_get_dirty` report writes made through the pointer from
+ `minifb_canvas_get_buffer`; presents upload such canvases whole anyway.
+
+ # Safety
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+ */
+void minifb_canvas_mark_dirty(struct MiniFBCanvas *canvas,
+                              uintptr_t x,
+                              uintptr_t y,
+                              uintptr_t width,
+                              uintptr_t height);
+
+/*
+ Get the region changed since the canvas was last presented
+
+ # Safety
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+ - All out pointers must be valid
+
+ Returns false, leaving the outputs untouched, if nothing changed
+ */
+bool minifb_canvas_get_dirty(const struct MiniFBCanvas *canvas,
+                             uintptr_t *out_x,
+                             uintptr_t *out_y,
+                             uintptr_t *out_width,
+                             uintptr_t *out_height);
+
+/*
+ Get the window's presentation statistics
+
+ # Safety
+ - `window` must be a valid pointer returned by `minifb_window_new`
+ - `out_stats` must be a valid pointer
+ */
+void minifb_window_get_dirty_stats(const struct MiniFBWindow *window,
+                                   struct MiniFBDirtyStats *out_stats);
+
+/*
+ Reset the window's presentation statistics to zero
+
+ # Safety
+ - `window` must be a valid pointer returned by `minifb_window_new`
+ */
+void minifb_window_reset_dirty_stats(struct MiniFBWindow *window);
+
+/*
+ Create an empty display list
+ */
+struct MiniFBDisplayList *minifb_display_list_new(void);
+
+/*
+ Free a display list
+
+ # Safety
+ - `list` must be a valid pointer returned by `minifb_display_list_new`, or null
+ - After calling this function, the pointer is invalid and must not be used
+ */
+void minifb_display_list_free(struct MiniFBDisplayList *list);
+
+/*
+ Remove all items; layer visibility is kept
+
+ # Safety
+ - `list` must be a valid pointer returned by `minifb_display_list_new`
+ */
+void minifb_display_list_clear(struct MiniFBDisplayList *list);
+
+/*
+ Get the number of items in a display list
+
+ # Safety
+ - `list` must be a valid pointer returned by `minifb_display_list_new`
+ */
+uintptr_t minifb_display_list_len(const struct MiniFBDisplayList *list);
+
+/*
+ Show or hide every item on a layer (all layers start visible)
+
+ # Safety
+ - `list` must be a valid pointer returned by `minifb_display_list_new`
+ */
+void minifb_display_list_set_layer_visible(struct MiniFBDisplayList *list,
+                                           uint32_t layer,
+                                           bool visible);
+
+/*
+ Check whether a layer is visible
+
+ # Safety
+ - `list` must be a valid pointer returned by `minifb_display_list_new`
+ */
+bool minifb_display_list_is_layer_visible(const struct MiniFBDisplayList *list, uint32_t layer);
+
+/*
+ Add one-pixel segments stored as `x0, y0, x1, y1` per segment
+
+ # Safety
+ - `list` must be a valid pointer returned by `minifb_display_list_new`
+ - `points` must point to at least `4 * segment_count` f32 values
+ */
+void minifb_display_list_add_segments(struct MiniFBDisplayList *list,
+                                      uint32_t layer,
+                                      const float *points,
+                                      uintptr_t segment_count,
+                                      uint32_t color,
+                                      float opacity);
+
+/*
+ Add a one-pixel polyline through `count` points
+
+ # Safety
+ - `list` must be a valid pointer returned by `minifb_display_list_new`
+ - `points` must point to at least `2 * count` f32 values
+ */
+void minifb_display_list_add_polyline(struct MiniFBDisplayList *list,
+                                      uint32_t layer,
+                                      const float *points,
+                                      uintptr_t count,
+                                      bool closed,
+                                      uint32_t color,
+                                      float opacity);
+
+/*
+ Add a polygon filled with the even-odd rule (see `minifb_canvas_fill_polygon`)
+
+ # Safety
+ - `list` must be a valid pointer returned by `minifb_display_list_new`
+ - `ring_sizes` must point to `ring_count` point counts
+ - `points` must point to `2 * sum(ring_sizes)` f32 values, rings back to back
+ */
+void minifb_display_list_add_polygon(struct MiniFBDisplayList *list,
+                                     uint32_t layer,
+                                     const float *points,
+                                     const uintptr_t *ring_sizes,
+                                     uintptr_t ring_count,
+                                     uint32_t color,
+                                     float opacity);
+
+/*
+ Add text anchored at a world-space point
+
+ # Safety
+ - `list` must be a valid pointer returned by `minifb_display_list_new`
+ - `text` must be a valid null-terminated UTF-8 C string
+ - `style` must be a valid pointer to MiniFBTextStyle
+
+ Returns 0 on success, -1 on error
+ */
+int32_t minifb_display_list_add_text(struct MiniFBDisplayList *list,
+                                     uint32_t layer,
+                                     const char *text,
+                                     double x,
+                                     double y,
+                                     const struct MiniFBTextStyle *style);
+
+/*
+ Add an image covering a world-space rectangle
+
+ The list keeps its own copy of the pixels, so `image` may be freed after
+ this call.
+
+ # Safety
+ - `list` must be a valid pointer returned by `minifb_display_list_new`
+ - `image` must be a valid pointer returned by `minifb_image_load` or `minifb_image_decode`
+ - `opts` must be a valid pointer to MiniFBBlitOptions
+ */
+void minifb_display_list_add_image(struct MiniFBDisplayList *list,
+                                   uint32_t layer,
+                                   const struct MiniFBImage *image,
+                                   double x,
+                                   double y,
+                                   double width,
+                                   double height,
+                                   const struct MiniFBBlitOptions *opts);
+
+/*
+ Render the visible layers of a display list into a canvas
+
+ # Safety
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+ - `list` must be a valid pointer returned by `minifb_display_list_new`
+ - `camera` must be a valid camera pointer, or null for pixel coordinates
+ - `font` must be a valid font pointer, or null for the built-in font at scale 1
+ */
+void minifb_canvas_draw_display_list(struct MiniFBCanvas *canvas,
+                                     const struct MiniFBDisplayList *list,
+                                     const struct MiniFBCamera *camera,
+                                     struct MiniFBFont *font);
+
+/*
+ Draw a one-pixel line
+
+ # Safety
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+ - `camera` must be a valid camera pointer, or null for pixel coordinates
+ */
+void minifb_canvas_draw_line(struct MiniFBCanvas *canvas,
+                             float x0,
+                             float y0,
+                             float x1,
+                             float y1,
+                             uint32_t color,
+                             const struct MiniFBCamera *camera);
+
+/*
+ Draw a one-pixel polyline through `count` points
+
+ # Safety
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+ - `points` must point to at least `2 * count` f32 values
+ - `camera` must be a valid camera pointer, or null for pixel coordinates
+ */
+void minifb_canvas_draw_polyline(struct MiniFBCanvas *canvas,
+                                 const float *points,
+                                 uintptr_t count,
+                                 bool closed,
+                                 uint32_t color,
+                                 const struct MiniFBCamera *camera);
+
+/*
+ Fill a polygon made of one or more rings using the even-odd rule, so
+ inner rings cut holes regardless of their orientation
+
+ # Safety
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+ - `ring_sizes` must point to `ring_count` point counts
+ - `points` must point to `2 * sum(ring_sizes)` f32 values, rings back to back
+ - `camera` must be a valid camera pointer, or null for pixel coordinates
+ */
+void minifb_canvas_fill_polygon(struct MiniFBCanvas *canvas,
+                                const float *points,
+                                const uintptr_t *ring_sizes,
+                                uintptr_t ring_count,
+                                uint32_t color,
+                                const struct MiniFBCamera *camera);
+
+/*
+ Start a frame server listening on a Unix socket path
+
+ A stale socket file at `path` is replaced. Requests are only handled
+ inside `minifb_frame_server_poll`, so surfaces' windows live on the
+ thread that polls.
+
+ # Safety
+ - `path` must be a valid null-terminated C string
+
+ Returns null on error (see `minifb_get_last_error`)
+ */
+struct MiniFBFrameServer *minifb_frame_server_new(const char *path);
+
+/*
+ Free a frame server, closing its surfaces and removing the socket file
+
+ # Safety
+ - `server` must be a valid pointer returned by `minifb_frame_server_new`, or null
+ - After calling this function, the pointer is invalid and must not be used
+ */
+void minifb_frame_server_free(struct MiniFBFrameServer *server);
+
+/*
+ Handle client requests and update all surfaces
+
+ Waits up to `timeout_ms` for the first request, so calling this in a loop
+ with about 16 ms gives a responsive viewer that is idle between frames.
+
+ # Safety
+ - `server` must be a valid pointer returned by `minifb_frame_server_new`
+
+ Returns the number of surfaces whose windows are still open
+ */
+uintptr_t minifb_frame_server_poll(struct MiniFBFrameServer *server, uint32_t timeout_ms);
+
+/*
+ Connect to a frame server
+
+ # Safety
+ - `path` must be a valid null-terminated C string
+
+ Returns null on error (see `minifb_get_last_error`)
+ */
+struct MiniFBFrameClient *minifb_frame_client_connect(const char *path);
+
+/*
+ Disconnect from a frame server; surfaces opened by the client stay up
+
+ # Safety
+ - `client` must be a valid pointer returned by `minifb_frame_client_connect`, or null
+ - After calling this function, the pointer is invalid and must not be used
+ */
+void minifb_frame_client_free(struct MiniFBFrameClient *client);
+
+/*
+ Open a surface (a window in the server process)
+
+ # Safety
+ - `client` must be a valid pointer returned by `minifb_frame_client_connect`
+ - `title` must be a valid null-terminated C string
+
+ Returns the surface id, or -1 on error
+ */
+int64_t minifb_frame_client_open(struct MiniFBFrameClient *client,
+                                 const char *title,
+                                 uint32_t width,
+                                 uint32_t height);
+
+/*
+ Show a frame in any supported pixel format on a surface
+
+ The pixels are converted to 0RGB in the server with the options given
+ here; frames of another size than the surface are stretched.
+
+ # Safety
+ - `client` must be a valid pointer returned by `minifb_frame_client_connect`
+ - `data` must point to `width * height` pixels of the format in `opts`
+ - `opts` must be a valid pointer to MiniFBPixelOptions
+
+ Returns 0 on success, -1 on error
+ */
+int32_t minifb_frame_client_push(struct MiniFBFrameClient *client,
+                                 uint32_t surface,
+                                 const void *data,
+                                 uint32_t width,
+                                 uint32_t height,
+                                 const struct MiniFBPixelOptions *opts);
+
+/*
+ Set a surface's window title
+
+ # Safety
+ - `client` must be a valid pointer returned by `minifb_frame_client_connect`
+ - `title` must be a valid null-terminated C string
+
+ Returns 0 on success, -1 on error
+ */
+int32_t minifb_frame_client_set_title(struct MiniFBFrameClient *client,
+                                      uint32_t surface,
+                                      const char *title);
+
+/*
+ Query a surface's keyboard and mouse state
+
+ # Safety
+ - `client` must be a valid pointer returned by `minifb_frame_client_connect`
+ - `out_input` must be a valid pointer
+
+ Returns 0 on success, -1 on error
+ */
+int32_t minifb_frame_client_get_input(struct MiniFBFrameClient *client,
+                                      uint32_t surface,
+                                      struct MiniFBSurfaceInput *out_input);
+
+/*
+ Close a surface and its window
+
+ # Safety
+ - `client` must be a valid pointer returned by `minifb_frame_client_connect`
+
+ Returns 0 on success, -1 on error
+ */
+int32_t minifb_frame_client_close(struct MiniFBFrameClient *client, uint32_t surface);
+
+/*
+ Create default gradient options (hue mode, unit spacing)
+ */
+struct MiniFBGradientOptions minifb_gradient_options_default(void);
+
+/*
+ Render the gradient of a sampled field into a canvas
+
+ # Safety
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+ - `field` must point to at least `width * height` f32 values
+ - `opts` must be a valid pointer to MiniFBGradientOptions
+
+ The field dimensions must match the canvas and `spacing` must be positive.
+ Returns 0 on success, -1 on error
+ */
+int32_t minifb_field_render_gradient(struct MiniFBCanvas *canvas,
+                                     const float *field,
+                                     uintptr_t width,
+                                     uintptr_t height,
+                                     const struct MiniFBGradientOptions *opts);
+
+/*
+ Create default grid options
+ */
+struct MiniFBGridOptions minifb_grid_options_default(void);
+
+/*
+ Draw a world-space grid with labeled axes and pixel rulers
+
+ # Safety
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+ - `camera` must be a valid camera pointer, or null for pixel coordinates
+ - `font` must be a valid font pointer, or null for the built-in font at scale 1
+ - `opts` must be a valid pointer to MiniFBGridOptions
+ */
+void minifb_canvas_draw_grid(struct MiniFBCanvas *canvas,
+                             const struct MiniFBCamera *camera,
+                             struct MiniFBFont *font,
+                             const struct MiniFBGridOptions *opts);
+
+/*
+ Create default blit options (bilinear, opaque, alpha honored)
+ */
+struct MiniFBBlitOptions minifb_blit_options_default(void);
+
+/*
+ Load a PNG, binary PPM (P6) or binary PGM (P5) file
+
+ # Safety
+ - `path` must be a valid null-terminated C string
+
+ Returns null on failure (see `minifb_get_last_error`)
+ */
+struct MiniFBImage *minifb_image_load(const char *path);
+
+/*
+ Decode an image from memory (same formats as `minifb_image_load`)
+
+ # Safety
+ - `data` must point to at least `len` bytes
+
+ Returns null on failure (see `minifb_get_last_error`)
+ */
+struct MiniFBImage *minifb_image_decode(const uint8_t *data, uintptr_t len);
+
+/*
+ Free an image
+
+ # Safety
+ - `image` must be a valid pointer returned by `minifb_image_load` or
+   `minifb_image_decode`, or null
+ - After calling this function, the pointer is invalid and must not be used
+ */
+void minifb_image_free(struct MiniFBImage *image);
+
+/*
+ Get the image size
+
+ # Safety
+ - `image` must be a valid image pointer
+ - `out_width` and `out_height` must be valid pointers
+ */
+void minifb_image_get_size(const struct MiniFBImage *image,
+                           uintptr_t *out_width,
+                           uintptr_t *out_height);
+
+/*
+ Copy the image into a 0RGB buffer, dropping the alpha channel
+
+ # Safety
+ - `image` must be a valid image pointer
+ - `out_buffer` must point to at least `width * height` u32 values
+ */
+void minifb_image_to_0rgb(const struct MiniFBImage *image, uint32_t *out_buffer);
+
+/*
+ Blit an image into the rectangle (x, y, width, height)
+
+ The rectangle is in world units when a camera is given and in pixels
+ otherwise, so a reference image can be pinned under world-space shapes.
+
+ # Safety
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+ - `image` must be a valid image pointer
+ - `camera` must be a valid camera pointer, or null
+ - `opts` must be a valid pointer to MiniFBBlitOptions
+ */
+void minifb_canvas_blit_image(struct MiniFBCanvas *canvas,
+                              const struct MiniFBImage *image,
+                              double x,
+                              double y,
+                              double width,
+                              double height,
+                              const struct MiniFBCamera *camera,
+                              const struct MiniFBBlitOptions *opts);
+
+/*
+ Create default letterbox settings (disabled, fractional scaling, black bars)
+ */
+struct MiniFBLetterbox minifb_letterbox_default(void);
+
+/*
+ Set letterbox presentation for all later updates of a window
+
+ Creating the window with `resize` enabled lets the user change its size
+ while the image keeps its aspect ratio.
+
+ # Safety
+ - `window` must be a valid pointer returned by `minifb_window_new`
+ - `letterbox` must be a valid pointer to MiniFBLetterbox
+ */
+void minifb_window_set_letterbox(struct MiniFBWindow *window,
+                                 const struct MiniFBLetterbox *letterbox);
+
+/*
+ Get the mouse position in pixels of the last presented buffer
+
+ Works in letterbox mode and with minifb's default stretching. The
+ coordinates are written even when the cursor is outside the image.
+
+ # Safety
+ - `window` must be a valid pointer returned by `minifb_window_new`
+ - `out_x` and `out_y` must be valid pointers
+ */
+enum MiniFBMouseBufferPos minifb_window_get_mouse_pos_buffer(const struct MiniFBWindow *window,
+                                                             float *out_x,
+                                                             float *out_y);
+
+/*
+ Create default pixel options for a format
+ */
+struct MiniFBPixelOptions minifb_pixel_options_default(enum MiniFBPixelFormat format);
+
+/*
+ Convert a buffer in any supported format to 0RGB
+
+ # Safety
+ - `data` must point to `width * height` pixels of the format in `opts`
+ - `opts` must be a valid pointer to MiniFBPixelOptions
+ - `out_buffer` must point to at least `width * height` u32 values
+ */
+void minifb_convert_pixels(const void *data,
+                           uintptr_t width,
+                           uintptr_t height,
+                           const struct MiniFBPixelOptions *opts,
+                           uint32_t *out_buffer);
+
+/*
+ Update the window with a buffer in any supported format
+
+ # Safety
+ - `window` must be a valid pointer returned by `minifb_window_new`
+ - `data` must point to `width * height` pixels of the format in `opts`
+ - `opts` must be a valid pointer to MiniFBPixelOptions
+
+ Returns 0 on success, -1 on error
+ */
+int32_t minifb_window_update_with_pixels(struct MiniFBWindow *window,
+                                         const void *data,
+                                         uintptr_t width,
+                                         uintptr_t height,
+                                         const struct MiniFBPixelOptions *opts);
+
+/*
+ Create the default pattern (white 45 degree hatch, 8 units apart, 1 unit wide)
+ */
+struct MiniFBPattern minifb_pattern_default(void);
+
+/*
+ Fill a polygon with a pattern, using the even-odd rule like
+ `minifb_canvas_fill_polygon`
+
+ # Safety
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+ - `ring_sizes` must point to `ring_count` point counts
+ - `points` must point to `2 * sum(ring_sizes)` f32 values, rings back to back
+ - `pattern` must be a valid pointer to MiniFBPattern; for `PatternImage`
+   its `image` must be a valid image pointer
+ - `camera` must be a valid camera pointer, or null for pixel coordinates
+ */
+void minifb_canvas_fill_polygon_pattern(struct MiniFBCanvas *canvas,
+                                        const float *points,
+                                        const uintptr_t *ring_sizes,
+                                        uintptr_t ring_count,
+                                        const struct MiniFBPattern *pattern,
+                                        const struct MiniFBCamera *camera);
+
+/*
+ Create a proxy for a window
+
+ Proxies stay valid after the window is freed; their requests fail from
+ then on.
+
+ # Safety
+ - `window` must be a valid pointer returned by `minifb_window_new`, used
+   on the thread that owns it
+
+ Returns null if the window is null
+ */
+struct MiniFBWindowProxy *minifb_window_proxy_new(struct MiniFBWindow *window);
+
+/*
+ Create another proxy for the same window, e.g. to hand to a new thread
+
+ # Safety
+ - `proxy` must be a valid pointer returned by `minifb_window_proxy_new`
+   or `minifb_window_proxy_clone`
+
+ Returns null if the proxy is null
+ */
+struct MiniFBWindowProxy *minifb_window_proxy_clone(const struct MiniFBWindowProxy *proxy);
+
+/*
+ Free a proxy; the window and other proxies are unaffected
+
+ # Safety
+ - `proxy` must be a valid pointer returned by `minifb_window_proxy_new`
+   or `minifb_window_proxy_clone`, or null
+ */
+void minifb_window_proxy_free(struct MiniFBWindowProxy *proxy);
+
+/*
+ Change the window title at its next update
+
+ Only the latest title queued before an update is shown.
+
+ # Safety
+ - `proxy` must be a valid proxy pointer
+ - `title` must be a valid null-terminated C string
+
+ Returns 0 on success, -1 if the window was freed
+ */
+int32_t minifb_window_proxy_set_title(const struct MiniFBWindowProxy *proxy, const char *title);
+
+/*
+ Close the window at its next update
+
+ From then on `minifb_window_is_open` returns false; the owning thread
+ still has to free the window.
+
+ # Safety
+ - `proxy` must be a valid proxy pointer
+
+ Returns 0 on success, -1 if the window was freed
+ */
+int32_t minifb_window_proxy_request_close(const struct MiniFBWindowProxy *proxy);
+
+/*
+ Send a user-defined message, delivered at the window's next update
+
+ The owning thread receives it with `minifb_window_take_message`.
+
+ # Safety
+ - `proxy` must be a valid proxy pointer
+
+ Returns 0 on success, -1 if the window was freed
+ */
+int32_t minifb_window_proxy_send(const struct MiniFBWindowProxy *proxy, uint64_t message);
+
+/*
+ Take the oldest message delivered by an update
+
+ # Safety
+ - `window` must be a valid pointer returned by `minifb_window_new`
+ - `out_message` must be a valid pointer
+
+ Returns true and writes the message if there was one
+ */
+bool minifb_window_take_message(struct MiniFBWindow *window, uint64_t *out_message);
+
+/*
+ Create an empty batch
+ */
+struct MiniFBBatch *minifb_batch_new(void);
+
+/*
+ Free a batch
+
+ # Safety
+ - `batch` must be a valid pointer returned by `minifb_batch_new`, or null
+ - After calling this function, the pointer is invalid and must not be used
+ */
+void minifb_batch_free(struct MiniFBBatch *batch);
+
+/*
+ Remove all primitives from a batch, keeping its allocation
+
+ # Safety
+ - `batch` must be a valid pointer returned by `minifb_batch_new`
+ */
+void minifb_batch_clear(struct MiniFBBatch *batch);
+
+/*
+ Add one-pixel segments, stored as `x0, y0, x1, y1` per segment as in a
+ `Line_buffer.t`
+
+ # Safety
+ - `batch` must be a valid pointer returned by `minifb_batch_new`
+ - `points` must point to at least `4 * segment_count` f32 values
+ - `camera` must be a valid camera pointer, or null for pixel coordinates
+ */
+void minifb_batch_add_segments(struct MiniFBBatch *batch,
+                               const float *points,
+                               uintptr_t segment_count,
+                               uint32_t color,
+                               float opacity,
+                               const struct MiniFBCamera *camera);
+
+/*
+ Add a polygon filled with the even-odd rule (see `minifb_canvas_fill_polygon`)
+
+ # Safety
+ - `batch` must be a valid pointer returned by `minifb_batch_new`
+ - `ring_sizes` must point to `ring_count` point counts
+ - `points` must point to `2 * sum(ring_sizes)` f32 values, rings back to back
+ - `camera` must be a valid camera pointer, or null for pixel coordinates
+ */
+void minifb_batch_add_polygon(struct MiniFBBatch *batch,
+                              const float *points,
+                              const uintptr_t *ring_sizes,
+                              uintptr_t ring_count,
+                              uint32_t color,
+                              float opacity,
+                              const struct MiniFBCamera *camera);
+
+/*
+ Rasterize a batch into a canvas
+
+ With `parallel` the work is spread over the global thread pool; the
+ output is identical either way.
+
+ # Safety
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+ - `batch` must be a valid pointer returned by `minifb_batch_new`
+ */
+void minifb_canvas_draw_batch(struct MiniFBCanvas *canvas,
+                              const struct MiniFBBatch *batch,
+                              bool parallel);
+
+/*
+ Start recording a window's input to a file
+
+ Frame 0 is the first update after this call. A recording already in
+ progress is finished first.
+
+ # Safety
+ - `window` must be a valid pointer returned by one of the window constructors
+ - `path` must be a valid null-terminated C string
+
+ Returns 0 on success, -1 on error (see `minifb_get_last_error`)
+ */
+int32_t minifb_window_record_start(struct MiniFBWindow *window, const char *path);
+
+/*
+ Finish the current recording, if any, and close its file
+
+ # Safety
+ - `window` must be a valid pointer returned by one of the window constructors
+
+ Returns 0 on success, -1 if writing the recording failed at any point
+ */
+int32_t minifb_window_record_stop(struct MiniFBWindow *window);
+
+/*
+ Replay a recording in place of live input
+
+ Starting with the next update, the key, mouse and scroll queries report
+ the recorded input frame by frame and live input is ignored. When the
+ recording ends, live input takes over again.
+
+ # Safety
+ - `window` must be a valid pointer returned by one of the window constructors
+ - `path` must be a valid null-terminated C string
+
+ Returns 0 on success, -1 on error (see `minifb_get_last_error`)
+ */
+int32_t minifb_window_replay_start(struct MiniFBWindow *window, const char *path);
+
+/*
+ Stop replaying and return to live input
+
+ # Safety
+ - `window` must be a valid pointer returned by one of the window constructors
+ */
+void minifb_window_replay_stop(struct MiniFBWindow *window);
+
+/*
+ Check whether a replay is still running
+
+ # Safety
+ - `window` must be a valid pointer returned by one of the window constructors
+ */
+bool minifb_window_is_replaying(const struct MiniFBWindow *window);
+
+/*
+ Create a window on a new render thread
+
+ The window is created like `minifb_window_new` (including the
+ `MINIFB_FFI_BACKEND` override) but on the render thread, which then
+ updates it continuously until it is closed.
+
+ # Safety
+ - `name` must be a valid null-terminated C string
+ - `opts` must be a valid pointer to MiniFBWindowOptions
+
+ Returns null on failure (see `minifb_get_last_error`)
+ */
+struct MiniFBRenderThread *minifb_render_thread_new(const char *name,
+                                                    uintptr_t width,
+                                                    uintptr_t height,
+                                                    const struct MiniFBWindowOptions *opts);
+
+/*
+ Close the window if it is still open, wait for the render thread to end
+ and free the handle
+
+ # Safety
+ - `handle` must be a valid pointer returned by `minifb_render_thread_new`, or null
+ - No other thread may use the handle during or after this call
+ */
+void minifb_render_thread_free(struct MiniFBRenderThread *handle);
+
+/*
+ Queue a 0RGB buffer to be shown, replacing a queued frame not yet shown
+
+ The buffer is copied, so it can be reused as soon as this returns.
+
+ # Safety
+ - `handle` must be a valid pointer returned by `minifb_render_thread_new`
+ - `buffer` must point to at least `width * height` u32 values
+
+ Returns 0 on success, -1 on invalid dimensions (see `minifb_get_last_error`)
+ or if the window has been closed
+ */
+int32_t minifb_render_thread_push(const struct MiniFBRenderThread *handle,
+                                  const uint32_t *buffer,
+                                  uintptr_t width,
+                                  uintptr_t height);
+
+/*
+ Queue a copy of a canvas to be shown, replacing a queued frame not yet shown
+
+ # Safety
+ - `handle` must be a valid pointer returned by `minifb_render_thread_new`
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+
+ Returns 0 on success, -1 if the window has been closed
+ */
+int32_t minifb_render_thread_push_canvas(const struct MiniFBRenderThread *handle,
+                                         const struct MiniFBCanvas *canvas);
+
+/*
+ Read the latest input snapshot
+
+ Key edges and scrolling are accumulated from every window update since
+ the previous read and reset by this call.
+
+ # Safety
+ - `handle` must be a valid pointer returned by `minifb_render_thread_new`
+ - `out_snapshot` must be a valid pointer
+ */
+void minifb_render_thread_get_input(const struct MiniFBRenderThread *handle,
+                                    struct MiniFBInputSnapshot *out_snapshot);
+
+/*
+ Check whether the render thread's window is still open
+
+ # Safety
+ - `handle` must be a valid pointer returned by `minifb_render_thread_new`
+ */
+bool minifb_render_thread_is_open(const struct MiniFBRenderThread *handle);
+
+/*
+ Ask the render thread to close its window and stop
+
+ Returns immediately; the window is closed at the thread's next iteration.
+
+ # Safety
+ - `handle` must be a valid pointer returned by `minifb_render_thread_new`
+ */
+void minifb_render_thread_request_close(const struct MiniFBRenderThread *handle);
+
+/*
+ Create a default run config: 60 updates and at most 60 frames a second,
+ catching up by up to 5 updates per frame
+ */
+struct MiniFBRunConfig minifb_run_config_default(void);
+
+/*
+ Run the window's main loop until it is closed
+
+ Every frame calls `update` as many times as the fixed update rate asks
+ for (none on the first frame), then `render` once, then waits for the
+ next frame. Both callbacks see the input of the last window update, but
+ its key presses, releases and scrolling only reach the first update of
+ the frame: later catch-up updates and the render after them see held
+ keys and buttons only, so one press isn't handled several times. The
+ window's target FPS is ignored while the loop runs, as the loop does its
+ own pacing.
+
+ The loop ends when the window closes (including through
+ `minifb_window_proxy_request_close`) or a callback returns false.
+
+ # Safety
+ - `window` must be a valid pointer returned by `minifb_window_new`
+ - `config` must be a valid pointer to MiniFBRunConfig
+ - The callbacks may be null; `user_data` is passed to them untouched
+
+ Returns 0 once the loop ended, -1 on invalid arguments (see
+ `minifb_get_last_error`)
+ */
+int32_t minifb_run(struct MiniFBWindow *window,
+                   MiniFBUpdateCallback update,
+                   MiniFBRenderCallback render,
+                   void *user_data,
+                   const struct MiniFBRunConfig *config);
+
+/*
+ Run the JSON command protocol on stdin and stdout until stdin closes or
+ a `quit` command arrives
+
+ This is what the `minifb-script` executable does; combine it with
+ `MINIFB_FFI_BACKEND=headless` (or `"headless": true` in `create`) for
+ scripts that should not need a display.
+
+ Returns 0 on success, -1 on an I/O error (see `minifb_get_last_error`)
+ */
+int32_t minifb_script_run_stdio(void);
+
+/*
+ Create the default stroke style (white, 1 pixel, miter joins, butt caps, solid)
+ */
+struct MiniFBStrokeStyle minifb_stroke_style_default(void);
+
+/*
+ Stroke a polyline with width, joins, caps and dashes
+
+ # Safety
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+ - `points` must point to at least `2 * count` f32 values
+ - `style` must be a valid pointer to MiniFBStrokeStyle whose `dash` points
+   to `dash_count` f32 values
+ - `camera` must be a valid camera pointer, or null for pixel coordinates
+ */
+void minifb_canvas_stroke_polyline(struct MiniFBCanvas *canvas,
+                                   const float *points,
+                                   uintptr_t count,
+                                   bool closed,
+                                   const struct MiniFBStrokeStyle *style,
+                                   const struct MiniFBCamera *camera);
+
+/*
+ Create default SVG options (fit to content, 8-unit text, visible layers only)
+ */
+struct MiniFBSvgOptions minifb_svg_options_default(void);
+
+/*
+ Write a display list to an SVG file
+
+ # Safety
+ - `list` must be a valid pointer returned by `minifb_display_list_new`
+ - `path` must be a valid null-terminated C string
+ - `opts` must be a valid pointer to MiniFBSvgOptions
+
+ Returns 0 on success, -1 on error (see `minifb_get_last_error`)
+ */
+int32_t minifb_display_list_save_svg(const struct MiniFBDisplayList *list,
+                                     const char *path,
+                                     const struct MiniFBSvgOptions *opts);
+
+/*
+ Create default terminal options (auto mode, whole terminal, mouse enabled)
+ */
+struct MiniFBTerminalOptions minifb_terminal_options_default(void);
+
+/*
+ Create a window drawn in the terminal on stdout, reading input from stdin
+
+ `width` and `height` are the logical window size: `minifb_window_get_size`
+ returns it and mouse positions are reported in it, while presented
+ buffers are scaled down to fit the terminal. All window functions work on
+ the returned handle; the ones that move or style a desktop window do
+ nothing. Ctrl-C closes the window instead of sending SIGINT.
+
+ Setting the environment variable `MINIFB_FFI_BACKEND=terminal` makes
+ `minifb_window_new` create terminal windows with default options.
+
+ # Safety
+ - `name` must be a valid null-terminated C string
+ - `opts` must be a valid pointer to MiniFBTerminalOptions
+
+ Returns null on failure
+ */
+struct MiniFBWindow *minifb_window_new_terminal(const char *name,
+                                                uintptr_t width,
+                                                uintptr_t height,
+                                                const struct MiniFBTerminalOptions *opts);
+
+/*
+ Create the default text style (white, top-left anchored, no background)
+ */
+struct MiniFBTextStyle minifb_text_style_default(void);
+
+/*
+ Create a font using the embedded 5x8 bitmap glyphs, magnified by an integer `scale`
+ */
+struct MiniFBFont *minifb_font_builtin(uint32_t scale);
+
+/*
+ Load a TrueType/OpenType font from a file, rendered at `size_px` pixels
+
+ # Safety
+ - `path` must be a valid null-terminated C string
+
+ Returns null on failure (see `minifb_get_last_error`)
+ */
+struct MiniFBFont *minifb_font_load_ttf(const char *path, float size_px);
+
+/*
+ Free a font
+
+ # Safety
+ - `font` must be a valid pointer returned by `minifb_font_builtin` or
+   `minifb_font_load_ttf`, or null
+ - After calling this function, the pointer is invalid and must not be used
+ */
+void minifb_font_free(struct MiniFBFont *font);
+
+/*
+ Draw a UTF-8 string into a canvas
+
+ # Safety
+ - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+ - `font` must be a valid font pointer, or null for the built-in font at scale 1
+ - `text` must be a valid null-terminated UTF-8 C string
+ - `style` must be a valid pointer to MiniFBTextStyle
+
+ Returns 0 on success, -1 on error
+ */
+int32_t minifb_canvas_draw_text(struct MiniFBCanvas *canvas,
+                                struct MiniFBFont *font,
+                                const char *text,
+                                int32_t x,
+                                int32_t y,
+                                const struct MiniFBTextStyle *style);
+
+/*
+ Measure the extents of a UTF-8 string without drawing it
+
+ # Safety
+ - `font` must be a valid font pointer, or null for the built-in font at scale 1
+ - `text` must be a valid null-terminated UTF-8 C string
+ - `out_width` and `out_height` must be valid pointers
+
+ Returns 0 on success, -1 on error
+ */
+int32_t minifb_text_measure(const struct MiniFBFont *font,
+                            const char *text,
+                            int32_t *out_width,
+                            int32_t *out_height);
+
+/*
+ Create default VNC options (port 5900, localhost only, input allowed)
+ */
+struct MiniFBVncOptions minifb_vnc_options_default(void);
+
+/*
+ Serve a window over RFB (VNC)
+
+ The framebuffer has the window's current size; later presents are
+ stretched to it. Clients see frames from the next present on and, if
+ `allow_input` is set, their key and pointer events arrive at the
+ window's following updates. Serving again replaces the running server.
+
+ # Safety
+ - `window` must be a valid pointer returned by one of the window constructors
+ - `opts` must be a valid pointer to MiniFBVncOptions
+
+ Returns the TCP port listened on, or -1 on error (see `minifb_get_last_error`)
+ */
+int32_t minifb_window_serve_vnc(struct MiniFBWindow *window, const struct MiniFBVncOptions *opts);
+
+/*
+ Stop serving a window over VNC and disconnect all clients
+
+ # Safety
+ - `window` must be a valid pointer returned by one of the window constructors
+ */
+void minifb_window_stop_vnc(struct MiniFBWindow *window);
+
+/*
+ Get a descriptor that becomes readable when the window has input to process
+
+ The descriptor is created on the first call and owned by the window:
+ don't read from or close it. It is readable while input events are
+ waiting or a close was requested. Native and terminal windows also
+ signal it at least every 16 ms, as their own event queues can't be
+ watched. Call `minifb_window_pump` once it is readable; that resets it.
+
+ # Safety
+ - `window` must be a valid pointer returned by `minifb_window_new`
+
+ Returns the descriptor, or -1 on failure (see `minifb_get_last_error`)
+ */
+int32_t minifb_window_wakeup_fd(struct MiniFBWindow *window);
+
+/*
+ Process pending window events without presenting or waiting
+
+ Does what `minifb_window_update` does, including gathering injected and
+ remote input, but ignores the target frame rate, so it returns at once.
+ The window's wakeup descriptor is reset first, so events arriving while
+ it runs signal it again.
+
+ # Safety
+ - `window` must be a valid pointer returned by `minifb_window_new`
+
+ Returns 0 on success, -1 if the window is null
+ */
+int32_t minifb_window_pump(struct MiniFBWindow *window);
+
 #endif  /* MINIFB_FFI_H */
@@ -0,0 +1,363 @@
+//! Accumulation buffer for motion blur and onion skinning
+//!
+//! Frames are summed in linear light with a weight each, so averaging many
+//! sub-frames of an animation gives physically plausible motion blur, and
+//! decaying the sum before every add gives an exponential trail. Separately,
+//! a short history of whole frames can be drawn behind the current one with
+//! fading tints to inspect how shapes move between frames.
+
+use crate::canvas::{lerp_rgb, try_alloc, MiniFBCanvas};
+use crate::composite::srgb_to_linear;
+use crate::present::linear_to_srgb8;
+use crate::set_last_error;
+use std::collections::VecDeque;
+
+/// How the accumulated sum is turned back into pixels
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiniFBAccumResolve {
+    /// Divide by the total weight added so far
+    AccumAverage = 0,
+    /// Use the weighted sum as is, clamped to white
+    AccumSum = 1,
+}
+
+/// Onion-skin drawing options
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MiniFBOnionOptions {
+    /// Number of previous frames to draw, at most the history length
+    pub frames: usize,
+    /// Opacity of the most recent previous frame
+    pub opacity: f32,
+    /// Opacity multiplier for each step further into the past
+    pub falloff: f32,
+    /// 0RGB tint mixed into past frames
+    pub tint: u32,
+    /// Amount of tint, 0.0 keeps the original colors
+    pub tint_strength: f32,
+    /// Pixels of this color count as empty: they are not drawn from past
+    /// frames, and past frames only show where the current frame has it
+    pub background: u32,
+}
+
+/// Create default onion-skin options (4 frames, reddish tint, black background)
+#[no_mangle]
+pub extern "C" fn minifb_onion_options_default() -> MiniFBOnionOptions {
+    MiniFBOnionOptions {
+        frames: 4,
+        opacity: 0.5,
+        falloff: 0.6,
+        tint: 0x00FF4040,
+        tint_strength: 0.5,
+        background: 0,
+    }
+}
+
+/// Opaque accumulation buffer
+pub struct MiniFBAccumulator {
+    width: usize,
+    height: usize,
+    /// Weighted sum of linear RGB
+    sum: Vec<[f32; 3]>,
+    total_weight: f32,
+    /// Most recent frame first
+    history: VecDeque<Vec<u32>>,
+    history_len: usize,
+}
+
+impl MiniFBAccumulator {
+    /// None if the sum buffer can't be allocated; the history grows as
+    /// frames are pushed
+    pub(crate) fn new(width: usize, height: usize, history_len: usize) -> Option<Self> {
+        Some(MiniFBAccumulator {
+            width,
+            height,
+            sum: try_alloc(width, height, [0.0; 3])?,
+            total_weight: 0.0,
+            history: VecDeque::new(),
+            history_len,
+        })
+    }
+
+    fn check_size(&self, canvas: &MiniFBCanvas) -> bool {
+        if canvas.width != self.width || canvas.height != self.height {
+            set_last_error("canvas dimensions do not match accumulation buffer");
+            return false;
+        }
+        true
+    }
+
+    pub(crate) fn add(&mut self, pixels: &[u32], weight: f32) {
+        for (acc, &p) in self.sum.iter_mut().zip(pixels) {
+            acc[0] += srgb_to_linear((p >> 16) as u8) * weight;
+            acc[1] += srgb_to_linear((p >> 8) as u8) * weight;
+            acc[2] += srgb_to_linear(p as u8) * weight;
+        }
+        self.total_weight += weight;
+    }
+
+    pub(crate) fn decay(&mut self, factor: f32) {
+        for acc in &mut self.sum {
+            for c in acc {
+                *c *= factor;
+            }
+        }
+        self.total_weight *= factor;
+    }
+
+    pub(crate) fn resolve(&self, out: &mut [u32], mode: MiniFBAccumResolve) {
+        let scale = match mode {
+            MiniFBAccumResolve::AccumAverage if self.total_weight > 0.0 => 1.0 / self.total_weight,
+            MiniFBAccumResolve::AccumAverage => 0.0,
+            MiniFBAccumResolve::AccumSum => 1.0,
+        };
+        for (o, acc) in out.iter_mut().zip(&self.sum) {
+            *o = (linear_to_srgb8(acc[0] * scale) << 16)
+                | (linear_to_srgb8(acc[1] * scale) << 8)
+                | linear_to_srgb8(acc[2] * scale);
+        }
+    }
+
+    pub(crate) fn push_frame(&mut self, pixels: &[u32]) {
+        if self.history_len == 0 {
+            return;
+        }
+        let mut frame = if self.history.len() == self.history_len {
+            self.history.pop_back().unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        frame.clear();
+        frame.extend_from_slice(pixels);
+        self.history.push_front(frame);
+    }
+
+    pub(crate) fn draw_onion(&self, canvas: &mut MiniFBCanvas, opts: &MiniFBOnionOptions) {
+        let strength = (opts.tint_strength.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let current = canvas.pixels.clone();
+        let count = opts.frames.min(self.history.len());
+        // Oldest first so more recent frames end up on top
+        for (age, frame) in self.history.iter().take(count).enumerate().rev() {
+            let opacity = opts.opacity.clamp(0.0, 1.0) * opts.falloff.max(0.0).powi(age as i32);
+            let alpha = (opacity.min(1.0) * 255.0).round() as u8;
+            if alpha == 0 {
+                continue;
+            }
+            for (i, (&past, &now)) in frame.iter().zip(&current).enumerate() {
+                if past != opts.background && now == opts.background {
+                    let (x, y) = ((i % self.width) as i64, (i / self.width) as i64);
+                    canvas.blend(x, y, lerp_rgb(past, opts.tint, strength), alpha);
+                }
+            }
+        }
+    }
+}
+
+/// Create an accumulation buffer keeping up to `history_len` frames for onion skinning
+///
+/// Returns null if either dimension is zero or the buffer can't be allocated
+#[no_mangle]
+pub extern "C" fn minifb_accum_new(
+    width: usize,
+    height: usize,
+    history_len: usize,
+) -> *mut MiniFBAccumulator {
+    if width == 0 || height == 0 {
+        set_last_error("accumulation buffer dimensions must be non-zero");
+        return std::ptr::null_mut();
+    }
+    match MiniFBAccumulator::new(width, height, history_len) {
+        Some(accum) => Box::into_raw(Box::new(accum)),
+        None => {
+            set_last_error("accumulation buffer dimensions are too large");
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Free an accumulation buffer
+///
+/// # Safety
+/// - `accum` must be a valid pointer returned by `minifb_accum_new`, or null
+/// - After calling this function, the pointer is invalid and must not be used
+#[no_mangle]
+pub unsafe extern "C" fn minifb_accum_free(accum: *mut MiniFBAccumulator) {
+    if !accum.is_null() {
+        drop(Box::from_raw(accum));
+    }
+}
+
+/// Reset the sum and total weight to zero and forget the frame history
+///
+/// # Safety
+/// - `accum` must be a valid pointer returned by `minifb_accum_new`
+#[no_mangle]
+pub unsafe extern "C" fn minifb_accum_clear(accum: *mut MiniFBAccumulator) {
+    if accum.is_null() {
+        return;
+    }
+    let accum = &mut *accum;
+    accum.sum.fill([0.0; 3]);
+    accum.total_weight = 0.0;
+    accum.history.clear();
+}
+
+/// Add a canvas to the sum with a weight
+///
+/// # Safety
+/// - `accum` must be a valid pointer returned by `minifb_accum_new`
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+///
+/// Returns 0 on success, -1 if the sizes differ
+#[no_mangle]
+pub unsafe extern "C" fn minifb_accum_add(
+    accum: *mut MiniFBAccumulator,
+    canvas: *const MiniFBCanvas,
+    weight: f32,
+) -> i32 {
+    if accum.is_null() || canvas.is_null() || !(*accum).check_size(&*canvas) {
+        return -1;
+    }
+    (*accum).add(&(*canvas).pixels, weight);
+    0
+}
+
+/// Multiply the sum and total weight by `factor`
+///
+/// Calling this before every `minifb_accum_add` turns the buffer into an
+/// exponential moving average; 0.9 leaves a trail of roughly ten frames.
+///
+/// # Safety
+/// - `accum` must be a valid pointer returned by `minifb_accum_new`
+#[no_mangle]
+pub unsafe extern "C" fn minifb_accum_decay(accum: *mut MiniFBAccumulator, factor: f32) {
+    if accum.is_null() {
+        return;
+    }
+    (*accum).decay(factor);
+}
+
+/// Get the total weight added so far (after decay)
+///
+/// # Safety
+/// - `accum` must be a valid pointer returned by `minifb_accum_new`
+#[no_mangle]
+pub unsafe extern "C" fn minifb_accum_get_weight(accum: *const MiniFBAccumulator) -> f32 {
+    if accum.is_null() {
+        return 0.0;
+    }
+    (*accum).total_weight
+}
+
+/// Write the accumulated image into a canvas
+///
+/// # Safety
+/// - `accum` must be a valid pointer returned by `minifb_accum_new`
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+///
+/// Returns 0 on success, -1 if the sizes differ
+#[no_mangle]
+pub unsafe extern "C" fn minifb_accum_resolve(
+    accum: *const MiniFBAccumulator,
+    canvas: *mut MiniFBCanvas,
+    mode: MiniFBAccumResolve,
+) -> i32 {
+    if accum.is_null() || canvas.is_null() || !(*accum).check_size(&*canvas) {
+        return -1;
+    }
+    let canvas = &mut *canvas;
+    (*accum).resolve(&mut canvas.pixels, mode);
+    canvas.mark_all_dirty();
+    0
+}
+
+/// Remember a copy of a canvas as the most recent onion-skin frame
+///
+/// # Safety
+/// - `accum` must be a valid pointer returned by `minifb_accum_new`
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+///
+/// Returns 0 on success, -1 if the sizes differ
+#[no_mangle]
+pub unsafe extern "C" fn minifb_accum_push_frame(
+    accum: *mut MiniFBAccumulator,
+    canvas: *const MiniFBCanvas,
+) -> i32 {
+    if accum.is_null() || canvas.is_null() || !(*accum).check_size(&*canvas) {
+        return -1;
+    }
+    (*accum).push_frame(&(*canvas).pixels);
+    0
+}
+
+/// Draw remembered frames behind the current canvas contents with fading tints
+///
+/// # Safety
+/// - `accum` must be a valid pointer returned by `minifb_accum_new`
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+/// - `opts` must be a valid pointer to MiniFBOnionOptions
+///
+/// Returns 0 on success, -1 if the sizes differ
+#[no_mangle]
+pub unsafe extern "C" fn minifb_accum_draw_onion(
+    accum: *const MiniFBAccumulator,
+    canvas: *mut MiniFBCanvas,
+    opts: *const MiniFBOnionOptions,
+) -> i32 {
+    if accum.is_null() || canvas.is_null() || opts.is_null() || !(*accum).check_size(&*canvas) {
+        return -1;
+    }
+    (*accum).draw_onion(&mut *canvas, &*opts);
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_average_and_decay() {
+        let mut accum = MiniFBAccumulator::new(2, 1, 0).unwrap();
+        accum.add(&[0xFFFFFF, 0x000000], 1.0);
+        accum.add(&[0x000000, 0x000000], 1.0);
+        let mut out = [0; 2];
+        accum.resolve(&mut out, MiniFBAccumResolve::AccumAverage);
+        // Half of white in linear light
+        assert_eq!(out, [0xBCBCBC, 0]);
+
+        accum.decay(0.0);
+        accum.add(&[0xFF0000, 0x00FF00], 2.0);
+        accum.resolve(&mut out, MiniFBAccumResolve::AccumAverage);
+        assert_eq!(out, [0xFF0000, 0x00FF00]);
+    }
+
+    #[test]
+    fn test_onion_draws_behind() {
+        let mut accum = MiniFBAccumulator::new(3, 1, 2).unwrap();
+        accum.push_frame(&[0x0000FF, 0, 0]);
+        accum.push_frame(&[0, 0x0000FF, 0]);
+        let mut canvas = MiniFBCanvas::new(3, 1);
+        canvas.pixels = vec![0, 0xFFFFFF, 0];
+        let opts = MiniFBOnionOptions {
+            opacity: 1.0,
+            falloff: 0.5,
+            tint_strength: 0.0,
+            ..minifb_onion_options_default()
+        };
+        accum.draw_onion(&mut canvas, &opts);
+        assert_eq!(canvas.pixels[0], 0x000080);
+        assert_eq!(canvas.pixels[1], 0xFFFFFF);
+        assert_eq!(canvas.pixels[2], 0);
+    }
+
+    #[test]
+    fn test_oversized_accum_is_null() {
+        assert!(minifb_accum_new(usize::MAX, 2, 0).is_null());
+        assert!(minifb_accum_new(1 << 31, 1 << 30, 0).is_null());
+        // The history is only allocated as frames arrive
+        let accum = minifb_accum_new(4, 4, usize::MAX);
+        assert!(!accum.is_null());
+        unsafe { minifb_accum_free(accum) };
+    }
+}
@@ -0,0 +1,521 @@
+//! Window backends
+//!
+//! A `MiniFBWindow` is normally a native minifb window, but it can also be
+//! drawn in a terminal or have no display at all. `Backend` mirrors the parts
+//! of `minifb::Window` the FFI uses, so every window function works the same
+//! for all of them.
+//!
+//! Input that does not come from a native window (terminal keys, VNC
+//! clients) is collected in an `InputState` at every update and combined with
+//! the native window's own state in the queries, unless a replay substitutes
+//! recorded input for all of it. Injected events build a state of their own,
+//! which takes priority: a key or button injected in an update is answered
+//! from it alone until the next update, and an injected pointer move wins
+//! over the others until another source moves the pointer.
+
+use crate::input::{InputEvent, InputState};
+use crate::proxy::Requests;
+use crate::record::{Recorder, Replay};
+use crate::terminal::Terminal;
+use crate::vnc::VncServer;
+use crate::wakeup::Waker;
+use crate::MiniFBWindow;
+use minifb::{CursorStyle, Key, KeyRepeat, MouseButton, MouseMode, Window};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// The whole window sits behind a boxed FFI handle, so variant sizes don't matter
+#[allow(clippy::large_enum_variant)]
+pub(crate) enum Device {
+    Native(Window),
+    Terminal(Terminal),
+    /// No display; frames are only seen by VNC clients
+    Headless {
+        size: (usize, usize),
+    },
+}
+
+/// Input source that moved the pointer last
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MouseSource {
+    Native,
+    Input,
+    Injected,
+}
+
+/// minifb-style update rate limit for devices that don't have their own
+struct RateLimit {
+    frame_time: Option<Duration>,
+    last: Instant,
+}
+
+impl RateLimit {
+    fn wait(&mut self) {
+        if let Some(frame_time) = self.frame_time {
+            let elapsed = self.last.elapsed();
+            if elapsed < frame_time {
+                std::thread::sleep(frame_time - elapsed);
+            }
+        }
+        self.last = Instant::now();
+    }
+}
+
+pub(crate) struct Backend {
+    pub(crate) device: Device,
+    pub(crate) input: InputState,
+    /// Injected events waiting for the next update
+    injected: Vec<InputEvent>,
+    injected_input: InputState,
+    /// Keys and buttons injected in the last update, which ignore live input
+    injected_keys: Vec<Key>,
+    injected_buttons: Vec<MouseButton>,
+    mouse_source: MouseSource,
+    native_mouse: Option<(f32, f32)>,
+    limit: RateLimit,
+    /// Last rate set on a native window, restored after a pump
+    pub(crate) target_fps: usize,
+    /// Input updates so far, one per update, present or pump
+    pub(crate) updates: u64,
+    /// Set once this update's key edges and scrolling were handed out
+    edges_taken: bool,
+    pub(crate) waker: Arc<Waker>,
+    /// Queue shared with the window's proxies, once one was created
+    pub(crate) requests: Option<Arc<Requests>>,
+    /// Set when a proxy asked the window to close
+    pub(crate) close_requested: bool,
+    /// Proxy messages delivered by updates and not taken yet
+    pub(crate) messages: VecDeque<u64>,
+    pub(crate) vnc: Option<VncServer>,
+    pub(crate) recorder: Option<Recorder>,
+    /// Set when writing the recording failed and it was abandoned
+    pub(crate) record_failed: bool,
+    pub(crate) replay: Option<Replay>,
+}
+
+impl Backend {
+    pub(crate) fn new(device: Device) -> Self {
+        Backend {
+            device,
+            input: InputState::default(),
+            injected: Vec::new(),
+            injected_input: InputState::default(),
+            injected_keys: Vec::new(),
+            injected_buttons: Vec::new(),
+            mouse_source: MouseSource::Native,
+            native_mouse: None,
+            // Same default limit as minifb windows
+            limit: RateLimit {
+                frame_time: Some(Duration::from_millis(4)),
+                last: Instant::now(),
+            },
+            target_fps: 250,
+            updates: 0,
+            edges_taken: false,
+            waker: Arc::default(),
+            requests: None,
+            close_requested: false,
+            messages: VecDeque::new(),
+            vnc: None,
+            recorder: None,
+            record_failed: false,
+            replay: None,
+        }
+    }
+
+    pub(crate) fn is_open(&self) -> bool {
+        if self.close_requested {
+            return false;
+        }
+        match &self.device {
+            Device::Native(w) => w.is_open(),
+            Device::Terminal(t) => t.is_open(),
+            Device::Headless { .. } => true,
+        }
+    }
+
+    /// Queue an input event as if it arrived before the next update
+    pub(crate) fn inject(&mut self, event: InputEvent) {
+        self.injected.push(event);
+        self.waker.wake();
+    }
+
+    /// What the wakeup descriptor's watcher thread has to wait for
+    #[cfg(unix)]
+    pub(crate) fn watch(&self) -> Option<crate::wakeup::Watch> {
+        use crate::wakeup::Watch;
+        match &self.device {
+            Device::Native(_) => Some(Watch::Tick),
+            Device::Terminal(t) if t.reads_stdin() => Some(Watch::Stdin),
+            Device::Terminal(_) => Some(Watch::Tick),
+            Device::Headless { .. } => None,
+        }
+    }
+
+    /// Gather input from every source for the new frame
+    fn poll_input(&mut self) {
+        self.updates += 1;
+        self.edges_taken = false;
+        if let Some(requests) = self.requests.clone() {
+            requests.apply(self);
+        }
+        self.input.begin_frame();
+        if let Device::Terminal(t) = &mut self.device {
+            t.read_input(&mut self.input);
+        }
+        if let Some(vnc) = &self.vnc {
+            for event in vnc.take_events() {
+                self.input.apply(event);
+            }
+        }
+        self.injected_input.begin_frame();
+        self.injected_keys.clear();
+        self.injected_buttons.clear();
+        for event in std::mem::take(&mut self.injected) {
+            match event {
+                InputEvent::Key(key, down) => {
+                    // Releasing a key held through live input still reports the release
+                    if !down && self.live_key_down(key) && !self.injected_input.is_key_down(key) {
+                        self.injected_input.down.push(key);
+                    }
+                    self.injected_keys.push(key);
+                }
+                InputEvent::Button(button, _) => self.injected_buttons.push(button),
+                _ => {}
+            }
+            self.injected_input.apply(event);
+        }
+        let native = self.native().and_then(|w| w.get_mouse_pos(MouseMode::Pass));
+        if self.injected_input.mouse_moved() {
+            self.mouse_source = MouseSource::Injected;
+        } else if self.input.mouse_moved() {
+            self.mouse_source = MouseSource::Input;
+        } else if self.native().is_some() && native != self.native_mouse {
+            self.mouse_source = MouseSource::Native;
+        }
+        self.native_mouse = native;
+        if self.replay.as_mut().is_some_and(|replay| !replay.advance()) {
+            self.replay = None;
+        }
+        if let Some(mut recorder) = self.recorder.take() {
+            match recorder.record(crate::record::observe(self)) {
+                Ok(()) => self.recorder = Some(recorder),
+                Err(_) => self.record_failed = true,
+            }
+        }
+    }
+
+    /// Hide this update's key presses, releases and scrolling from later
+    /// queries, for callers that consume them once
+    pub(crate) fn take_edges(&mut self) {
+        self.edges_taken = true;
+        self.input.clear_edges();
+        self.injected_input.clear_edges();
+    }
+
+    /// Recorded input that replaces live input while a replay runs
+    fn replayed(&self) -> Option<&InputState> {
+        self.replay.as_ref().map(|replay| &replay.state)
+    }
+
+    pub(crate) fn update(&mut self) {
+        match &mut self.device {
+            Device::Native(w) => w.update(),
+            _ => self.limit.wait(),
+        }
+        self.poll_input();
+    }
+
+    /// Like `update`, but without waiting for the target frame rate
+    pub(crate) fn pump(&mut self) {
+        self.waker.clear();
+        if let Device::Native(w) = &mut self.device {
+            w.set_target_fps(0);
+            w.update();
+            w.set_target_fps(self.target_fps);
+        }
+        self.poll_input();
+    }
+
+    pub(crate) fn update_with_buffer(
+        &mut self,
+        buffer: &[u32],
+        width: usize,
+        height: usize,
+    ) -> minifb::Result<()> {
+        if width * height == 0 || buffer.len() < width * height {
+            return Err(minifb::Error::UpdateFailed(
+                "buffer is empty or smaller than width * height".into(),
+            ));
+        }
+        match &mut self.device {
+            Device::Native(w) => w.update_with_buffer(buffer, width, height)?,
+            Device::Terminal(t) => {
+                t.draw(buffer, width, height)
+                    .map_err(|e| minifb::Error::UpdateFailed(e.to_string()))?;
+                self.limit.wait();
+            }
+            Device::Headless { .. } => self.limit.wait(),
+        }
+        let size = self.get_size();
+        if let Some(vnc) = &self.vnc {
+            vnc.publish(buffer, width, height, size);
+        }
+        self.poll_input();
+        Ok(())
+    }
+
+    pub(crate) fn get_size(&self) -> (usize, usize) {
+        match &self.device {
+            Device::Native(w) => w.get_size(),
+            Device::Terminal(t) => t.size,
+            Device::Headless { size } => *size,
+        }
+    }
+
+    pub(crate) fn set_title(&mut self, title: &str) {
+        match &mut self.device {
+            Device::Native(w) => w.set_title(title),
+            Device::Terminal(t) => t.set_title(title),
+            Device::Headless { .. } => {}
+        }
+    }
+
+    pub(crate) fn set_target_fps(&mut self, fps: usize) {
+        self.target_fps = fps;
+        match &mut self.device {
+            Device::Native(w) => w.set_target_fps(fps),
+            _ => {
+                self.limit.frame_time = (fps > 0).then(|| Duration::from_secs_f32(1.0 / fps as f32))
+            }
+        }
+    }
+
+    pub(crate) fn set_background_color(&mut self, red: u8, green: u8, blue: u8) {
+        if let Device::Native(w) = &mut self.device {
+            w.set_background_color(red, green, blue);
+        }
+    }
+
+    fn native(&self) -> Option<&Window> {
+        match &self.device {
+            Device::Native(w) => Some(w),
+            _ => None,
+        }
+    }
+
+    /// Whether `key` is held on the native window or through `input`
+    fn live_key_down(&self, key: Key) -> bool {
+        self.input.is_key_down(key) || self.native().is_some_and(|w| w.is_key_down(key))
+    }
+
+    /// Whether live input counts for `key`, i.e. it wasn't injected this update
+    fn live_key(&self, key: Key) -> bool {
+        !self.injected_keys.contains(&key)
+    }
+
+    pub(crate) fn is_key_down(&self, key: Key) -> bool {
+        if let Some(replayed) = self.replayed() {
+            return replayed.is_key_down(key);
+        }
+        self.injected_input.is_key_down(key) || (self.live_key(key) && self.live_key_down(key))
+    }
+
+    pub(crate) fn is_key_pressed(&self, key: Key, repeat: KeyRepeat) -> bool {
+        if self.edges_taken {
+            return false;
+        }
+        if let Some(replayed) = self.replayed() {
+            return replayed.is_key_pressed(key, repeat);
+        }
+        self.injected_input.is_key_pressed(key, repeat)
+            || (self.live_key(key)
+                && (self.input.is_key_pressed(key, repeat)
+                    || self.native().is_some_and(|w| w.is_key_pressed(key, repeat))))
+    }
+
+    pub(crate) fn is_key_released(&self, key: Key) -> bool {
+        if self.edges_taken {
+            return false;
+        }
+        if let Some(replayed) = self.replayed() {
+            return replayed.is_key_released(key);
+        }
+        self.injected_input.is_key_released(key)
+            || (self.live_key(key)
+                && (self.input.is_key_released(key)
+                    || self.native().is_some_and(|w| w.is_key_released(key))))
+    }
+
+    pub(crate) fn get_keys(&self) -> Vec<Key> {
+        if let Some(replayed) = self.replayed() {
+            return replayed.get_keys();
+        }
+        let mut keys = self.native().map(Window::get_keys).unwrap_or_default();
+        keys.retain(|&key| self.live_key(key));
+        let input = self
+            .input
+            .get_keys()
+            .into_iter()
+            .filter(|&key| self.live_key(key));
+        for key in input.chain(self.injected_input.get_keys()) {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        keys
+    }
+
+    pub(crate) fn get_mouse_pos(&self, mode: MouseMode) -> Option<(f32, f32)> {
+        if let Some(replayed) = self.replayed() {
+            return replayed.get_mouse_pos(mode, self.get_size());
+        }
+        match (self.mouse_source, self.native()) {
+            (MouseSource::Injected, _) => self.injected_input.get_mouse_pos(mode, self.get_size()),
+            (MouseSource::Native, Some(w)) => w.get_mouse_pos(mode),
+            _ => self.input.get_mouse_pos(mode, self.get_size()),
+        }
+    }
+
+    pub(crate) fn get_mouse_down(&self, button: MouseButton) -> bool {
+        if let Some(replayed) = self.replayed() {
+            return replayed.get_mouse_down(button);
+        }
+        self.injected_input.get_mouse_down(button)
+            || (!self.injected_buttons.contains(&button)
+                && (self.input.get_mouse_down(button)
+                    || self.native().is_some_and(|w| w.get_mouse_down(button))))
+    }
+
+    pub(crate) fn get_scroll_wheel(&self) -> Option<(f32, f32)> {
+        if self.edges_taken {
+            return None;
+        }
+        if let Some(replayed) = self.replayed() {
+            return replayed.get_scroll_wheel();
+        }
+        let native = self.native().and_then(Window::get_scroll_wheel);
+        [
+            self.input.get_scroll_wheel(),
+            self.injected_input.get_scroll_wheel(),
+        ]
+        .into_iter()
+        .fold(native, |sum, scroll| match (sum, scroll) {
+            (Some(a), Some(b)) => Some((a.0 + b.0, a.1 + b.1)),
+            (a, b) => a.or(b),
+        })
+    }
+
+    pub(crate) fn set_position(&mut self, x: isize, y: isize) {
+        if let Device::Native(w) = &mut self.device {
+            w.set_position(x, y);
+        }
+    }
+
+    pub(crate) fn get_position(&self) -> (isize, isize) {
+        self.native().map(Window::get_position).unwrap_or((0, 0))
+    }
+
+    pub(crate) fn topmost(&self, topmost: bool) {
+        if let Some(w) = self.native() {
+            w.topmost(topmost);
+        }
+    }
+
+    pub(crate) fn set_cursor_visibility(&mut self, visible: bool) {
+        if let Device::Native(w) = &mut self.device {
+            w.set_cursor_visibility(visible);
+        }
+    }
+
+    pub(crate) fn set_cursor_style(&mut self, style: CursorStyle) {
+        if let Device::Native(w) = &mut self.device {
+            w.set_cursor_style(style);
+        }
+    }
+}
+
+impl Drop for Backend {
+    fn drop(&mut self) {
+        self.waker.close();
+        if let Some(requests) = &self.requests {
+            requests.window_gone();
+        }
+    }
+}
+
+/// Create a window without any display
+///
+/// Headless windows behave like native ones of a fixed size that never
+/// close: frames can be presented and are shown to VNC clients (see
+/// `minifb_window_serve_vnc`), and input comes from those clients. Setting
+/// the environment variable `MINIFB_FFI_BACKEND=headless` makes
+/// `minifb_window_new` create headless windows.
+///
+/// Returns null if either dimension is zero
+#[no_mangle]
+pub extern "C" fn minifb_window_new_headless(width: usize, height: usize) -> *mut MiniFBWindow {
+    if width == 0 || height == 0 {
+        crate::set_last_error("window dimensions must be non-zero");
+        return std::ptr::null_mut();
+    }
+    Box::into_raw(Box::new(MiniFBWindow::with_backend(
+        Backend::new(Device::Headless {
+            size: (width, height),
+        }),
+        width,
+        height,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headless_input_edges() {
+        let mut backend = Backend::new(Device::Headless { size: (8, 8) });
+        backend.limit.frame_time = None;
+        backend.update();
+        backend.input.apply(InputEvent::Key(Key::A, true));
+        backend.input.apply(InputEvent::MouseMove(Some((3.0, 9.0))));
+        assert!(backend.is_key_pressed(Key::A, KeyRepeat::No));
+        assert_eq!(backend.get_mouse_pos(MouseMode::Discard), None);
+        assert_eq!(backend.get_mouse_pos(MouseMode::Clamp), Some((3.0, 7.0)));
+
+        backend.update_with_buffer(&[0; 64], 8, 8).unwrap();
+        assert!(backend.is_key_down(Key::A));
+        assert!(!backend.is_key_pressed(Key::A, KeyRepeat::No));
+        assert!(backend.update_with_buffer(&[0; 63], 8, 8).is_err());
+    }
+
+    #[test]
+    fn test_injected_input_overrides_live_input() {
+        let mut backend = Backend::new(Device::Headless { size: (8, 8) });
+        backend.limit.frame_time = None;
+        // Held and pointed through a live source, as by a VNC client
+        backend.input.apply(InputEvent::Key(Key::A, true));
+        backend
+            .input
+            .apply(InputEvent::Button(MouseButton::Left, true));
+        backend.input.apply(InputEvent::MouseMove(Some((2.0, 2.0))));
+        backend.update();
+        assert_eq!(backend.get_mouse_pos(MouseMode::Pass), Some((2.0, 2.0)));
+
+        backend.inject(InputEvent::Key(Key::A, false));
+        backend.inject(InputEvent::Button(MouseButton::Left, false));
+        backend.inject(InputEvent::MouseMove(None));
+        backend.update();
+        assert!(!backend.is_key_down(Key::A) && backend.is_key_released(Key::A));
+        assert!(backend.get_keys().is_empty());
+        assert!(!backend.get_mouse_down(MouseButton::Left));
+        assert_eq!(backend.get_mouse_pos(MouseMode::Pass), None);
+
+        // The live key and button count again once the injected frame is over
+        backend.update();
+        assert!(backend.is_key_down(Key::A) && !backend.is_key_released(Key::A));
+        assert!(backend.get_mouse_down(MouseButton::Left));
+        assert_eq!(backend.get_mouse_pos(MouseMode::Pass), None);
+    }
+}
@@ -0,0 +1,5 @@
+//! Drive a window with JSON commands on stdin; see the `script` module
+
+fn main() {
+    std::process::exit(minifb_ffi::script::minifb_script_run_stdio());
+}
@@ -0,0 +1,492 @@
+//! World-space camera: a 2D affine world-to-screen transform with built-in
+//! drag-to-pan and scroll-to-zoom handling
+//!
+//! Transforms use the same row-vector layout as OCaml's `Matrix.t`:
+//! `x' = x * m11 + y * m21 + m31`, `y' = x * m12 + y * m22 + m32`.
+
+use crate::{MiniFBMouseButton, MiniFBWindow};
+use minifb::MouseMode;
+
+/// 2D affine transform, field-for-field compatible with `Matrix.t`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MiniFBTransform {
+    pub m11: f64,
+    pub m12: f64,
+    pub m21: f64,
+    pub m22: f64,
+    pub m31: f64,
+    pub m32: f64,
+}
+
+impl MiniFBTransform {
+    pub(crate) const IDENTITY: MiniFBTransform = MiniFBTransform {
+        m11: 1.0,
+        m12: 0.0,
+        m21: 0.0,
+        m22: 1.0,
+        m31: 0.0,
+        m32: 0.0,
+    };
+
+    pub(crate) fn translation(dx: f64, dy: f64) -> Self {
+        MiniFBTransform {
+            m31: dx,
+            m32: dy,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub(crate) fn scale(sx: f64, sy: f64) -> Self {
+        MiniFBTransform {
+            m11: sx,
+            m22: sy,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// `self` followed by `other` (same as `Matrix.mul self other`)
+    pub(crate) fn then(&self, other: &MiniFBTransform) -> Self {
+        let (a, b) = (self, other);
+        MiniFBTransform {
+            m11: a.m11 * b.m11 + a.m12 * b.m21,
+            m12: a.m11 * b.m12 + a.m12 * b.m22,
+            m21: a.m21 * b.m11 + a.m22 * b.m21,
+            m22: a.m21 * b.m12 + a.m22 * b.m22,
+            m31: a.m31 * b.m11 + a.m32 * b.m21 + b.m31,
+            m32: a.m31 * b.m12 + a.m32 * b.m22 + b.m32,
+        }
+    }
+
+    pub(crate) fn determinant(&self) -> f64 {
+        self.m11 * self.m22 - self.m12 * self.m21
+    }
+
+    pub(crate) fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let m11 = self.m22 / det;
+        let m12 = -self.m12 / det;
+        let m21 = -self.m21 / det;
+        let m22 = self.m11 / det;
+        Some(MiniFBTransform {
+            m11,
+            m12,
+            m21,
+            m22,
+            m31: -(self.m31 * m11 + self.m32 * m21),
+            m32: -(self.m31 * m12 + self.m32 * m22),
+        })
+    }
+
+    #[inline]
+    pub(crate) fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            x * self.m11 + y * self.m21 + self.m31,
+            x * self.m12 + y * self.m22 + self.m32,
+        )
+    }
+}
+
+/// Opaque camera handle
+pub struct MiniFBCamera {
+    pub(crate) world_to_screen: MiniFBTransform,
+    pub(crate) screen_to_world: MiniFBTransform,
+    /// Last cursor position while a drag is in progress
+    drag_from: Option<(f32, f32)>,
+    /// Zoom multiplier per scroll wheel unit, minus one
+    pub(crate) zoom_speed: f32,
+}
+
+impl MiniFBCamera {
+    pub(crate) fn new() -> Self {
+        MiniFBCamera {
+            world_to_screen: MiniFBTransform::IDENTITY,
+            screen_to_world: MiniFBTransform::IDENTITY,
+            drag_from: None,
+            zoom_speed: 0.1,
+        }
+    }
+
+    /// Replace the transform, rejecting singular matrices
+    pub(crate) fn set(&mut self, transform: MiniFBTransform) -> bool {
+        match transform.inverse() {
+            Some(inverse) => {
+                self.world_to_screen = transform;
+                self.screen_to_world = inverse;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Compose a screen-space operation after the current transform
+    fn then_screen(&mut self, op: &MiniFBTransform) -> bool {
+        let next = self.world_to_screen.then(op);
+        self.set(next)
+    }
+
+    pub(crate) fn pan(&mut self, dx: f64, dy: f64) -> bool {
+        self.then_screen(&MiniFBTransform::translation(dx, dy))
+    }
+
+    /// Zoom by `factor` keeping the screen point (sx, sy) fixed
+    pub(crate) fn zoom_at(&mut self, factor: f64, sx: f64, sy: f64) -> bool {
+        let op = MiniFBTransform::translation(-sx, -sy)
+            .then(&MiniFBTransform::scale(factor, factor))
+            .then(&MiniFBTransform::translation(sx, sy));
+        self.then_screen(&op)
+    }
+
+    /// Fit a world rectangle into a `width` x `height` viewport with a pixel
+    /// margin, preserving aspect ratio and flipping nothing
+    pub(crate) fn fit(
+        &mut self,
+        world: (f64, f64, f64, f64),
+        width: f64,
+        height: f64,
+        padding: f64,
+    ) -> bool {
+        let (min_x, min_y, max_x, max_y) = world;
+        let (ww, wh) = (max_x - min_x, max_y - min_y);
+        let (vw, vh) = (width - 2.0 * padding, height - 2.0 * padding);
+        if ww <= 0.0 || wh <= 0.0 || vw <= 0.0 || vh <= 0.0 {
+            return false;
+        }
+        let s = (vw / ww).min(vh / wh);
+        let cx = (min_x + max_x) / 2.0;
+        let cy = (min_y + max_y) / 2.0;
+        self.set(
+            MiniFBTransform::translation(-cx, -cy)
+                .then(&MiniFBTransform::scale(s, s))
+                .then(&MiniFBTransform::translation(width / 2.0, height / 2.0)),
+        )
+    }
+
+    /// World-space bounding box of the screen rectangle (0, 0)-(width, height)
+    pub(crate) fn visible_world_rect(&self, width: f64, height: f64) -> (f64, f64, f64, f64) {
+        let corners = [
+            self.screen_to_world.apply(0.0, 0.0),
+            self.screen_to_world.apply(width, 0.0),
+            self.screen_to_world.apply(0.0, height),
+            self.screen_to_world.apply(width, height),
+        ];
+        corners.iter().fold(
+            (
+                f64::INFINITY,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+            ),
+            |(x0, y0, x1, y1), &(x, y)| (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+        )
+    }
+
+    /// Apply one frame of mouse input: drag with `pan_button` pans, the
+    /// scroll wheel zooms around the cursor. Returns true if the view changed
+    pub(crate) fn handle_input(
+        &mut self,
+        mouse: Option<(f32, f32)>,
+        pan_down: bool,
+        scroll: Option<(f32, f32)>,
+    ) -> bool {
+        let mut changed = false;
+        match (mouse, pan_down) {
+            (Some((x, y)), true) => {
+                if let Some((px, py)) = self.drag_from {
+                    if (x, y) != (px, py) {
+                        changed |= self.pan((x - px) as f64, (y - py) as f64);
+                    }
+                }
+                self.drag_from = Some((x, y));
+            }
+            _ => self.drag_from = None,
+        }
+        if let (Some((x, y)), Some((_, sy))) = (mouse, scroll) {
+            if sy != 0.0 {
+                let factor = (1.0 + self.zoom_speed as f64).powf(sy as f64);
+                changed |= self.zoom_at(factor, x as f64, y as f64);
+            }
+        }
+        changed
+    }
+}
+
+/// Create a camera with the identity transform (world units are pixels)
+#[no_mangle]
+pub extern "C" fn minifb_camera_new() -> *mut MiniFBCamera {
+    Box::into_raw(Box::new(MiniFBCamera::new()))
+}
+
+/// Free a camera
+///
+/// # Safety
+/// - `camera` must be a valid pointer returned by `minifb_camera_new`, or null
+/// - After calling this function, the pointer is invalid and must not be used
+#[no_mangle]
+pub unsafe extern "C" fn minifb_camera_free(camera: *mut MiniFBCamera) {
+    if !camera.is_null() {
+        drop(Box::from_raw(camera));
+    }
+}
+
+/// Set the world-to-screen transform
+///
+/// # Safety
+/// - `camera` must be a valid pointer returned by `minifb_camera_new`
+/// - `transform` must be a valid pointer
+///
+/// Returns false (leaving the camera unchanged) if the transform is not invertible
+#[no_mangle]
+pub unsafe extern "C" fn minifb_camera_set_transform(
+    camera: *mut MiniFBCamera,
+    transform: *const MiniFBTransform,
+) -> bool {
+    if camera.is_null() || transform.is_null() {
+        return false;
+    }
+    (*camera).set(*transform)
+}
+
+/// Get the world-to-screen transform
+///
+/// # Safety
+/// - `camera` must be a valid pointer returned by `minifb_camera_new`
+/// - `out` must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn minifb_camera_get_transform(
+    camera: *const MiniFBCamera,
+    out: *mut MiniFBTransform,
+) {
+    if camera.is_null() || out.is_null() {
+        return;
+    }
+    *out = (*camera).world_to_screen;
+}
+
+/// Get the screen-to-world transform
+///
+/// # Safety
+/// - `camera` must be a valid pointer returned by `minifb_camera_new`
+/// - `out` must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn minifb_camera_get_inverse(
+    camera: *const MiniFBCamera,
+    out: *mut MiniFBTransform,
+) {
+    if camera.is_null() || out.is_null() {
+        return;
+    }
+    *out = (*camera).screen_to_world;
+}
+
+/// Map a world-space point to screen pixels
+///
+/// # Safety
+/// - `camera` must be a valid pointer returned by `minifb_camera_new`
+/// - `out_x` and `out_y` must be valid pointers
+#[no_mangle]
+pub unsafe extern "C" fn minifb_camera_world_to_screen(
+    camera: *const MiniFBCamera,
+    x: f64,
+    y: f64,
+    out_x: *mut f64,
+    out_y: *mut f64,
+) {
+    if camera.is_null() || out_x.is_null() || out_y.is_null() {
+        return;
+    }
+    let (sx, sy) = (*camera).world_to_screen.apply(x, y);
+    *out_x = sx;
+    *out_y = sy;
+}
+
+/// Map a screen pixel position to world space
+///
+/// # Safety
+/// - `camera` must be a valid pointer returned by `minifb_camera_new`
+/// - `out_x` and `out_y` must be valid pointers
+#[no_mangle]
+pub unsafe extern "C" fn minifb_camera_screen_to_world(
+    camera: *const MiniFBCamera,
+    x: f64,
+    y: f64,
+    out_x: *mut f64,
+    out_y: *mut f64,
+) {
+    if camera.is_null() || out_x.is_null() || out_y.is_null() {
+        return;
+    }
+    let (wx, wy) = (*camera).screen_to_world.apply(x, y);
+    *out_x = wx;
+    *out_y = wy;
+}
+
+/// Pan the view by a screen-space offset in pixels
+///
+/// # Safety
+/// - `camera` must be a valid pointer returned by `minifb_camera_new`
+#[no_mangle]
+pub unsafe extern "C" fn minifb_camera_pan(camera: *mut MiniFBCamera, dx: f64, dy: f64) {
+    if camera.is_null() {
+        return;
+    }
+    (*camera).pan(dx, dy);
+}
+
+/// Zoom by `factor` around the screen point (x, y)
+///
+/// # Safety
+/// - `camera` must be a valid pointer returned by `minifb_camera_new`
+#[no_mangle]
+pub unsafe extern "C" fn minifb_camera_zoom_at(
+    camera: *mut MiniFBCamera,
+    factor: f64,
+    x: f64,
+    y: f64,
+) {
+    if camera.is_null() {
+        return;
+    }
+    (*camera).zoom_at(factor, x, y);
+}
+
+/// Set the zoom multiplier per scroll wheel unit used by `minifb_camera_handle_input`
+/// (0.1 means each unit zooms by 10%)
+///
+/// # Safety
+/// - `camera` must be a valid pointer returned by `minifb_camera_new`
+#[no_mangle]
+pub unsafe extern "C" fn minifb_camera_set_zoom_speed(camera: *mut MiniFBCamera, speed: f32) {
+    if camera.is_null() {
+        return;
+    }
+    (*camera).zoom_speed = speed;
+}
+
+/// Fit the world rectangle (min_x, min_y)-(max_x, max_y) into a
+/// `width` x `height` viewport, keeping `padding` pixels free on each side
+///
+/// # Safety
+/// - `camera` must be a valid pointer returned by `minifb_camera_new`
+///
+/// Returns false (leaving the camera unchanged) for empty rectangles
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn minifb_camera_fit(
+    camera: *mut MiniFBCamera,
+    min_x: f64,
+    min_y: f64,
+    max_x: f64,
+    max_y: f64,
+    width: usize,
+    height: usize,
+    padding: f64,
+) -> bool {
+    if camera.is_null() {
+        return false;
+    }
+    (*camera).fit(
+        (min_x, min_y, max_x, max_y),
+        width as f64,
+        height as f64,
+        padding,
+    )
+}
+
+/// Get the world-space bounding box visible in a `width` x `height` viewport
+///
+/// # Safety
+/// - `camera` must be a valid pointer returned by `minifb_camera_new`
+/// - all `out_*` pointers must be valid
+#[no_mangle]
+pub unsafe extern "C" fn minifb_camera_get_visible_rect(
+    camera: *const MiniFBCamera,
+    width: usize,
+    height: usize,
+    out_min_x: *mut f64,
+    out_min_y: *mut f64,
+    out_max_x: *mut f64,
+    out_max_y: *mut f64,
+) {
+    if camera.is_null()
+        || out_min_x.is_null()
+        || out_min_y.is_null()
+        || out_max_x.is_null()
+        || out_max_y.is_null()
+    {
+        return;
+    }
+    let (x0, y0, x1, y1) = (*camera).visible_world_rect(width as f64, height as f64);
+    *out_min_x = x0;
+    *out_min_y = y0;
+    *out_max_x = x1;
+    *out_max_y = y1;
+}
+
+/// Apply this frame's window input to the camera: dragging with `pan_button`
+/// held pans, the scroll wheel zooms around the cursor
+///
+/// Call once per frame after `minifb_window_update`.
+///
+/// # Safety
+/// - `camera` must be a valid pointer returned by `minifb_camera_new`
+/// - `window` must be a valid pointer returned by `minifb_window_new`
+///
+/// Returns true if the view changed
+#[no_mangle]
+pub unsafe extern "C" fn minifb_camera_handle_input(
+    camera: *mut MiniFBCamera,
+    window: *const MiniFBWindow,
+    pan_button: MiniFBMouseButton,
+) -> bool {
+    if camera.is_null() || window.is_null() {
+        return false;
+    }
+    let window = &(*window).window;
+    (*camera).handle_input(
+        window.get_mouse_pos(MouseMode::Pass),
+        window.get_mouse_down(pan_button.to_minifb()),
+        window.get_scroll_wheel(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: (f64, f64), b: (f64, f64)) -> bool {
+        (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_inverse_round_trip() {
+        let mut camera = MiniFBCamera::new();
+        assert!(camera.set(MiniFBTransform {
+            m11: 2.0,
+            m12: 0.5,
+            m21: -0.25,
+            m22: 3.0,
+            m31: 10.0,
+            m32: -4.0,
+        }));
+        let screen = camera.world_to_screen.apply(1.5, -2.0);
+        assert!(close(
+            camera.screen_to_world.apply(screen.0, screen.1),
+            (1.5, -2.0)
+        ));
+        assert!(!camera.set(MiniFBTransform::scale(0.0, 1.0)));
+    }
+
+    #[test]
+    fn test_zoom_keeps_cursor_anchored() {
+        let mut camera = MiniFBCamera::new();
+        camera.pan(30.0, 20.0);
+        let before = camera.screen_to_world.apply(100.0, 50.0);
+        assert!(camera.handle_input(Some((100.0, 50.0)), false, Some((0.0, 3.0))));
+        assert!(close(camera.screen_to_world.apply(100.0, 50.0), before));
+        assert!(camera.world_to_screen.m11 > 1.3);
+    }
+}
@@ -0,0 +1,340 @@
+//! Rust-owned framebuffers and the pixel-level raster helpers shared by the
+//! drawing modules.
+
+use crate::dirty::DirtyRect;
+use crate::raster::blend_span;
+use crate::{set_last_error, MiniFBWindow};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_CANVAS_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Opaque Rust-owned framebuffer in minifb's 0RGB u32 layout
+pub struct MiniFBCanvas {
+    pub(crate) pixels: Vec<u32>,
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    /// Unique per canvas, lets a window tell whether it already shows this one
+    pub(crate) id: u64,
+    /// Region changed since the last present
+    pub(crate) dirty: Option<DirtyRect>,
+    /// Bumped by every present, so a window can tell whether `dirty` still
+    /// covers everything changed since its own last upload
+    pub(crate) presents: u64,
+    /// The raw buffer was handed out, so writes may bypass `dirty`
+    pub(crate) shared: bool,
+}
+
+impl MiniFBCanvas {
+    pub(crate) fn new(width: usize, height: usize) -> Self {
+        Self::with_pixels(width, height, vec![0; width * height])
+    }
+
+    /// Like `new`, but None instead of a panic or abort when the pixels
+    /// can't be allocated
+    pub(crate) fn try_new(width: usize, height: usize) -> Option<Self> {
+        Some(Self::with_pixels(
+            width,
+            height,
+            try_alloc(width, height, 0)?,
+        ))
+    }
+
+    fn with_pixels(width: usize, height: usize, pixels: Vec<u32>) -> Self {
+        MiniFBCanvas {
+            pixels,
+            width,
+            height,
+            id: NEXT_CANVAS_ID.fetch_add(1, Ordering::Relaxed),
+            dirty: Some(DirtyRect::full(width, height)),
+            presents: 0,
+            shared: false,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn in_bounds(&self, x: i64, y: i64) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height
+    }
+
+    #[inline]
+    pub(crate) fn set(&mut self, x: i64, y: i64, color: u32) {
+        if self.in_bounds(x, y) {
+            let (x, y) = (x as usize, y as usize);
+            self.pixels[y * self.width + x] = color;
+            self.mark_dirty(x, y, x + 1, y + 1);
+        }
+    }
+
+    /// Blend `color` over the pixel at (x, y) with coverage `alpha` (0-255)
+    #[inline]
+    pub(crate) fn blend(&mut self, x: i64, y: i64, color: u32, alpha: u8) {
+        if !self.in_bounds(x, y) || alpha == 0 {
+            return;
+        }
+        let (x, y) = (x as usize, y as usize);
+        let index = y * self.width + x;
+        self.pixels[index] = lerp_rgb(self.pixels[index], color, alpha);
+        self.mark_dirty(x, y, x + 1, y + 1);
+    }
+
+    /// Blend a solid rectangle over the canvas, clipped to its bounds
+    pub(crate) fn fill_rect(&mut self, x: i64, y: i64, w: i64, h: i64, color: u32, alpha: u8) {
+        let x0 = x.max(0);
+        let y0 = y.max(0);
+        let x1 = x.saturating_add(w).min(self.width as i64);
+        let y1 = y.saturating_add(h).min(self.height as i64);
+        if x0 >= x1 || y0 >= y1 || alpha == 0 {
+            return;
+        }
+        let (x0, y0, x1, y1) = (x0 as usize, y0 as usize, x1 as usize, y1 as usize);
+        for row in self.pixels.chunks_exact_mut(self.width).take(y1).skip(y0) {
+            blend_span(&mut row[x0..x1], color, alpha);
+        }
+        self.mark_dirty(x0, y0, x1, y1);
+    }
+
+    /// Bresenham line between two pixel positions, clipped to the canvas
+    pub(crate) fn line(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, color: u32) {
+        bresenham(x0, y0, x1, y1, |x, y| self.set(x, y, color));
+    }
+}
+
+/// Visit every pixel of the Bresenham line from (x0, y0) to (x1, y1)
+pub(crate) fn bresenham(x0: i64, y0: i64, x1: i64, y1: i64, mut plot: impl FnMut(i64, i64)) {
+    let dx = (x1 - x0).abs();
+    let dy = -(y1 - y0).abs();
+    let sx = if x0 < x1 { 1 } else { -1 };
+    let sy = if y0 < y1 { 1 } else { -1 };
+    let (mut x, mut y) = (x0, y0);
+    let mut err = dx + dy;
+    loop {
+        plot(x, y);
+        if x == x1 && y == y1 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
+/// Pack 8-bit channels into minifb's 0RGB layout
+#[inline]
+pub(crate) fn rgb(r: u8, g: u8, b: u8) -> u32 {
+    ((r as u32) << 16) | ((g as u32) << 8) | b as u32
+}
+
+/// Interpolate between two 0RGB colors per channel, `t` in 0-255
+#[inline]
+pub(crate) fn lerp_rgb(dst: u32, src: u32, t: u8) -> u32 {
+    let t = t as u32;
+    let mix = |shift: u32| {
+        let d = (dst >> shift) & 0xFF;
+        let s = (src >> shift) & 0xFF;
+        ((s * t + d * (255 - t) + 127) / 255) << shift
+    };
+    mix(16) | mix(8) | mix(0)
+}
+
+/// Allocate `width * height` copies of `value`, or None if the size
+/// overflows or the memory isn't available
+pub(crate) fn try_alloc<T: Clone>(width: usize, height: usize, value: T) -> Option<Vec<T>> {
+    let count = width.checked_mul(height)?;
+    if count.checked_mul(std::mem::size_of::<T>())? > isize::MAX as usize {
+        return None;
+    }
+    let mut buffer = Vec::new();
+    buffer.try_reserve_exact(count).ok()?;
+    buffer.resize(count, value);
+    Some(buffer)
+}
+
+/// Create a canvas of the given size, cleared to black
+///
+/// Returns null if either dimension is zero or the pixels can't be allocated
+#[no_mangle]
+pub extern "C" fn minifb_canvas_new(width: usize, height: usize) -> *mut MiniFBCanvas {
+    if width == 0 || height == 0 {
+        set_last_error("canvas dimensions must be non-zero");
+        return std::ptr::null_mut();
+    }
+    match MiniFBCanvas::try_new(width, height) {
+        Some(canvas) => Box::into_raw(Box::new(canvas)),
+        None => {
+            set_last_error("canvas dimensions are too large");
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Free a canvas
+///
+/// # Safety
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`, or null
+/// - After calling this function, the pointer is invalid and must not be used
+#[no_mangle]
+pub unsafe extern "C" fn minifb_canvas_free(canvas: *mut MiniFBCanvas) {
+    if !canvas.is_null() {
+        drop(Box::from_raw(canvas));
+    }
+}
+
+/// Get a pointer to the canvas pixels (`width * height` u32 values, row-major)
+///
+/// Writes through the pointer can't be tracked, so from now on every
+/// present uploads the whole canvas, even if nothing seems to have changed.
+/// `minifb_canvas_get_dirty` only reports them once marked with
+/// `minifb_canvas_mark_dirty`.
+///
+/// # Safety
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+/// - The returned pointer is valid until the canvas is freed
+#[no_mangle]
+pub unsafe extern "C" fn minifb_canvas_get_buffer(canvas: *mut MiniFBCanvas) -> *mut u32 {
+    if canvas.is_null() {
+        return std::ptr::null_mut();
+    }
+    (*canvas).mark_all_dirty();
+    (*canvas).shared = true;
+    (*canvas).pixels.as_mut_ptr()
+}
+
+/// Get the canvas size
+///
+/// # Safety
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+/// - `out_width` and `out_height` must be valid pointers
+#[no_mangle]
+pub unsafe extern "C" fn minifb_canvas_get_size(
+    canvas: *const MiniFBCanvas,
+    out_width: *mut usize,
+    out_height: *mut usize,
+) {
+    if canvas.is_null() || out_width.is_null() || out_height.is_null() {
+        return;
+    }
+    *out_width = (*canvas).width;
+    *out_height = (*canvas).height;
+}
+
+/// Fill the whole canvas with one 0RGB color
+///
+/// # Safety
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+#[no_mangle]
+pub unsafe extern "C" fn minifb_canvas_clear(canvas: *mut MiniFBCanvas, color: u32) {
+    if canvas.is_null() {
+        return;
+    }
+    (*canvas).pixels.fill(color);
+    (*canvas).mark_all_dirty();
+}
+
+/// Update the window with the contents of a canvas
+///
+/// If the window already shows this canvas and nothing was drawn since, the
+/// upload is skipped and only window events are processed. Canvases whose
+/// buffer was handed out by `minifb_canvas_get_buffer` are always uploaded.
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by `minifb_window_new`
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+///
+/// Returns 0 on success, -1 on error
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_update_with_canvas(
+    window: *mut MiniFBWindow,
+    canvas: *mut MiniFBCanvas,
+) -> i32 {
+    if window.is_null() || canvas.is_null() {
+        return -1;
+    }
+    let canvas = &mut *canvas;
+    let window = &mut *window;
+    // Only if no other window presented the canvas since this one did is
+    // `dirty` complete
+    let shown = !canvas.shared
+        && window.presented_canvas == Some((canvas.id, canvas.presents))
+        && !window.viewport_stale();
+    let rect = match canvas.dirty {
+        None if shown => {
+            window.window.update();
+            window.record_skip();
+            return 0;
+        }
+        Some(rect) if shown => rect,
+        _ => DirtyRect::full(canvas.width, canvas.height),
+    };
+    match window.present(&canvas.pixels, canvas.width, canvas.height) {
+        Ok(_) => {
+            canvas.presents += 1;
+            window.record_present(Some((canvas.id, canvas.presents)), rect);
+            canvas.dirty = None;
+            0
+        }
+        Err(e) => {
+            set_last_error(&format!("{:?}", e));
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_is_clipped() {
+        let mut canvas = MiniFBCanvas::new(4, 4);
+        canvas.line(-2, 1, 10, 1, 0xFFFFFF);
+        assert_eq!(&canvas.pixels[4..8], &[0xFFFFFF; 4]);
+        assert!(canvas.pixels[..4].iter().all(|&p| p == 0));
+    }
+
+    #[test]
+    fn test_oversized_canvas_is_null() {
+        assert!(minifb_canvas_new(usize::MAX, 2).is_null());
+        assert!(minifb_canvas_new(1 << 40, 1 << 40).is_null());
+        // The pixel count fits in usize, the byte size doesn't
+        assert!(minifb_canvas_new(1 << 31, 1 << 31).is_null());
+        assert!(minifb_canvas_new(1 << 31, 1 << 30).is_null());
+    }
+
+    #[test]
+    fn test_present_skips_only_unchanged_canvases() {
+        let canvas = minifb_canvas_new(4, 4);
+        let a = crate::backend::minifb_window_new_headless(4, 4);
+        let b = crate::backend::minifb_window_new_headless(4, 4);
+        unsafe {
+            let uploads = |w: *mut MiniFBWindow| (*w).dirty_stats.frames_presented;
+            assert_eq!(minifb_window_update_with_canvas(a, canvas), 0);
+            assert_eq!(minifb_window_update_with_canvas(b, canvas), 0);
+            assert_eq!(minifb_window_update_with_canvas(b, canvas), 0);
+            assert_eq!(uploads(b), 1);
+
+            // A change presented in one window still reaches the other
+            (*canvas).set(1, 1, 0xFF);
+            assert_eq!(minifb_window_update_with_canvas(a, canvas), 0);
+            assert_eq!(minifb_window_update_with_canvas(b, canvas), 0);
+            assert_eq!(uploads(b), 2);
+
+            // Writes through a handed-out buffer can't be seen, so every
+            // present uploads
+            let pixels = minifb_canvas_get_buffer(canvas);
+            assert_eq!(minifb_window_update_with_canvas(a, canvas), 0);
+            *pixels = 0xFF00;
+            assert_eq!(minifb_window_update_with_canvas(a, canvas), 0);
+            assert_eq!(uploads(a), 4);
+
+            crate::minifb_window_free(a);
+            crate::minifb_window_free(b);
+            minifb_canvas_free(canvas);
+        }
+    }
+}
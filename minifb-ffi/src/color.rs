@@ -0,0 +1,326 @@
+//! CSS color string parsing
+//!
+//! Accepts the same strings `Layer.t` hands to SVG: named colors,
+//! `#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa`, and `rgb()`/`rgba()` in both the
+//! comma and the space/slash syntax, with numbers or percentages.
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+/// Straight (non-premultiplied) 8-bit sRGB color with alpha
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MiniFBColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl MiniFBColor {
+    pub(crate) const fn opaque(rgb: u32) -> Self {
+        MiniFBColor {
+            r: (rgb >> 16) as u8,
+            g: (rgb >> 8) as u8,
+            b: rgb as u8,
+            a: 255,
+        }
+    }
+
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if let Some(hex) = s.strip_prefix('#') {
+            return parse_hex(hex);
+        }
+        let lower = s.to_ascii_lowercase();
+        if let Some(args) = lower
+            .strip_prefix("rgba(")
+            .or_else(|| lower.strip_prefix("rgb("))
+        {
+            return parse_rgb_function(args.strip_suffix(')')?);
+        }
+        if lower == "transparent" {
+            return Some(MiniFBColor {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 0,
+            });
+        }
+        NAMED_COLORS
+            .binary_search_by_key(&lower.as_str(), |&(name, _)| name)
+            .ok()
+            .map(|i| MiniFBColor::opaque(NAMED_COLORS[i].1))
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<MiniFBColor> {
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let nibble = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).ok().map(|v| v * 17);
+    let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    let (r, g, b, a) = match hex.len() {
+        3 => (nibble(0)?, nibble(1)?, nibble(2)?, 255),
+        4 => (nibble(0)?, nibble(1)?, nibble(2)?, nibble(3)?),
+        6 => (byte(0)?, byte(2)?, byte(4)?, 255),
+        8 => (byte(0)?, byte(2)?, byte(4)?, byte(6)?),
+        _ => return None,
+    };
+    Some(MiniFBColor { r, g, b, a })
+}
+
+/// A color channel: `0..255` or a percentage
+fn parse_channel(s: &str) -> Option<u8> {
+    let value = match s.strip_suffix('%') {
+        Some(pct) => pct.trim().parse::<f32>().ok()? * 2.55,
+        None => s.parse::<f32>().ok()?,
+    };
+    Some(value.clamp(0.0, 255.0).round() as u8)
+}
+
+/// An alpha value: `0..1` or a percentage
+fn parse_alpha(s: &str) -> Option<u8> {
+    let value = match s.strip_suffix('%') {
+        Some(pct) => pct.trim().parse::<f32>().ok()? / 100.0,
+        None => s.parse::<f32>().ok()?,
+    };
+    Some((value.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+fn parse_rgb_function(args: &str) -> Option<MiniFBColor> {
+    // Both "r, g, b, a" and "r g b / a" are valid CSS
+    let (channels, alpha) = match args.split_once('/') {
+        Some((channels, alpha)) => (channels, Some(alpha.trim())),
+        None => (args, None),
+    };
+    let mut parts: Vec<&str> = channels
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|p| !p.is_empty())
+        .collect();
+    let alpha = match (alpha, parts.len()) {
+        (Some(alpha), 3) => Some(alpha),
+        (None, 4) => parts.pop(),
+        (None, 3) => None,
+        _ => return None,
+    };
+    Some(MiniFBColor {
+        r: parse_channel(parts[0])?,
+        g: parse_channel(parts[1])?,
+        b: parse_channel(parts[2])?,
+        a: match alpha {
+            Some(alpha) => parse_alpha(alpha)?,
+            None => 255,
+        },
+    })
+}
+
+/// Parse a CSS color string
+///
+/// # Safety
+/// - `s` must be a valid null-terminated C string
+/// - `out` must be a valid pointer
+///
+/// Returns true on success; `out` is left untouched on failure
+#[no_mangle]
+pub unsafe extern "C" fn minifb_color_parse(s: *const c_char, out: *mut MiniFBColor) -> bool {
+    if s.is_null() || out.is_null() {
+        return false;
+    }
+    let parsed = CStr::from_ptr(s).to_str().ok().and_then(MiniFBColor::parse);
+    match parsed {
+        Some(color) => {
+            *out = color;
+            true
+        }
+        None => {
+            crate::set_last_error("invalid CSS color");
+            false
+        }
+    }
+}
+
+/// CSS named colors, sorted by name for binary search
+const NAMED_COLORS: [(&str, u32); 148] = [
+    ("aliceblue", 0xF0F8FF),
+    ("antiquewhite", 0xFAEBD7),
+    ("aqua", 0x00FFFF),
+    ("aquamarine", 0x7FFFD4),
+    ("azure", 0xF0FFFF),
+    ("beige", 0xF5F5DC),
+    ("bisque", 0xFFE4C4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xFFEBCD),
+    ("blue", 0x0000FF),
+    ("blueviolet", 0x8A2BE2),
+    ("brown", 0xA52A2A),
+    ("burlywood", 0xDEB887),
+    ("cadetblue", 0x5F9EA0),
+    ("chartreuse", 0x7FFF00),
+    ("chocolate", 0xD2691E),
+    ("coral", 0xFF7F50),
+    ("cornflowerblue", 0x6495ED),
+    ("cornsilk", 0xFFF8DC),
+    ("crimson", 0xDC143C),
+    ("cyan", 0x00FFFF),
+    ("darkblue", 0x00008B),
+    ("darkcyan", 0x008B8B),
+    ("darkgoldenrod", 0xB8860B),
+    ("darkgray", 0xA9A9A9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xA9A9A9),
+    ("darkkhaki", 0xBDB76B),
+    ("darkmagenta", 0x8B008B),
+    ("darkolivegreen", 0x556B2F),
+    ("darkorange", 0xFF8C00),
+    ("darkorchid", 0x9932CC),
+    ("darkred", 0x8B0000),
+    ("darksalmon", 0xE9967A),
+    ("darkseagreen", 0x8FBC8F),
+    ("darkslateblue", 0x483D8B),
+    ("darkslategray", 0x2F4F4F),
+    ("darkslategrey", 0x2F4F4F),
+    ("darkturquoise", 0x00CED1),
+    ("darkviolet", 0x9400D3),
+    ("deeppink", 0xFF1493),
+    ("deepskyblue", 0x00BFFF),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1E90FF),
+    ("firebrick", 0xB22222),
+    ("floralwhite", 0xFFFAF0),
+    ("forestgreen", 0x228B22),
+    ("fuchsia", 0xFF00FF),
+    ("gainsboro", 0xDCDCDC),
+    ("ghostwhite", 0xF8F8FF),
+    ("gold", 0xFFD700),
+    ("goldenrod", 0xDAA520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xADFF2F),
+    ("grey", 0x808080),
+    ("honeydew", 0xF0FFF0),
+    ("hotpink", 0xFF69B4),
+    ("indianred", 0xCD5C5C),
+    ("indigo", 0x4B0082),
+    ("ivory", 0xFFFFF0),
+    ("khaki", 0xF0E68C),
+    ("lavender", 0xE6E6FA),
+    ("lavenderblush", 0xFFF0F5),
+    ("lawngreen", 0x7CFC00),
+    ("lemonchiffon", 0xFFFACD),
+    ("lightblue", 0xADD8E6),
+    ("lightcoral", 0xF08080),
+    ("lightcyan", 0xE0FFFF),
+    ("lightgoldenrodyellow", 0xFAFAD2),
+    ("lightgray", 0xD3D3D3),
+    ("lightgreen", 0x90EE90),
+    ("lightgrey", 0xD3D3D3),
+    ("lightpink", 0xFFB6C1),
+    ("lightsalmon", 0xFFA07A),
+    ("lightseagreen", 0x20B2AA),
+    ("lightskyblue", 0x87CEFA),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xB0C4DE),
+    ("lightyellow", 0xFFFFE0),
+    ("lime", 0x00FF00),
+    ("limegreen", 0x32CD32),
+    ("linen", 0xFAF0E6),
+    ("magenta", 0xFF00FF),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66CDAA),
+    ("mediumblue", 0x0000CD),
+    ("mediumorchid", 0xBA55D3),
+    ("mediumpurple", 0x9370DB),
+    ("mediumseagreen", 0x3CB371),
+    ("mediumslateblue", 0x7B68EE),
+    ("mediumspringgreen", 0x00FA9A),
+    ("mediumturquoise", 0x48D1CC),
+    ("mediumvioletred", 0xC71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xF5FFFA),
+    ("mistyrose", 0xFFE4E1),
+    ("moccasin", 0xFFE4B5),
+    ("navajowhite", 0xFFDEAD),
+    ("navy", 0x000080),
+    ("oldlace", 0xFDF5E6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6B8E23),
+    ("orange", 0xFFA500),
+    ("orangered", 0xFF4500),
+    ("orchid", 0xDA70D6),
+    ("palegoldenrod", 0xEEE8AA),
+    ("palegreen", 0x98FB98),
+    ("paleturquoise", 0xAFEEEE),
+    ("palevioletred", 0xDB7093),
+    ("papayawhip", 0xFFEFD5),
+    ("peachpuff", 0xFFDAB9),
+    ("peru", 0xCD853F),
+    ("pink", 0xFFC0CB),
+    ("plum", 0xDDA0DD),
+    ("powderblue", 0xB0E0E6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xFF0000),
+    ("rosybrown", 0xBC8F8F),
+    ("royalblue", 0x4169E1),
+    ("saddlebrown", 0x8B4513),
+    ("salmon", 0xFA8072),
+    ("sandybrown", 0xF4A460),
+    ("seagreen", 0x2E8B57),
+    ("seashell", 0xFFF5EE),
+    ("sienna", 0xA0522D),
+    ("silver", 0xC0C0C0),
+    ("skyblue", 0x87CEEB),
+    ("slateblue", 0x6A5ACD),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xFFFAFA),
+    ("springgreen", 0x00FF7F),
+    ("steelblue", 0x4682B4),
+    ("tan", 0xD2B48C),
+    ("teal", 0x008080),
+    ("thistle", 0xD8BFD8),
+    ("tomato", 0xFF6347),
+    ("turquoise", 0x40E0D0),
+    ("violet", 0xEE82EE),
+    ("wheat", 0xF5DEB3),
+    ("white", 0xFFFFFF),
+    ("whitesmoke", 0xF5F5F5),
+    ("yellow", 0xFFFF00),
+    ("yellowgreen", 0x9ACD32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_named_colors_sorted() {
+        assert!(NAMED_COLORS.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(
+            MiniFBColor::parse("Red"),
+            Some(MiniFBColor::opaque(0xFF0000))
+        );
+        assert_eq!(MiniFBColor::parse("nonsense"), None);
+    }
+
+    #[test]
+    fn test_hex_and_functional() {
+        let half_red = MiniFBColor {
+            r: 255,
+            g: 0,
+            b: 0,
+            a: 128,
+        };
+        assert_eq!(
+            MiniFBColor::parse("#f00"),
+            Some(MiniFBColor::opaque(0xFF0000))
+        );
+        assert_eq!(MiniFBColor::parse("#ff000080"), Some(half_red));
+        assert_eq!(MiniFBColor::parse("rgba(255, 0, 0, 0.5)"), Some(half_red));
+        assert_eq!(MiniFBColor::parse("rgb(100% 0% 0% / 50%)"), Some(half_red));
+        assert_eq!(MiniFBColor::parse("#12345"), None);
+    }
+}
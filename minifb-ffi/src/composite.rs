@@ -0,0 +1,264 @@
+//! Per-layer coverage compositing
+//!
+//! A `Scene.t` is drawn by compositing one coverage mask per layer, in
+//! order, with the layer's CSS color. SVG renderers blend in sRGB space, so
+//! that is the default here too; linear-light blending is available for
+//! physically correct anti-aliased edges.
+
+use crate::canvas::MiniFBCanvas;
+use crate::color::MiniFBColor;
+use crate::set_last_error;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::slice;
+use std::sync::OnceLock;
+
+/// How a layer's color combines with what is already on the canvas
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiniFBBlendMode {
+    BlendNormal = 0,
+    BlendMultiply = 1,
+    BlendScreen = 2,
+}
+
+/// Layer compositing options
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MiniFBLayerOptions {
+    pub blend: MiniFBBlendMode,
+    /// Multiplied with the coverage and the color's own alpha
+    pub opacity: f32,
+    /// Blend in linear light instead of sRGB
+    pub linear_light: bool,
+}
+
+/// Create default layer options (normal blending, opaque, sRGB space)
+#[no_mangle]
+pub extern "C" fn minifb_layer_options_default() -> MiniFBLayerOptions {
+    MiniFBLayerOptions {
+        blend: MiniFBBlendMode::BlendNormal,
+        opacity: 1.0,
+        linear_light: false,
+    }
+}
+
+fn srgb_to_linear_table() -> &'static [f32; 256] {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [0.0; 256];
+        for (i, v) in table.iter_mut().enumerate() {
+            let c = i as f32 / 255.0;
+            *v = if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            };
+        }
+        table
+    })
+}
+
+#[inline]
+pub(crate) fn srgb_to_linear(c: u8) -> f32 {
+    srgb_to_linear_table()[c as usize]
+}
+
+#[inline]
+pub(crate) fn linear_to_srgb(c: f32) -> u8 {
+    let c = c.clamp(0.0, 1.0);
+    let s = if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (s * 255.0).round() as u8
+}
+
+#[inline]
+fn blend_channel(mode: MiniFBBlendMode, dst: f32, src: f32) -> f32 {
+    match mode {
+        MiniFBBlendMode::BlendNormal => src,
+        MiniFBBlendMode::BlendMultiply => src * dst,
+        MiniFBBlendMode::BlendScreen => src + dst - src * dst,
+    }
+}
+
+/// Composite one pixel; `alpha` already includes coverage and opacity
+#[inline]
+pub(crate) fn composite_pixel(
+    dst: u32,
+    color: MiniFBColor,
+    alpha: f32,
+    mode: MiniFBBlendMode,
+    linear_light: bool,
+) -> u32 {
+    let channel = |shift: u32, src: u8| -> u32 {
+        let d = ((dst >> shift) & 0xFF) as u8;
+        let out = if linear_light {
+            let (d, s) = (srgb_to_linear(d), srgb_to_linear(src));
+            linear_to_srgb(d + (blend_channel(mode, d, s) - d) * alpha)
+        } else {
+            let (d, s) = (d as f32 / 255.0, src as f32 / 255.0);
+            ((d + (blend_channel(mode, d, s) - d) * alpha).clamp(0.0, 1.0) * 255.0).round() as u8
+        };
+        (out as u32) << shift
+    };
+    channel(16, color.r) | channel(8, color.g) | channel(0, color.b)
+}
+
+impl MiniFBCanvas {
+    pub(crate) fn composite(
+        &mut self,
+        coverage: &[f32],
+        color: MiniFBColor,
+        opts: &MiniFBLayerOptions,
+    ) {
+        let base = opts.opacity.clamp(0.0, 1.0) * color.a as f32 / 255.0;
+        let width = self.width;
+        let (mut first, mut last) = (usize::MAX, 0);
+        let (mut x_min, mut x_max) = (usize::MAX, 0);
+        for (i, (pixel, &cov)) in self.pixels.iter_mut().zip(coverage).enumerate() {
+            let alpha = cov.clamp(0.0, 1.0) * base;
+            if alpha > 0.0 {
+                *pixel = composite_pixel(*pixel, color, alpha, opts.blend, opts.linear_light);
+                first = first.min(i);
+                last = i;
+                x_min = x_min.min(i % width);
+                x_max = x_max.max(i % width);
+            }
+        }
+        if first <= last {
+            self.mark_dirty(x_min, first / width, x_max + 1, last / width + 1);
+        }
+    }
+}
+
+/// Convert a sampled signed distance field into an anti-aliased coverage mask
+///
+/// Negative distances are inside. `spacing` is the world-space size of one
+/// sample, so edges get roughly one pixel of anti-aliasing.
+///
+/// # Safety
+/// - `field` and `out_coverage` must each point to at least `width * height` f32 values
+///
+/// Returns 0 on success, -1 on error (see `minifb_get_last_error`)
+#[no_mangle]
+pub unsafe extern "C" fn minifb_field_to_coverage(
+    field: *const f32,
+    width: usize,
+    height: usize,
+    spacing: f32,
+    out_coverage: *mut f32,
+) -> i32 {
+    if field.is_null() || out_coverage.is_null() {
+        return -1;
+    }
+    if !(spacing > 0.0 && spacing.is_finite()) {
+        set_last_error("sample spacing must be positive");
+        return -1;
+    }
+    let Some(count) = width.checked_mul(height) else {
+        set_last_error("field dimensions too large");
+        return -1;
+    };
+    let field = slice::from_raw_parts(field, count);
+    let out = slice::from_raw_parts_mut(out_coverage, count);
+    for (o, &d) in out.iter_mut().zip(field) {
+        let c = 0.5 - d / spacing;
+        *o = if c.is_nan() { 0.0 } else { c.clamp(0.0, 1.0) };
+    }
+    0
+}
+
+/// Composite a layer's coverage mask onto a canvas in a CSS color
+///
+/// # Safety
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+/// - `coverage` must point to at least `width * height` f32 values in 0.0-1.0
+/// - `color` must be a valid null-terminated C string
+/// - `opts` must be a valid pointer to MiniFBLayerOptions
+///
+/// The mask dimensions must match the canvas. Returns 0 on success, -1 on error
+#[no_mangle]
+pub unsafe extern "C" fn minifb_canvas_composite_layer(
+    canvas: *mut MiniFBCanvas,
+    coverage: *const f32,
+    width: usize,
+    height: usize,
+    color: *const c_char,
+    opts: *const MiniFBLayerOptions,
+) -> i32 {
+    if canvas.is_null() || coverage.is_null() || color.is_null() || opts.is_null() {
+        return -1;
+    }
+    let canvas = &mut *canvas;
+    if canvas.width != width || canvas.height != height {
+        set_last_error("coverage dimensions do not match canvas");
+        return -1;
+    }
+    let color = match CStr::from_ptr(color)
+        .to_str()
+        .ok()
+        .and_then(MiniFBColor::parse)
+    {
+        Some(color) => color,
+        None => {
+            set_last_error("invalid CSS color");
+            return -1;
+        }
+    };
+    let coverage = slice::from_raw_parts(coverage, width * height);
+    canvas.composite(coverage, color, &*opts);
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend_modes() {
+        let white = MiniFBColor::opaque(0xFFFFFF);
+        let red = MiniFBColor::opaque(0xFF0000);
+        let mode = |m| composite_pixel(0x808080, red, 1.0, m, false);
+        assert_eq!(mode(MiniFBBlendMode::BlendNormal), 0xFF0000);
+        assert_eq!(mode(MiniFBBlendMode::BlendMultiply), 0x800000);
+        assert_eq!(mode(MiniFBBlendMode::BlendScreen), 0xFF8080);
+        assert_eq!(
+            composite_pixel(0x000000, white, 0.5, MiniFBBlendMode::BlendNormal, false),
+            0x808080
+        );
+    }
+
+    #[test]
+    fn test_linear_light_half_coverage_is_brighter() {
+        let white = MiniFBColor::opaque(0xFFFFFF);
+        // 50% linear-light coverage of white over black is sRGB 188, not 128
+        let pixel = composite_pixel(0x000000, white, 0.5, MiniFBBlendMode::BlendNormal, true);
+        assert_eq!(pixel, 0xBCBCBC);
+    }
+
+    #[test]
+    fn test_field_to_coverage_spacing() {
+        let field = [-1.0f32, 0.0, 0.25, 2.0];
+        let mut out = [9.0f32; 4];
+        unsafe {
+            assert_eq!(
+                minifb_field_to_coverage(field.as_ptr(), 2, 2, 1.0, out.as_mut_ptr()),
+                0
+            );
+            assert_eq!(out, [1.0, 0.5, 0.25, 0.0]);
+            for spacing in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+                let mut out = [9.0f32; 4];
+                let result =
+                    minifb_field_to_coverage(field.as_ptr(), 2, 2, spacing, out.as_mut_ptr());
+                assert_eq!(result, -1);
+                assert_eq!(out, [9.0; 4]);
+            }
+            let result =
+                minifb_field_to_coverage(field.as_ptr(), usize::MAX, 2, 1.0, out.as_mut_ptr());
+            assert_eq!(result, -1);
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! Damage tracking for canvases and presentation statistics for windows
+//!
+//! Every canvas drawing routine widens the canvas' dirty rectangle. Presenting
+//! a canvas that the window already shows and that has no damage skips the
+//! buffer upload and only pumps events, so idle frames stay cheap.
+
+use crate::canvas::MiniFBCanvas;
+use crate::MiniFBWindow;
+
+/// Half-open pixel rectangle [x0, x1) x [y0, y1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DirtyRect {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl DirtyRect {
+    pub(crate) fn full(width: usize, height: usize) -> Self {
+        DirtyRect {
+            x0: 0,
+            y0: 0,
+            x1: width,
+            y1: height,
+        }
+    }
+
+    pub(crate) fn area(&self) -> usize {
+        (self.x1 - self.x0) * (self.y1 - self.y0)
+    }
+}
+
+/// Grow `dirty` to cover [x0, x1) x [y0, y1); empty rectangles are ignored
+#[inline]
+pub(crate) fn extend(dirty: &mut Option<DirtyRect>, x0: usize, y0: usize, x1: usize, y1: usize) {
+    if x0 >= x1 || y0 >= y1 {
+        return;
+    }
+    *dirty = Some(match *dirty {
+        Some(r) => DirtyRect {
+            x0: r.x0.min(x0),
+            y0: r.y0.min(y0),
+            x1: r.x1.max(x1),
+            y1: r.y1.max(y1),
+        },
+        None => DirtyRect { x0, y0, x1, y1 },
+    });
+}
+
+/// Presentation counters for one window
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MiniFBDirtyStats {
+    /// Frames whose pixels were uploaded
+    pub frames_presented: u64,
+    /// Canvas presents skipped because nothing changed
+    pub frames_skipped: u64,
+    /// Damaged region of the most recent uploaded frame
+    pub last_x: usize,
+    pub last_y: usize,
+    pub last_width: usize,
+    pub last_height: usize,
+    /// Sum of damaged pixels over all uploaded frames
+    pub total_dirty_pixels: u64,
+}
+
+impl MiniFBCanvas {
+    /// Mark [x0, x1) x [y0, y1) as changed, clipped to the canvas
+    #[inline]
+    pub(crate) fn mark_dirty(&mut self, x0: usize, y0: usize, x1: usize, y1: usize) {
+        extend(
+            &mut self.dirty,
+            x0,
+            y0,
+            x1.min(self.width),
+            y1.min(self.height),
+        );
+    }
+
+    pub(crate) fn mark_all_dirty(&mut self) {
+        self.dirty = Some(DirtyRect::full(self.width, self.height));
+    }
+}
+
+impl MiniFBWindow {
+    /// Record an upload; `canvas` is the id and present count of the
+    /// presented canvas, if any
+    pub(crate) fn record_present(&mut self, canvas: Option<(u64, u64)>, rect: DirtyRect) {
+        self.presented_canvas = canvas;
+        let stats = &mut self.dirty_stats;
+        stats.frames_presented += 1;
+        stats.last_x = rect.x0;
+        stats.last_y = rect.y0;
+        stats.last_width = rect.x1 - rect.x0;
+        stats.last_height = rect.y1 - rect.y0;
+        stats.total_dirty_pixels += rect.area() as u64;
+    }
+
+    pub(crate) fn record_skip(&mut self) {
+        self.dirty_stats.frames_skipped += 1;
+        self.dirty_stats.last_width = 0;
+        self.dirty_stats.last_height = 0;
+    }
+}
+
+/// Mark a region of the canvas as changed
+///
+/// Lets `minifb_canvas_get_dirty` report writes made through the pointer from
+/// `minifb_canvas_get_buffer`; presents upload such canvases whole anyway.
+///
+/// # Safety
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+#[no_mangle]
+pub unsafe extern "C" fn minifb_canvas_mark_dirty(
+    canvas: *mut MiniFBCanvas,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) {
+    if canvas.is_null() {
+        return;
+    }
+    (*canvas).mark_dirty(x, y, x.saturating_add(width), y.saturating_add(height));
+}
+
+/// Get the region changed since the canvas was last presented
+///
+/// # Safety
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+/// - All out pointers must be valid
+///
+/// Returns false, leaving the outputs untouched, if nothing changed
+#[no_mangle]
+pub unsafe extern "C" fn minifb_canvas_get_dirty(
+    canvas: *const MiniFBCanvas,
+    out_x: *mut usize,
+    out_y: *mut usize,
+    out_width: *mut usize,
+    out_height: *mut usize,
+) -> bool {
+    if canvas.is_null()
+        || out_x.is_null()
+        || out_y.is_null()
+        || out_width.is_null()
+        || out_height.is_null()
+    {
+        return false;
+    }
+    match (*canvas).dirty {
+        Some(r) => {
+            *out_x = r.x0;
+            *out_y = r.y0;
+            *out_width = r.x1 - r.x0;
+            *out_height = r.y1 - r.y0;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Get the window's presentation statistics
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by `minifb_window_new`
+/// - `out_stats` must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_get_dirty_stats(
+    window: *const MiniFBWindow,
+    out_stats: *mut MiniFBDirtyStats,
+) {
+    if window.is_null() || out_stats.is_null() {
+        return;
+    }
+    *out_stats = (*window).dirty_stats;
+}
+
+/// Reset the window's presentation statistics to zero
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by `minifb_window_new`
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_reset_dirty_stats(window: *mut MiniFBWindow) {
+    if window.is_null() {
+        return;
+    }
+    (*window).dirty_stats = MiniFBDirtyStats::default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drawing_marks_dirty() {
+        let mut canvas = MiniFBCanvas::new(16, 16);
+        assert_eq!(canvas.dirty, Some(DirtyRect::full(16, 16)));
+        canvas.dirty = None;
+        canvas.set(3, 4, 1);
+        canvas.line(10, 2, 12, 6, 1);
+        canvas.set(-5, 40, 1);
+        assert_eq!(
+            canvas.dirty,
+            Some(DirtyRect {
+                x0: 3,
+                y0: 2,
+                x1: 13,
+                y1: 7
+            })
+        );
+        canvas.mark_dirty(14, 14, 100, 100);
+        assert_eq!(canvas.dirty.map(|r| (r.x1, r.y1)), Some((16, 16)));
+    }
+}
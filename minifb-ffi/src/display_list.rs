@@ -0,0 +1,393 @@
+//! Retained display lists
+//!
+//! A display list keeps world-space geometry, text and images on the Rust
+//! side, so a pan or zoom only needs a new camera and one render call instead
+//! of resubmitting the scene. Every item carries a layer id; hidden layers
+//! are skipped when rendering. Runs of consecutive vector items go through
+//! the parallel batch rasterizer, text and images are drawn in between in
+//! list order.
+
+use crate::camera::{MiniFBCamera, MiniFBTransform};
+use crate::canvas::MiniFBCanvas;
+use crate::draw::{camera_transform, read_points, read_rings};
+use crate::image::{MiniFBBlitOptions, MiniFBImage};
+use crate::raster::{MiniFBBatch, Shape};
+use crate::set_last_error;
+use crate::text::{MiniFBFont, MiniFBTextStyle};
+use std::collections::HashSet;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::slice;
+
+pub(crate) enum ItemKind {
+    /// Independent segments, as in a `Line_buffer.t`
+    Segments(Vec<((f64, f64), (f64, f64))>),
+    Polyline {
+        points: Vec<(f64, f64)>,
+        closed: bool,
+    },
+    /// Rings filled together with the even-odd rule
+    Fill(Vec<Vec<(f64, f64)>>),
+    /// Text anchored at a world-space point, drawn at its pixel size
+    Text {
+        text: String,
+        anchor: (f64, f64),
+        style: MiniFBTextStyle,
+    },
+    Image {
+        image: MiniFBImage,
+        rect: (f64, f64, f64, f64),
+        opts: MiniFBBlitOptions,
+    },
+}
+
+pub(crate) struct Item {
+    pub layer: u32,
+    pub color: u32,
+    pub opacity: f32,
+    pub kind: ItemKind,
+}
+
+/// Opaque retained list of world-space drawing items
+#[derive(Default)]
+pub struct MiniFBDisplayList {
+    pub(crate) items: Vec<Item>,
+    hidden: HashSet<u32>,
+}
+
+impl MiniFBDisplayList {
+    pub(crate) fn is_visible(&self, layer: u32) -> bool {
+        !self.hidden.contains(&layer)
+    }
+
+    fn push(&mut self, layer: u32, color: u32, opacity: f32, kind: ItemKind) {
+        self.items.push(Item {
+            layer,
+            color,
+            opacity,
+            kind,
+        });
+    }
+}
+
+impl MiniFBCanvas {
+    pub(crate) fn draw_display_list(
+        &mut self,
+        list: &MiniFBDisplayList,
+        transform: &MiniFBTransform,
+        font: &mut MiniFBFont,
+    ) {
+        let mut batch = MiniFBBatch::default();
+        let apply = |p: &(f64, f64)| transform.apply(p.0, p.1);
+        for item in list.items.iter().filter(|i| list.is_visible(i.layer)) {
+            let (color, opacity) = (item.color, item.opacity);
+            match &item.kind {
+                ItemKind::Segments(segments) => {
+                    for (a, b) in segments {
+                        batch.push(Shape::Segment(apply(a), apply(b)), color, opacity);
+                    }
+                }
+                ItemKind::Polyline { points, closed } => {
+                    let points: Vec<(f64, f64)> = points.iter().map(apply).collect();
+                    for pair in points.windows(2) {
+                        batch.push(Shape::Segment(pair[0], pair[1]), color, opacity);
+                    }
+                    if *closed {
+                        let last = points[points.len() - 1];
+                        batch.push(Shape::Segment(last, points[0]), color, opacity);
+                    }
+                }
+                ItemKind::Fill(rings) => {
+                    let rings = rings.iter().map(|r| r.iter().map(apply).collect());
+                    batch.push(Shape::Rings(rings.collect()), color, opacity);
+                }
+                ItemKind::Text {
+                    text,
+                    anchor,
+                    style,
+                } => {
+                    self.draw_batch(&batch, true);
+                    batch = MiniFBBatch::default();
+                    let (x, y) = apply(anchor);
+                    font.draw(self, text, x.round() as i64, y.round() as i64, style);
+                }
+                ItemKind::Image { image, rect, opts } => {
+                    self.draw_batch(&batch, true);
+                    batch = MiniFBBatch::default();
+                    self.blit(image, *rect, transform, opts);
+                }
+            }
+        }
+        self.draw_batch(&batch, true);
+    }
+}
+
+/// Create an empty display list
+#[no_mangle]
+pub extern "C" fn minifb_display_list_new() -> *mut MiniFBDisplayList {
+    Box::into_raw(Box::default())
+}
+
+/// Free a display list
+///
+/// # Safety
+/// - `list` must be a valid pointer returned by `minifb_display_list_new`, or null
+/// - After calling this function, the pointer is invalid and must not be used
+#[no_mangle]
+pub unsafe extern "C" fn minifb_display_list_free(list: *mut MiniFBDisplayList) {
+    if !list.is_null() {
+        drop(Box::from_raw(list));
+    }
+}
+
+/// Remove all items; layer visibility is kept
+///
+/// # Safety
+/// - `list` must be a valid pointer returned by `minifb_display_list_new`
+#[no_mangle]
+pub unsafe extern "C" fn minifb_display_list_clear(list: *mut MiniFBDisplayList) {
+    if list.is_null() {
+        return;
+    }
+    (*list).items.clear();
+}
+
+/// Get the number of items in a display list
+///
+/// # Safety
+/// - `list` must be a valid pointer returned by `minifb_display_list_new`
+#[no_mangle]
+pub unsafe extern "C" fn minifb_display_list_len(list: *const MiniFBDisplayList) -> usize {
+    if list.is_null() {
+        return 0;
+    }
+    (*list).items.len()
+}
+
+/// Show or hide every item on a layer (all layers start visible)
+///
+/// # Safety
+/// - `list` must be a valid pointer returned by `minifb_display_list_new`
+#[no_mangle]
+pub unsafe extern "C" fn minifb_display_list_set_layer_visible(
+    list: *mut MiniFBDisplayList,
+    layer: u32,
+    visible: bool,
+) {
+    if list.is_null() {
+        return;
+    }
+    if visible {
+        (*list).hidden.remove(&layer);
+    } else {
+        (*list).hidden.insert(layer);
+    }
+}
+
+/// Check whether a layer is visible
+///
+/// # Safety
+/// - `list` must be a valid pointer returned by `minifb_display_list_new`
+#[no_mangle]
+pub unsafe extern "C" fn minifb_display_list_is_layer_visible(
+    list: *const MiniFBDisplayList,
+    layer: u32,
+) -> bool {
+    !list.is_null() && (*list).is_visible(layer)
+}
+
+/// Add one-pixel segments stored as `x0, y0, x1, y1` per segment
+///
+/// # Safety
+/// - `list` must be a valid pointer returned by `minifb_display_list_new`
+/// - `points` must point to at least `4 * segment_count` f32 values
+#[no_mangle]
+pub unsafe extern "C" fn minifb_display_list_add_segments(
+    list: *mut MiniFBDisplayList,
+    layer: u32,
+    points: *const f32,
+    segment_count: usize,
+    color: u32,
+    opacity: f32,
+) {
+    if list.is_null() || points.is_null() {
+        return;
+    }
+    let segments = slice::from_raw_parts(points, segment_count * 4)
+        .chunks_exact(4)
+        .map(|s| ((s[0] as f64, s[1] as f64), (s[2] as f64, s[3] as f64)))
+        .collect();
+    (*list).push(layer, color, opacity, ItemKind::Segments(segments));
+}
+
+/// Add a one-pixel polyline through `count` points
+///
+/// # Safety
+/// - `list` must be a valid pointer returned by `minifb_display_list_new`
+/// - `points` must point to at least `2 * count` f32 values
+#[no_mangle]
+pub unsafe extern "C" fn minifb_display_list_add_polyline(
+    list: *mut MiniFBDisplayList,
+    layer: u32,
+    points: *const f32,
+    count: usize,
+    closed: bool,
+    color: u32,
+    opacity: f32,
+) {
+    if list.is_null() || points.is_null() || count < 2 {
+        return;
+    }
+    let points = read_points(points, count, &MiniFBTransform::IDENTITY);
+    (*list).push(layer, color, opacity, ItemKind::Polyline { points, closed });
+}
+
+/// Add a polygon filled with the even-odd rule (see `minifb_canvas_fill_polygon`)
+///
+/// # Safety
+/// - `list` must be a valid pointer returned by `minifb_display_list_new`
+/// - `ring_sizes` must point to `ring_count` point counts
+/// - `points` must point to `2 * sum(ring_sizes)` f32 values, rings back to back
+#[no_mangle]
+pub unsafe extern "C" fn minifb_display_list_add_polygon(
+    list: *mut MiniFBDisplayList,
+    layer: u32,
+    points: *const f32,
+    ring_sizes: *const usize,
+    ring_count: usize,
+    color: u32,
+    opacity: f32,
+) {
+    if list.is_null() || points.is_null() || ring_sizes.is_null() {
+        return;
+    }
+    let rings = read_rings(points, ring_sizes, ring_count, &MiniFBTransform::IDENTITY);
+    (*list).push(layer, color, opacity, ItemKind::Fill(rings));
+}
+
+/// Add text anchored at a world-space point
+///
+/// # Safety
+/// - `list` must be a valid pointer returned by `minifb_display_list_new`
+/// - `text` must be a valid null-terminated UTF-8 C string
+/// - `style` must be a valid pointer to MiniFBTextStyle
+///
+/// Returns 0 on success, -1 on error
+#[no_mangle]
+pub unsafe extern "C" fn minifb_display_list_add_text(
+    list: *mut MiniFBDisplayList,
+    layer: u32,
+    text: *const c_char,
+    x: f64,
+    y: f64,
+    style: *const MiniFBTextStyle,
+) -> i32 {
+    if list.is_null() || text.is_null() || style.is_null() {
+        return -1;
+    }
+    let text = match CStr::from_ptr(text).to_str() {
+        Ok(s) => s.to_string(),
+        Err(_) => {
+            set_last_error("text is not valid UTF-8");
+            return -1;
+        }
+    };
+    let style = *style;
+    let kind = ItemKind::Text {
+        text,
+        anchor: (x, y),
+        style,
+    };
+    (*list).push(layer, style.color, 1.0, kind);
+    0
+}
+
+/// Add an image covering a world-space rectangle
+///
+/// The list keeps its own copy of the pixels, so `image` may be freed after
+/// this call.
+///
+/// # Safety
+/// - `list` must be a valid pointer returned by `minifb_display_list_new`
+/// - `image` must be a valid pointer returned by `minifb_image_load` or `minifb_image_decode`
+/// - `opts` must be a valid pointer to MiniFBBlitOptions
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn minifb_display_list_add_image(
+    list: *mut MiniFBDisplayList,
+    layer: u32,
+    image: *const MiniFBImage,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    opts: *const MiniFBBlitOptions,
+) {
+    if list.is_null() || image.is_null() || opts.is_null() {
+        return;
+    }
+    let kind = ItemKind::Image {
+        image: (*image).clone(),
+        rect: (x, y, width, height),
+        opts: *opts,
+    };
+    (*list).push(layer, 0, (*opts).opacity, kind);
+}
+
+/// Render the visible layers of a display list into a canvas
+///
+/// # Safety
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+/// - `list` must be a valid pointer returned by `minifb_display_list_new`
+/// - `camera` must be a valid camera pointer, or null for pixel coordinates
+/// - `font` must be a valid font pointer, or null for the built-in font at scale 1
+#[no_mangle]
+pub unsafe extern "C" fn minifb_canvas_draw_display_list(
+    canvas: *mut MiniFBCanvas,
+    list: *const MiniFBDisplayList,
+    camera: *const MiniFBCamera,
+    font: *mut MiniFBFont,
+) {
+    if canvas.is_null() || list.is_null() {
+        return;
+    }
+    let mut builtin;
+    let font = if font.is_null() {
+        builtin = MiniFBFont::builtin(1);
+        &mut builtin
+    } else {
+        &mut *font
+    };
+    (*canvas).draw_display_list(&*list, &camera_transform(camera), font);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layers_and_camera() {
+        let mut list = MiniFBDisplayList::default();
+        let square = vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)];
+        list.push(1, 0xFF0000, 1.0, ItemKind::Fill(vec![square]));
+        list.push(
+            2,
+            0x00FF00,
+            1.0,
+            ItemKind::Segments(vec![((0.0, 3.0), (3.0, 3.0))]),
+        );
+        let mut font = MiniFBFont::builtin(1);
+
+        let zoom = MiniFBTransform::scale(2.0, 2.0);
+        let mut canvas = MiniFBCanvas::new(8, 8);
+        canvas.draw_display_list(&list, &zoom, &mut font);
+        assert_eq!(canvas.pixels[3 * 8 + 3], 0xFF0000);
+        assert_eq!(canvas.pixels[6 * 8 + 5], 0x00FF00);
+
+        unsafe { minifb_display_list_set_layer_visible(&mut list, 1, false) };
+        let mut canvas = MiniFBCanvas::new(8, 8);
+        canvas.draw_display_list(&list, &zoom, &mut font);
+        assert_eq!(canvas.pixels[3 * 8 + 3], 0);
+        assert_eq!(canvas.pixels[6 * 8 + 5], 0x00FF00);
+    }
+}
@@ -0,0 +1,475 @@
+//! Vector drawing primitives: lines, polylines and filled polygons
+//!
+//! Every primitive takes an optional camera. Points are in world space when
+//! a camera is given and in pixels otherwise. Point arrays are flat
+//! `x0, y0, x1, y1, ...` float32 buffers, as produced from `Connected.t`.
+
+use crate::camera::{MiniFBCamera, MiniFBTransform};
+use crate::canvas::MiniFBCanvas;
+use crate::dirty::extend;
+use crate::raster::{MiniFBBatch, Shape, TILE_SIZE};
+use std::ops::Range;
+use std::slice;
+
+/// Clip a segment to the rectangle [x_min, x_max] x [y_min, y_max] (Liang-Barsky)
+pub(crate) fn clip_segment(
+    (x0, y0): (f64, f64),
+    (x1, y1): (f64, f64),
+    bounds: (f64, f64, f64, f64),
+) -> Option<((f64, f64), (f64, f64))> {
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let (t0, t1) = clip_range((x0, y0), (x1, y1), bounds)?;
+    Some(((x0 + t0 * dx, y0 + t0 * dy), (x0 + t1 * dx, y0 + t1 * dy)))
+}
+
+/// The part of the segment inside the rectangle, as a range of its
+/// parameter from 0 (start) to 1 (end)
+pub(crate) fn clip_range(
+    (x0, y0): (f64, f64),
+    (x1, y1): (f64, f64),
+    (x_min, y_min, x_max, y_max): (f64, f64, f64, f64),
+) -> Option<(f64, f64)> {
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let mut t0: f64 = 0.0;
+    let mut t1: f64 = 1.0;
+    for (p, q) in [
+        (-dx, x0 - x_min),
+        (dx, x_max - x0),
+        (-dy, y0 - y_min),
+        (dy, y_max - y0),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let r = q / p;
+            if p < 0.0 {
+                t0 = t0.max(r);
+            } else {
+                t1 = t1.min(r);
+            }
+        }
+    }
+    if t0 > t1 || !t0.is_finite() || !t1.is_finite() {
+        return None;
+    }
+    Some((t0, t1))
+}
+
+/// Call `span(y, x_start, x_end)` for every run of pixels whose centers lie
+/// inside the rings under the even-odd rule; `x_end` is exclusive
+pub(crate) fn fill_spans(
+    width: usize,
+    height: usize,
+    rings: &[Vec<(f64, f64)>],
+    span: impl FnMut(usize, usize, usize),
+) {
+    fill_spans_in_rows(width, height, rings, 0..height, span);
+}
+
+/// `fill_spans` restricted to the rows in `rows`, so disjoint row ranges of
+/// one polygon can be rasterized independently
+pub(crate) fn fill_spans_in_rows(
+    width: usize,
+    height: usize,
+    rings: &[Vec<(f64, f64)>],
+    rows: Range<usize>,
+    mut span: impl FnMut(usize, usize, usize),
+) {
+    let edges: Vec<((f64, f64), (f64, f64))> = rings
+        .iter()
+        .filter(|ring| ring.len() >= 3)
+        .flat_map(|ring| {
+            ring.iter()
+                .zip(ring.iter().cycle().skip(1))
+                .map(|(&a, &b)| (a, b))
+        })
+        .filter(|((_, ay), (_, by))| ay != by)
+        .collect();
+    if edges.is_empty() {
+        return;
+    }
+    let y_lo = edges
+        .iter()
+        .map(|(a, b)| a.1.min(b.1))
+        .fold(f64::INFINITY, f64::min);
+    let y_hi = edges
+        .iter()
+        .map(|(a, b)| a.1.max(b.1))
+        .fold(f64::NEG_INFINITY, f64::max);
+    let row_start = ((y_lo - 0.5).ceil().max(0.0) as usize).max(rows.start);
+    let row_end = (((y_hi - 0.5).floor() + 1.0).clamp(0.0, height as f64) as usize).min(rows.end);
+
+    let mut crossings: Vec<f64> = Vec::new();
+    for y in row_start..row_end {
+        let sample_y = y as f64 + 0.5;
+        crossings.clear();
+        for &((ax, ay), (bx, by)) in &edges {
+            // Half-open so shared vertices are counted once
+            if (ay <= sample_y) != (by <= sample_y) {
+                crossings.push(ax + (sample_y - ay) * (bx - ax) / (by - ay));
+            }
+        }
+        crossings.sort_by(|a, b| a.total_cmp(b));
+        for pair in crossings.chunks_exact(2) {
+            let start = (pair[0] - 0.5).ceil().clamp(0.0, width as f64) as usize;
+            let end = ((pair[1] - 0.5).ceil()).clamp(0.0, width as f64) as usize;
+            if start < end {
+                span(y, start, end);
+            }
+        }
+    }
+}
+
+/// Polylines with at least this many segments are drawn through the tiled
+/// rasterizer on the thread pool
+const PARALLEL_SEGMENTS: usize = 256;
+
+/// Polygons at least this tall are filled through the tiled rasterizer on
+/// the thread pool
+const PARALLEL_ROWS: usize = 2 * TILE_SIZE;
+
+/// Vertical samples per pixel row used by `fill_coverage` when anti-aliasing
+const COVERAGE_SUBROWS: usize = 4;
+
+/// Add a horizontal run [xa, xb) with weight `w` to a coverage row, splitting
+/// the end pixels by their covered fraction
+fn add_coverage(cov: &mut [f32], xa: f64, xb: f64, w: f32) {
+    let xa = xa.clamp(0.0, cov.len() as f64);
+    let xb = xb.clamp(0.0, cov.len() as f64);
+    if xb <= xa {
+        return;
+    }
+    let (ia, ib) = (xa.floor() as usize, xb.floor() as usize);
+    if ia == ib {
+        cov[ia] += (xb - xa) as f32 * w;
+        return;
+    }
+    cov[ia] += (ia as f64 + 1.0 - xa) as f32 * w;
+    for c in &mut cov[ia + 1..ib] {
+        *c += w;
+    }
+    if ib < cov.len() {
+        cov[ib] += (xb - ib as f64) as f32 * w;
+    }
+}
+
+/// Call `row(y, x_start, coverage)` for every pixel row touched by the rings
+/// under the nonzero winding rule, with coverage in 0.0-1.0 per pixel
+///
+/// With `antialias` each row is sampled at several heights and partially
+/// covered pixels get exact horizontal coverage; without it only pixel
+/// centers count, as in `fill_spans`.
+pub(crate) fn fill_coverage(
+    width: usize,
+    height: usize,
+    rings: &[Vec<(f64, f64)>],
+    antialias: bool,
+    mut row: impl FnMut(usize, usize, &[f32]),
+) {
+    // (x_top, y_top, x_bottom, y_bottom, winding)
+    let mut edges: Vec<(f64, f64, f64, f64, i32)> = rings
+        .iter()
+        .filter(|ring| ring.len() >= 3)
+        .flat_map(|ring| ring.iter().zip(ring.iter().cycle().skip(1)))
+        .filter(|(a, b)| a.1 != b.1)
+        .map(|(&a, &b)| {
+            if a.1 < b.1 {
+                (a.0, a.1, b.0, b.1, 1)
+            } else {
+                (b.0, b.1, a.0, a.1, -1)
+            }
+        })
+        .collect();
+    if edges.is_empty() || width == 0 {
+        return;
+    }
+    let y_lo = edges.iter().map(|e| e.1).fold(f64::INFINITY, f64::min);
+    let y_hi = edges.iter().map(|e| e.3).fold(f64::NEG_INFINITY, f64::max);
+    let row_start = y_lo.floor().max(0.0) as usize;
+    let row_end = y_hi.ceil().clamp(0.0, height as f64) as usize;
+    let subrows = if antialias { COVERAGE_SUBROWS } else { 1 };
+    let weight = 1.0 / subrows as f32;
+
+    // Active edge table: samples only look at the edges spanning them, so
+    // many short edges (round joins, long paths) stay cheap
+    edges.sort_by(|a, b| a.1.total_cmp(&b.1));
+    let mut next_edge = 0;
+    let mut active: Vec<(f64, f64, f64, f64, i32)> = Vec::new();
+
+    let mut cov = vec![0.0f32; width];
+    let mut crossings: Vec<(f64, i32)> = Vec::new();
+    for y in row_start..row_end {
+        let (mut lo, mut hi) = (width, 0);
+        for s in 0..subrows {
+            let sample_y = y as f64 + (s as f64 + 0.5) / subrows as f64;
+            while next_edge < edges.len() && edges[next_edge].1 <= sample_y {
+                active.push(edges[next_edge]);
+                next_edge += 1;
+            }
+            active.retain(|e| sample_y < e.3);
+            crossings.clear();
+            for &(x0, y0, x1, y1, dir) in &active {
+                crossings.push((x0 + (sample_y - y0) * (x1 - x0) / (y1 - y0), dir));
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut winding = 0;
+            let mut start = 0.0;
+            for &(x, dir) in &crossings {
+                let was_inside = winding != 0;
+                winding += dir;
+                if !was_inside && winding != 0 {
+                    start = x;
+                } else if was_inside && winding == 0 {
+                    let (xa, xb) = if antialias {
+                        (start, x)
+                    } else {
+                        ((start - 0.5).ceil(), (x - 0.5).ceil())
+                    };
+                    add_coverage(&mut cov, xa, xb, weight);
+                    lo = lo.min(xa.floor().clamp(0.0, width as f64) as usize);
+                    hi = hi.max(xb.ceil().clamp(0.0, width as f64) as usize);
+                }
+            }
+        }
+        if lo < hi {
+            row(y, lo, &cov[lo..hi]);
+            cov[lo..hi].fill(0.0);
+        }
+    }
+}
+
+impl MiniFBCanvas {
+    /// Line between two screen-space points, clipped before rasterizing so
+    /// far-off-screen endpoints stay cheap
+    pub(crate) fn line_f(&mut self, a: (f64, f64), b: (f64, f64), color: u32) {
+        let bounds = (-1.0, -1.0, self.width as f64, self.height as f64);
+        if let Some((a, b)) = clip_segment(a, b, bounds) {
+            self.line(
+                a.0.round() as i64,
+                a.1.round() as i64,
+                b.0.round() as i64,
+                b.1.round() as i64,
+                color,
+            );
+        }
+    }
+
+    pub(crate) fn fill_rings(&mut self, rings: &[Vec<(f64, f64)>], color: u32) {
+        let (y_lo, y_hi) = rings
+            .iter()
+            .flatten()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), p| {
+                (lo.min(p.1), hi.max(p.1))
+            });
+        if y_hi - y_lo >= PARALLEL_ROWS as f64 {
+            let mut batch = MiniFBBatch::default();
+            batch.push(Shape::Rings(rings.to_vec()), color, 1.0);
+            self.draw_batch(&batch, true);
+            return;
+        }
+        let width = self.width;
+        let pixels = &mut self.pixels;
+        let dirty = &mut self.dirty;
+        fill_spans(self.width, self.height, rings, |y, x0, x1| {
+            pixels[y * width + x0..y * width + x1].fill(color);
+            extend(dirty, x0, y, x1, y + 1);
+        });
+    }
+}
+
+/// Resolve an optional camera to the transform applied to input points
+pub(crate) unsafe fn camera_transform(camera: *const MiniFBCamera) -> MiniFBTransform {
+    if camera.is_null() {
+        MiniFBTransform::IDENTITY
+    } else {
+        (*camera).world_to_screen
+    }
+}
+
+/// Read `count` xy pairs and map them through `transform`
+pub(crate) unsafe fn read_points(
+    points: *const f32,
+    count: usize,
+    transform: &MiniFBTransform,
+) -> Vec<(f64, f64)> {
+    slice::from_raw_parts(points, count * 2)
+        .chunks_exact(2)
+        .map(|p| transform.apply(p[0] as f64, p[1] as f64))
+        .collect()
+}
+
+/// Read `ring_count` rings stored back to back, sized by `ring_sizes`
+pub(crate) unsafe fn read_rings(
+    points: *const f32,
+    ring_sizes: *const usize,
+    ring_count: usize,
+    transform: &MiniFBTransform,
+) -> Vec<Vec<(f64, f64)>> {
+    let mut offset = 0;
+    slice::from_raw_parts(ring_sizes, ring_count)
+        .iter()
+        .map(|&size| {
+            let ring = read_points(points.add(offset * 2), size, transform);
+            offset += size;
+            ring
+        })
+        .collect()
+}
+
+/// Draw a one-pixel line
+///
+/// # Safety
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+/// - `camera` must be a valid camera pointer, or null for pixel coordinates
+#[no_mangle]
+pub unsafe extern "C" fn minifb_canvas_draw_line(
+    canvas: *mut MiniFBCanvas,
+    x0: f32,
+    y0: f32,
+    x1: f32,
+    y1: f32,
+    color: u32,
+    camera: *const MiniFBCamera,
+) {
+    if canvas.is_null() {
+        return;
+    }
+    let transform = camera_transform(camera);
+    (*canvas).line_f(
+        transform.apply(x0 as f64, y0 as f64),
+        transform.apply(x1 as f64, y1 as f64),
+        color,
+    );
+}
+
+/// Draw a one-pixel polyline through `count` points
+///
+/// # Safety
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+/// - `points` must point to at least `2 * count` f32 values
+/// - `camera` must be a valid camera pointer, or null for pixel coordinates
+#[no_mangle]
+pub unsafe extern "C" fn minifb_canvas_draw_polyline(
+    canvas: *mut MiniFBCanvas,
+    points: *const f32,
+    count: usize,
+    closed: bool,
+    color: u32,
+    camera: *const MiniFBCamera,
+) {
+    if canvas.is_null() || points.is_null() || count < 2 {
+        return;
+    }
+    let points = read_points(points, count, &camera_transform(camera));
+    if count >= PARALLEL_SEGMENTS {
+        let mut batch = MiniFBBatch::default();
+        for pair in points.windows(2) {
+            batch.push(Shape::Segment(pair[0], pair[1]), color, 1.0);
+        }
+        if closed {
+            batch.push(Shape::Segment(points[count - 1], points[0]), color, 1.0);
+        }
+        (*canvas).draw_batch(&batch, true);
+        return;
+    }
+    for pair in points.windows(2) {
+        (*canvas).line_f(pair[0], pair[1], color);
+    }
+    if closed {
+        (*canvas).line_f(points[count - 1], points[0], color);
+    }
+}
+
+/// Fill a polygon made of one or more rings using the even-odd rule, so
+/// inner rings cut holes regardless of their orientation
+///
+/// # Safety
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+/// - `ring_sizes` must point to `ring_count` point counts
+/// - `points` must point to `2 * sum(ring_sizes)` f32 values, rings back to back
+/// - `camera` must be a valid camera pointer, or null for pixel coordinates
+#[no_mangle]
+pub unsafe extern "C" fn minifb_canvas_fill_polygon(
+    canvas: *mut MiniFBCanvas,
+    points: *const f32,
+    ring_sizes: *const usize,
+    ring_count: usize,
+    color: u32,
+    camera: *const MiniFBCamera,
+) {
+    if canvas.is_null() || points.is_null() || ring_sizes.is_null() {
+        return;
+    }
+    let rings = read_rings(points, ring_sizes, ring_count, &camera_transform(camera));
+    (*canvas).fill_rings(&rings, color);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x0: f64, y0: f64, x1: f64, y1: f64) -> Vec<(f64, f64)> {
+        vec![(x0, y0), (x1, y0), (x1, y1), (x0, y1)]
+    }
+
+    #[test]
+    fn test_fill_with_hole() {
+        let mut canvas = MiniFBCanvas::new(10, 10);
+        canvas.fill_rings(
+            &[square(0.0, 0.0, 10.0, 10.0), square(3.0, 3.0, 7.0, 7.0)],
+            1,
+        );
+        let filled = canvas.pixels.iter().filter(|&&p| p == 1).count();
+        assert_eq!(filled, 100 - 16);
+        assert_eq!(canvas.pixels[5 * 10 + 5], 0);
+    }
+
+    #[test]
+    fn test_tall_fill_matches_scanline() {
+        // A star with a hole, tall enough to go through the tiled rasterizer
+        let star: Vec<(f64, f64)> = (0..22)
+            .map(|i| {
+                let (r, a) = (if i % 2 == 0 { 140.0 } else { 55.0 }, i as f64 * 0.2856);
+                (150.0 + r * a.cos(), 150.0 + r * a.sin())
+            })
+            .collect();
+        let rings = [star, square(120.0, 130.0, 170.0, 180.0)];
+        let mut tiled = MiniFBCanvas::new(300, 300);
+        tiled.fill_rings(&rings, 0xABCDEF);
+        let mut reference = vec![0u32; 300 * 300];
+        fill_spans(300, 300, &rings, |y, x0, x1| {
+            reference[y * 300 + x0..y * 300 + x1].fill(0xABCDEF)
+        });
+        assert!(tiled.pixels == reference);
+    }
+
+    #[test]
+    fn test_coverage_of_overlapping_discs() {
+        // Many short edges, overlapping under the nonzero rule: the covered
+        // area is that of one disc
+        let disc: Vec<(f64, f64)> = (0..128)
+            .map(|i| {
+                let a = i as f64 * std::f64::consts::TAU / 128.0;
+                (20.0 + 10.0 * a.cos(), 20.0 + 10.0 * a.sin())
+            })
+            .collect();
+        let mut area = 0.0;
+        fill_coverage(40, 40, &[disc.clone(), disc], true, |_, _, row| {
+            area += row.iter().map(|&c| c.min(1.0) as f64).sum::<f64>();
+        });
+        assert!(
+            (area - std::f64::consts::PI * 100.0).abs() < 1.0,
+            "{}",
+            area
+        );
+    }
+
+    #[test]
+    fn test_far_line_is_clipped() {
+        let mut canvas = MiniFBCanvas::new(8, 8);
+        canvas.line_f((-1e12, 4.0), (1e12, 4.0), 7);
+        assert!(canvas.pixels[32..40].iter().all(|&p| p == 7));
+    }
+}
@@ -0,0 +1,970 @@
+//! Unix-socket frame server for showing images from other processes
+//!
+//! A long-lived viewer creates a `MiniFBFrameServer` on a socket path and
+//! polls it from its main loop; programs that don't want to open windows of
+//! their own connect with a `MiniFBFrameClient`, open surfaces and push
+//! frames to them. Surfaces are ordinary windows owned by the server and stay
+//! up after the client that opened them exits, until closed by the user or
+//! the client.
+//!
+//! Every message is a kind byte and a little-endian u32 payload length
+//! followed by the payload; all numbers are little-endian. Each request gets
+//! exactly one reply.
+//!
+//! | Request | Payload | Reply |
+//! |---|---|---|
+//! | 1 open | width u32, height u32, title | surface |
+//! | 2 frame | surface u32, width u32, height u32, format u8, tone map u8, linear u8, 0 u8, range min f32, range max f32, exposure f32, pixels | ok |
+//! | 3 title | surface u32, title | ok |
+//! | 4 input | surface u32 | input |
+//! | 5 close | surface u32 | ok |
+//!
+//! Replies are 0 ok (empty), 1 error (UTF-8 message), 2 surface (id u32) and
+//! 3 input (open u8, has mouse u8, buttons u8, 0 u8, mouse x f32, mouse y
+//! f32, scroll x f32, scroll y f32, key count u32, key codes i32 each).
+
+use crate::present::{convert, MiniFBPixelFormat, MiniFBPixelOptions, MiniFBToneMap};
+use crate::{set_last_error, MiniFBKey, MiniFBWindow};
+use minifb::{MouseButton, MouseMode};
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::io::{self, Read, Write};
+use std::os::raw::{c_char, c_void};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+const MSG_OPEN: u8 = 1;
+const MSG_FRAME: u8 = 2;
+const MSG_TITLE: u8 = 3;
+const MSG_INPUT: u8 = 4;
+const MSG_CLOSE: u8 = 5;
+
+const REPLY_OK: u8 = 0;
+const REPLY_ERROR: u8 = 1;
+const REPLY_SURFACE: u8 = 2;
+const REPLY_INPUT: u8 = 3;
+
+/// Fixed part of a frame request before the pixels
+const FRAME_HEADER: usize = 28;
+/// Largest accepted payload, to keep a bad length from exhausting memory
+const MAX_PAYLOAD: usize = 1 << 30;
+/// Largest surface width or height a client can open
+const MAX_SURFACE_SIDE: usize = 16384;
+
+/// Maximum number of held keys reported in `MiniFBSurfaceInput`
+pub const MINIFB_SURFACE_MAX_KEYS: usize = 16;
+
+/// Input state of a surface as seen by a client
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MiniFBSurfaceInput {
+    /// False once the user closed the surface's window
+    pub open: bool,
+    pub has_mouse: bool,
+    pub mouse_x: f32,
+    pub mouse_y: f32,
+    /// Held buttons: bit 0 left, bit 1 middle, bit 2 right
+    pub mouse_buttons: u32,
+    /// Scrolling since the previous input query
+    pub scroll_x: f32,
+    pub scroll_y: f32,
+    pub key_count: u32,
+    /// Held keys as MiniFBKey codes
+    pub keys: [i32; MINIFB_SURFACE_MAX_KEYS],
+}
+
+impl MiniFBSurfaceInput {
+    fn encode(&self) -> Vec<u8> {
+        let mut out = vec![
+            self.open as u8,
+            self.has_mouse as u8,
+            self.mouse_buttons as u8,
+            0,
+        ];
+        for v in [self.mouse_x, self.mouse_y, self.scroll_x, self.scroll_y] {
+            out.extend(v.to_le_bytes());
+        }
+        out.extend(self.key_count.to_le_bytes());
+        for &key in &self.keys[..self.key_count as usize] {
+            out.extend(key.to_le_bytes());
+        }
+        out
+    }
+
+    fn decode(b: &[u8]) -> Option<Self> {
+        let f32_at = |i: usize| {
+            b.get(i..i + 4)
+                .map(|v| f32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+        };
+        let key_count = u32_at(b, 20)?.min(MINIFB_SURFACE_MAX_KEYS as u32);
+        let mut input = MiniFBSurfaceInput {
+            open: *b.first()? != 0,
+            has_mouse: *b.get(1)? != 0,
+            mouse_buttons: *b.get(2)? as u32,
+            mouse_x: f32_at(4)?,
+            mouse_y: f32_at(8)?,
+            scroll_x: f32_at(12)?,
+            scroll_y: f32_at(16)?,
+            key_count,
+            keys: [0; MINIFB_SURFACE_MAX_KEYS],
+        };
+        for (i, key) in input.keys[..key_count as usize].iter_mut().enumerate() {
+            *key = u32_at(b, 24 + 4 * i)? as i32;
+        }
+        Some(input)
+    }
+}
+
+fn u32_at(b: &[u8], i: usize) -> Option<u32> {
+    b.get(i..i + 4)
+        .map(|v| u32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+}
+
+fn write_message(stream: &mut UnixStream, kind: u8, payload: &[u8]) -> io::Result<()> {
+    let mut header = [kind, 0, 0, 0, 0];
+    header[1..].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+    stream.write_all(&header)?;
+    stream.write_all(payload)
+}
+
+fn read_message(stream: &mut UnixStream) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0; 5];
+    stream.read_exact(&mut header)?;
+    let len = u32::from_le_bytes([header[1], header[2], header[3], header[4]]) as usize;
+    if len > MAX_PAYLOAD {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message too large",
+        ));
+    }
+    let mut payload = vec![0; len];
+    stream.read_exact(&mut payload)?;
+    Ok((header[0], payload))
+}
+
+fn bytes_per_pixel(format: MiniFBPixelFormat) -> usize {
+    match format {
+        MiniFBPixelFormat::FormatGray8 => 1,
+        MiniFBPixelFormat::FormatRgba8 | MiniFBPixelFormat::FormatBgra8 => 4,
+        MiniFBPixelFormat::FormatGrayF32 => 4,
+        MiniFBPixelFormat::FormatRgbF32 => 12,
+    }
+}
+
+enum Command {
+    Open {
+        title: String,
+        width: usize,
+        height: usize,
+    },
+    Frame {
+        surface: u32,
+        pixels: Vec<u32>,
+        width: usize,
+        height: usize,
+    },
+    Title {
+        surface: u32,
+        title: String,
+    },
+    Input {
+        surface: u32,
+    },
+    Close {
+        surface: u32,
+    },
+}
+
+enum Reply {
+    Ok,
+    Error(String),
+    Surface(u32),
+    Input(MiniFBSurfaceInput),
+}
+
+/// Decode a request; frames are converted to 0RGB here, off the main thread
+fn parse_command(kind: u8, payload: Vec<u8>) -> Result<Command, String> {
+    let surface = || u32_at(&payload, 0).ok_or("truncated request");
+    let text = |from: usize| {
+        String::from_utf8(payload.get(from..).unwrap_or_default().to_vec())
+            .map_err(|_| "title is not UTF-8".to_string())
+    };
+    Ok(match kind {
+        MSG_OPEN => {
+            let width = surface()? as usize;
+            let height = u32_at(&payload, 4).ok_or("truncated request")? as usize;
+            if width > MAX_SURFACE_SIDE || height > MAX_SURFACE_SIDE {
+                return Err(format!(
+                    "surface sides are limited to {} pixels",
+                    MAX_SURFACE_SIDE
+                ));
+            }
+            Command::Open {
+                width,
+                height,
+                title: text(8)?,
+            }
+        }
+        MSG_FRAME => {
+            if payload.len() < FRAME_HEADER {
+                return Err("truncated request".into());
+            }
+            let format = match payload[12] {
+                0 => MiniFBPixelFormat::FormatGray8,
+                1 => MiniFBPixelFormat::FormatRgba8,
+                2 => MiniFBPixelFormat::FormatBgra8,
+                3 => MiniFBPixelFormat::FormatGrayF32,
+                4 => MiniFBPixelFormat::FormatRgbF32,
+                _ => return Err("unknown pixel format".into()),
+            };
+            let tone_map = match payload[13] {
+                0 => MiniFBToneMap::ToneMapClamp,
+                1 => MiniFBToneMap::ToneMapReinhard,
+                2 => MiniFBToneMap::ToneMapAces,
+                _ => return Err("unknown tone map".into()),
+            };
+            let f32_at = |i: usize| f32::from_bits(u32_at(&payload, i).unwrap_or(0));
+            let opts = MiniFBPixelOptions {
+                format,
+                range_min: f32_at(16),
+                range_max: f32_at(20),
+                exposure: f32_at(24),
+                tone_map,
+                linear: payload[14] != 0,
+            };
+            let width = u32_at(&payload, 4).unwrap_or(0) as usize;
+            let height = u32_at(&payload, 8).unwrap_or(0) as usize;
+            let data = &payload[FRAME_HEADER..];
+            let expected = width
+                .checked_mul(height)
+                .and_then(|n| n.checked_mul(bytes_per_pixel(format)));
+            if width * height == 0 || expected != Some(data.len()) {
+                return Err("pixel data does not match dimensions and format".into());
+            }
+            // Copy into words so float formats are aligned for `convert`
+            let aligned: Vec<u32> = data
+                .chunks(4)
+                .map(|c| {
+                    let mut word = [0; 4];
+                    word[..c.len()].copy_from_slice(c);
+                    u32::from_ne_bytes(word)
+                })
+                .collect();
+            let mut pixels = vec![0; width * height];
+            // SAFETY: `aligned` holds width * height pixels of `format`
+            unsafe {
+                convert(
+                    aligned.as_ptr() as *const c_void,
+                    width,
+                    height,
+                    &opts,
+                    &mut pixels,
+                )
+            };
+            Command::Frame {
+                surface: surface()?,
+                pixels,
+                width,
+                height,
+            }
+        }
+        MSG_TITLE => Command::Title {
+            surface: surface()?,
+            title: text(4)?,
+        },
+        MSG_INPUT => Command::Input {
+            surface: surface()?,
+        },
+        MSG_CLOSE => Command::Close {
+            surface: surface()?,
+        },
+        _ => return Err(format!("unknown request {}", kind)),
+    })
+}
+
+/// Forward one client's requests to the server and write back the replies
+fn serve_connection(mut stream: UnixStream, commands: Sender<(Command, Sender<Reply>)>) {
+    while let Ok((kind, payload)) = read_message(&mut stream) {
+        let reply = match parse_command(kind, payload) {
+            Ok(command) => {
+                // One channel per request, so a request dropped unanswered
+                // along with the server ends the wait below
+                let (reply_tx, reply_rx) = mpsc::channel();
+                if commands.send((command, reply_tx)).is_err() {
+                    return;
+                }
+                match reply_rx.recv() {
+                    Ok(reply) => reply,
+                    Err(_) => return,
+                }
+            }
+            Err(e) => Reply::Error(e),
+        };
+        let written = match reply {
+            Reply::Ok => write_message(&mut stream, REPLY_OK, &[]),
+            Reply::Error(e) => write_message(&mut stream, REPLY_ERROR, e.as_bytes()),
+            Reply::Surface(id) => write_message(&mut stream, REPLY_SURFACE, &id.to_le_bytes()),
+            Reply::Input(input) => write_message(&mut stream, REPLY_INPUT, &input.encode()),
+        };
+        if written.is_err() {
+            return;
+        }
+    }
+}
+
+struct Surface {
+    /// None once the user closed the window
+    window: Option<Box<MiniFBWindow>>,
+    scroll: (f32, f32),
+}
+
+/// Opaque frame server handle
+pub struct MiniFBFrameServer {
+    path: PathBuf,
+    commands: Receiver<(Command, Sender<Reply>)>,
+    surfaces: HashMap<u32, Surface>,
+    next_surface: u32,
+    stop: Arc<AtomicBool>,
+    /// Open connections by id, shut down when the server is freed
+    connections: Arc<Mutex<HashMap<u64, UnixStream>>>,
+    accept: Option<JoinHandle<()>>,
+    /// Create surfaces without a display regardless of `MINIFB_FFI_BACKEND`
+    headless: bool,
+}
+
+impl MiniFBFrameServer {
+    fn bind(path: PathBuf) -> io::Result<Self> {
+        // A socket file left behind by a viewer that didn't exit cleanly;
+        // anything else at the path is left alone
+        if UnixStream::connect(&path).is_err() {
+            match std::fs::symlink_metadata(&path) {
+                Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(&path)?,
+                Ok(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        "path exists and is not a socket",
+                    ))
+                }
+                Err(_) => {}
+            }
+        }
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+        let (tx, commands) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let connections = Arc::new(Mutex::new(HashMap::new()));
+        let accept = {
+            let (stop, connections) = (stop.clone(), connections.clone());
+            std::thread::spawn(move || {
+                let mut next_id = 0u64;
+                while !stop.load(Ordering::Relaxed) {
+                    let Ok((stream, _)) = listener.accept() else {
+                        std::thread::sleep(Duration::from_millis(50));
+                        continue;
+                    };
+                    if stream.set_nonblocking(false).is_err() {
+                        continue;
+                    }
+                    let id = next_id;
+                    next_id += 1;
+                    if let Ok(clone) = stream.try_clone() {
+                        let mut connections = connections.lock().unwrap_or_else(|e| e.into_inner());
+                        connections.insert(id, clone);
+                    }
+                    let (tx, connections) = (tx.clone(), connections.clone());
+                    std::thread::spawn(move || {
+                        serve_connection(stream, tx);
+                        let mut connections = connections.lock().unwrap_or_else(|e| e.into_inner());
+                        connections.remove(&id);
+                    });
+                }
+            })
+        };
+        Ok(MiniFBFrameServer {
+            path,
+            commands,
+            surfaces: HashMap::new(),
+            next_surface: 1,
+            stop,
+            connections,
+            accept: Some(accept),
+            headless: false,
+        })
+    }
+
+    fn open_window(
+        &self,
+        title: &str,
+        width: usize,
+        height: usize,
+    ) -> Result<Box<MiniFBWindow>, String> {
+        let window = if self.headless {
+            crate::backend::minifb_window_new_headless(width, height)
+        } else {
+            let title = CString::new(title).map_err(|_| "title contains a null byte")?;
+            // SAFETY: both pointers are valid for the duration of the call
+            unsafe {
+                crate::minifb_window_new(
+                    title.as_ptr(),
+                    width,
+                    height,
+                    &crate::minifb_window_options_default(),
+                )
+            }
+        };
+        if window.is_null() {
+            return Err(crate::last_error().unwrap_or_else(|| "window creation failed".into()));
+        }
+        // SAFETY: freshly created by a window constructor
+        let mut window = unsafe { Box::from_raw(window) };
+        // Pacing comes from the server's poll loop
+        window.window.set_target_fps(0);
+        Ok(window)
+    }
+
+    fn handle(&mut self, command: Command) -> Reply {
+        let surface_id = match &command {
+            Command::Open { .. } => 0,
+            Command::Frame { surface, .. }
+            | Command::Title { surface, .. }
+            | Command::Input { surface }
+            | Command::Close { surface } => *surface,
+        };
+        if let Command::Open {
+            title,
+            width,
+            height,
+        } = command
+        {
+            return match self.open_window(&title, width, height) {
+                Ok(window) => {
+                    let id = self.next_surface;
+                    self.next_surface += 1;
+                    self.surfaces.insert(
+                        id,
+                        Surface {
+                            window: Some(window),
+                            scroll: (0.0, 0.0),
+                        },
+                    );
+                    Reply::Surface(id)
+                }
+                Err(e) => Reply::Error(e),
+            };
+        }
+        let Some(surface) = self.surfaces.get_mut(&surface_id) else {
+            return Reply::Error(format!("no surface {}", surface_id));
+        };
+        match (command, &mut surface.window) {
+            (Command::Close { .. }, _) => {
+                self.surfaces.remove(&surface_id);
+                Reply::Ok
+            }
+            (Command::Input { .. }, window) => {
+                Reply::Input(surface_input(window.as_deref(), &mut surface.scroll))
+            }
+            (_, None) => Reply::Error(format!("surface {} was closed", surface_id)),
+            (Command::Title { title, .. }, Some(window)) => {
+                window.window.set_title(&title);
+                Reply::Ok
+            }
+            (
+                Command::Frame {
+                    pixels,
+                    width,
+                    height,
+                    ..
+                },
+                Some(window),
+            ) => match window.present(&pixels, width, height) {
+                Ok(()) => {
+                    window.record_present(None, crate::dirty::DirtyRect::full(width, height));
+                    surface.scroll = add_scroll(surface.scroll, window);
+                    Reply::Ok
+                }
+                Err(e) => Reply::Error(format!("{:?}", e)),
+            },
+            (Command::Open { .. }, _) => unreachable!("handled above"),
+        }
+    }
+
+    /// Handle waiting requests, then keep every surface's window responsive
+    fn poll(&mut self, timeout: Duration) -> usize {
+        let mut next = self.commands.recv_timeout(timeout);
+        loop {
+            match next {
+                Ok((command, reply)) => {
+                    let _ = reply.send(self.handle(command));
+                }
+                Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => break,
+            }
+            next = self.commands.recv_timeout(Duration::ZERO);
+        }
+        for surface in self.surfaces.values_mut() {
+            if let Some(window) = &mut surface.window {
+                window.window.update();
+                surface.scroll = add_scroll(surface.scroll, window);
+                if !window.window.is_open() {
+                    surface.window = None;
+                }
+            }
+        }
+        self.surfaces
+            .values()
+            .filter(|s| s.window.is_some())
+            .count()
+    }
+}
+
+fn add_scroll(scroll: (f32, f32), window: &MiniFBWindow) -> (f32, f32) {
+    match window.window.get_scroll_wheel() {
+        Some((x, y)) => (scroll.0 + x, scroll.1 + y),
+        None => scroll,
+    }
+}
+
+/// Snapshot a surface's input and restart scroll accumulation
+fn surface_input(window: Option<&MiniFBWindow>, scroll: &mut (f32, f32)) -> MiniFBSurfaceInput {
+    let Some(window) = window else {
+        return MiniFBSurfaceInput::default();
+    };
+    let mouse = window.window.get_mouse_pos(MouseMode::Pass);
+    let buttons = [MouseButton::Left, MouseButton::Middle, MouseButton::Right];
+    let mut input = MiniFBSurfaceInput {
+        open: true,
+        has_mouse: mouse.is_some(),
+        mouse_x: mouse.map_or(0.0, |m| m.0),
+        mouse_y: mouse.map_or(0.0, |m| m.1),
+        mouse_buttons: buttons
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| window.window.get_mouse_down(b))
+            .fold(0, |mask, (i, _)| mask | 1 << i),
+        scroll_x: scroll.0,
+        scroll_y: scroll.1,
+        ..Default::default()
+    };
+    *scroll = (0.0, 0.0);
+    for key in window
+        .window
+        .get_keys()
+        .into_iter()
+        .take(MINIFB_SURFACE_MAX_KEYS)
+    {
+        input.keys[input.key_count as usize] = MiniFBKey::from_minifb(key) as i32;
+        input.key_count += 1;
+    }
+    input
+}
+
+impl Drop for MiniFBFrameServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(accept) = self.accept.take() {
+            let _ = accept.join();
+        }
+        let connections = self.connections.lock().unwrap_or_else(|e| e.into_inner());
+        for stream in connections.values() {
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+        }
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Opaque frame client handle
+pub struct MiniFBFrameClient {
+    stream: UnixStream,
+}
+
+impl MiniFBFrameClient {
+    /// Send a request and return the reply payload, or set the last error
+    fn request(&mut self, kind: u8, payload: &[&[u8]], expect: u8) -> Option<Vec<u8>> {
+        let len: usize = payload.iter().map(|p| p.len()).sum();
+        let result = (|| {
+            let mut header = vec![kind];
+            header.extend((len as u32).to_le_bytes());
+            self.stream.write_all(&header)?;
+            for part in payload {
+                self.stream.write_all(part)?;
+            }
+            read_message(&mut self.stream)
+        })();
+        match result {
+            Ok((kind, reply)) if kind == expect => Some(reply),
+            Ok((REPLY_ERROR, message)) => {
+                set_last_error(&String::from_utf8_lossy(&message));
+                None
+            }
+            Ok(_) => {
+                set_last_error("unexpected reply from frame server");
+                None
+            }
+            Err(e) => {
+                set_last_error(&format!("frame server: {}", e));
+                None
+            }
+        }
+    }
+}
+
+unsafe fn c_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    match CStr::from_ptr(s).to_str() {
+        Ok(s) => Some(s),
+        Err(_) => {
+            set_last_error("string is not UTF-8");
+            None
+        }
+    }
+}
+
+/// Start a frame server listening on a Unix socket path
+///
+/// A stale socket file at `path` is replaced. Requests are only handled
+/// inside `minifb_frame_server_poll`, so surfaces' windows live on the
+/// thread that polls.
+///
+/// # Safety
+/// - `path` must be a valid null-terminated C string
+///
+/// Returns null on error (see `minifb_get_last_error`)
+#[no_mangle]
+pub unsafe extern "C" fn minifb_frame_server_new(path: *const c_char) -> *mut MiniFBFrameServer {
+    let Some(path) = c_str(path) else {
+        return std::ptr::null_mut();
+    };
+    match MiniFBFrameServer::bind(PathBuf::from(path)) {
+        Ok(server) => Box::into_raw(Box::new(server)),
+        Err(e) => {
+            set_last_error(&format!("frame server: {}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Free a frame server, closing its surfaces and removing the socket file
+///
+/// # Safety
+/// - `server` must be a valid pointer returned by `minifb_frame_server_new`, or null
+/// - After calling this function, the pointer is invalid and must not be used
+#[no_mangle]
+pub unsafe extern "C" fn minifb_frame_server_free(server: *mut MiniFBFrameServer) {
+    if !server.is_null() {
+        drop(Box::from_raw(server));
+    }
+}
+
+/// Handle client requests and update all surfaces
+///
+/// Waits up to `timeout_ms` for the first request, so calling this in a loop
+/// with about 16 ms gives a responsive viewer that is idle between frames.
+///
+/// # Safety
+/// - `server` must be a valid pointer returned by `minifb_frame_server_new`
+///
+/// Returns the number of surfaces whose windows are still open
+#[no_mangle]
+pub unsafe extern "C" fn minifb_frame_server_poll(
+    server: *mut MiniFBFrameServer,
+    timeout_ms: u32,
+) -> usize {
+    if server.is_null() {
+        return 0;
+    }
+    (*server).poll(Duration::from_millis(timeout_ms as u64))
+}
+
+/// Connect to a frame server
+///
+/// # Safety
+/// - `path` must be a valid null-terminated C string
+///
+/// Returns null on error (see `minifb_get_last_error`)
+#[no_mangle]
+pub unsafe extern "C" fn minifb_frame_client_connect(
+    path: *const c_char,
+) -> *mut MiniFBFrameClient {
+    let Some(path) = c_str(path) else {
+        return std::ptr::null_mut();
+    };
+    match UnixStream::connect(path) {
+        Ok(stream) => Box::into_raw(Box::new(MiniFBFrameClient { stream })),
+        Err(e) => {
+            set_last_error(&format!("frame server: {}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Disconnect from a frame server; surfaces opened by the client stay up
+///
+/// # Safety
+/// - `client` must be a valid pointer returned by `minifb_frame_client_connect`, or null
+/// - After calling this function, the pointer is invalid and must not be used
+#[no_mangle]
+pub unsafe extern "C" fn minifb_frame_client_free(client: *mut MiniFBFrameClient) {
+    if !client.is_null() {
+        drop(Box::from_raw(client));
+    }
+}
+
+/// Open a surface (a window in the server process)
+///
+/// # Safety
+/// - `client` must be a valid pointer returned by `minifb_frame_client_connect`
+/// - `title` must be a valid null-terminated C string
+///
+/// Returns the surface id, or -1 on error
+#[no_mangle]
+pub unsafe extern "C" fn minifb_frame_client_open(
+    client: *mut MiniFBFrameClient,
+    title: *const c_char,
+    width: u32,
+    height: u32,
+) -> i64 {
+    if client.is_null() {
+        return -1;
+    }
+    let Some(title) = c_str(title) else {
+        return -1;
+    };
+    let size = [width.to_le_bytes(), height.to_le_bytes()].concat();
+    match (*client).request(MSG_OPEN, &[&size, title.as_bytes()], REPLY_SURFACE) {
+        Some(reply) => u32_at(&reply, 0).map_or(-1, |id| id as i64),
+        None => -1,
+    }
+}
+
+/// Show a frame in any supported pixel format on a surface
+///
+/// The pixels are converted to 0RGB in the server with the options given
+/// here; frames of another size than the surface are stretched.
+///
+/// # Safety
+/// - `client` must be a valid pointer returned by `minifb_frame_client_connect`
+/// - `data` must point to `width * height` pixels of the format in `opts`
+/// - `opts` must be a valid pointer to MiniFBPixelOptions
+///
+/// Returns 0 on success, -1 on error
+#[no_mangle]
+pub unsafe extern "C" fn minifb_frame_client_push(
+    client: *mut MiniFBFrameClient,
+    surface: u32,
+    data: *const c_void,
+    width: u32,
+    height: u32,
+    opts: *const MiniFBPixelOptions,
+) -> i32 {
+    if client.is_null() || data.is_null() || opts.is_null() {
+        return -1;
+    }
+    let opts = &*opts;
+    let len = width as usize * height as usize * bytes_per_pixel(opts.format);
+    if FRAME_HEADER + len > MAX_PAYLOAD {
+        set_last_error("frame too large");
+        return -1;
+    }
+    let mut header = Vec::with_capacity(FRAME_HEADER);
+    for v in [surface, width, height] {
+        header.extend(v.to_le_bytes());
+    }
+    header.extend([opts.format as u8, opts.tone_map as u8, opts.linear as u8, 0]);
+    for v in [opts.range_min, opts.range_max, opts.exposure] {
+        header.extend(v.to_le_bytes());
+    }
+    let pixels = std::slice::from_raw_parts(data as *const u8, len);
+    match (*client).request(MSG_FRAME, &[&header, pixels], REPLY_OK) {
+        Some(_) => 0,
+        None => -1,
+    }
+}
+
+/// Set a surface's window title
+///
+/// # Safety
+/// - `client` must be a valid pointer returned by `minifb_frame_client_connect`
+/// - `title` must be a valid null-terminated C string
+///
+/// Returns 0 on success, -1 on error
+#[no_mangle]
+pub unsafe extern "C" fn minifb_frame_client_set_title(
+    client: *mut MiniFBFrameClient,
+    surface: u32,
+    title: *const c_char,
+) -> i32 {
+    if client.is_null() {
+        return -1;
+    }
+    let Some(title) = c_str(title) else {
+        return -1;
+    };
+    match (*client).request(
+        MSG_TITLE,
+        &[&surface.to_le_bytes(), title.as_bytes()],
+        REPLY_OK,
+    ) {
+        Some(_) => 0,
+        None => -1,
+    }
+}
+
+/// Query a surface's keyboard and mouse state
+///
+/// # Safety
+/// - `client` must be a valid pointer returned by `minifb_frame_client_connect`
+/// - `out_input` must be a valid pointer
+///
+/// Returns 0 on success, -1 on error
+#[no_mangle]
+pub unsafe extern "C" fn minifb_frame_client_get_input(
+    client: *mut MiniFBFrameClient,
+    surface: u32,
+    out_input: *mut MiniFBSurfaceInput,
+) -> i32 {
+    if client.is_null() || out_input.is_null() {
+        return -1;
+    }
+    let Some(reply) = (*client).request(MSG_INPUT, &[&surface.to_le_bytes()], REPLY_INPUT) else {
+        return -1;
+    };
+    match MiniFBSurfaceInput::decode(&reply) {
+        Some(input) => {
+            *out_input = input;
+            0
+        }
+        None => {
+            set_last_error("malformed input reply");
+            -1
+        }
+    }
+}
+
+/// Close a surface and its window
+///
+/// # Safety
+/// - `client` must be a valid pointer returned by `minifb_frame_client_connect`
+///
+/// Returns 0 on success, -1 on error
+#[no_mangle]
+pub unsafe extern "C" fn minifb_frame_client_close(
+    client: *mut MiniFBFrameClient,
+    surface: u32,
+) -> i32 {
+    if client.is_null() {
+        return -1;
+    }
+    match (*client).request(MSG_CLOSE, &[&surface.to_le_bytes()], REPLY_OK) {
+        Some(_) => 0,
+        None => -1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_push_and_query() {
+        let path =
+            std::env::temp_dir().join(format!("minifb-ffi-test-{}.sock", std::process::id()));
+        let mut server = MiniFBFrameServer::bind(path.clone()).unwrap();
+        server.headless = true;
+        let client = std::thread::spawn(move || unsafe {
+            let path = CString::new(path.to_str().unwrap()).unwrap();
+            let client = minifb_frame_client_connect(path.as_ptr());
+            assert!(!client.is_null());
+            let title = CString::new("test").unwrap();
+            let id = minifb_frame_client_open(client, title.as_ptr(), 2, 2);
+            assert_eq!(id, 1);
+            let rgba = [255u8, 0, 0, 255].repeat(4);
+            let opts = crate::present::minifb_pixel_options_default(MiniFBPixelFormat::FormatRgba8);
+            let surface = id as u32;
+            assert_eq!(
+                minifb_frame_client_push(client, surface, rgba.as_ptr() as _, 2, 2, &opts),
+                0
+            );
+            // Other sizes are stretched to the surface
+            assert_eq!(
+                minifb_frame_client_push(client, surface, rgba.as_ptr() as _, 1, 3, &opts),
+                0
+            );
+            assert_eq!(
+                minifb_frame_client_push(client, 9, rgba.as_ptr() as _, 2, 2, &opts),
+                -1
+            );
+            let mut input = MiniFBSurfaceInput::default();
+            assert_eq!(
+                minifb_frame_client_get_input(client, surface, &mut input),
+                0
+            );
+            assert!(input.open && !input.has_mouse && input.key_count == 0);
+            assert_eq!(minifb_frame_client_close(client, surface), 0);
+            assert_eq!(
+                minifb_frame_client_get_input(client, surface, &mut input),
+                -1
+            );
+            minifb_frame_client_free(client);
+        });
+        while !client.is_finished() {
+            server.poll(Duration::from_millis(10));
+        }
+        client.join().unwrap();
+        assert!(server.surfaces.is_empty());
+        // The finished client's connection is forgotten
+        while !server.connections.lock().unwrap().is_empty() {
+            std::thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn test_bind_spares_files_and_drop_ends_waits() {
+        let path =
+            std::env::temp_dir().join(format!("minifb-ffi-bind-{}.sock", std::process::id()));
+        std::fs::write(&path, b"not a socket").unwrap();
+        assert!(MiniFBFrameServer::bind(path.clone()).is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"not a socket");
+        std::fs::remove_file(&path).unwrap();
+
+        // A request still queued when the server goes away is not waited on
+        // forever
+        let server = MiniFBFrameServer::bind(path.clone()).unwrap();
+        let connections = server.connections.clone();
+        let client = std::thread::spawn(move || unsafe {
+            let path = CString::new(path.to_str().unwrap()).unwrap();
+            let client = minifb_frame_client_connect(path.as_ptr());
+            let title = CString::new("test").unwrap();
+            minifb_frame_client_open(client, title.as_ptr(), 2, 2);
+            minifb_frame_client_free(client);
+        });
+        while connections.lock().unwrap().is_empty() {
+            std::thread::yield_now();
+        }
+        std::thread::sleep(Duration::from_millis(100));
+        drop(server);
+        client.join().unwrap();
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while !connections.lock().unwrap().is_empty() {
+            assert!(std::time::Instant::now() < deadline);
+            std::thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn test_hostile_dimensions_rejected() {
+        let mut frame = vec![0u8; FRAME_HEADER + 4];
+        frame[4..12].fill(0xFF);
+        // RGB f32, so width * height * 12 overflows
+        frame[12] = 4;
+        assert!(parse_command(MSG_FRAME, frame).is_err());
+        let open = vec![0xFFu8; 8];
+        assert!(parse_command(MSG_OPEN, open).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+//! Finite-difference gradient visualization of sampled implicit fields
+//!
+//! Fields are row-major float32 buffers, one sample per canvas pixel (the
+//! layout of `Float_bigarray.t`). For an exact signed distance field the
+//! gradient magnitude is 1.0 everywhere, so the magnitude view highlights
+//! the regions where inexact shape operations break the distance property.
+
+use crate::canvas::{rgb, MiniFBCanvas};
+use crate::set_last_error;
+use std::slice;
+
+/// Gradient rendering mode
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiniFBGradientMode {
+    /// Hue encodes gradient direction, brightness its (clamped) magnitude
+    GradientHue = 0,
+    /// Diverging map of `|grad| - 1`: white is exact, blue under, red over
+    GradientMagnitude = 1,
+    /// Arrow glyphs on a sparse grid, drawn over the existing canvas contents
+    GradientArrows = 2,
+}
+
+/// Gradient rendering options
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MiniFBGradientOptions {
+    pub mode: MiniFBGradientMode,
+    /// World-space distance between adjacent samples
+    pub spacing: f32,
+    /// Deviation from 1.0 at which the magnitude map saturates
+    pub tolerance: f32,
+    /// Distance between arrow glyphs, in pixels
+    pub arrow_spacing: usize,
+    /// 0RGB color of arrow glyphs
+    pub arrow_color: u32,
+}
+
+/// Create default gradient options (hue mode, unit spacing)
+#[no_mangle]
+pub extern "C" fn minifb_gradient_options_default() -> MiniFBGradientOptions {
+    MiniFBGradientOptions {
+        mode: MiniFBGradientMode::GradientHue,
+        spacing: 1.0,
+        tolerance: 0.25,
+        arrow_spacing: 16,
+        arrow_color: 0x00FFFFFF,
+    }
+}
+
+/// Central differences in the interior, one-sided differences at the edges
+fn gradient_at(field: &[f32], width: usize, height: usize, x: usize, y: usize) -> (f32, f32) {
+    let at = |x: usize, y: usize| field[y * width + x];
+    let diff = |lo: f32, hi: f32, steps: usize| {
+        if steps == 0 {
+            0.0
+        } else {
+            (hi - lo) / steps as f32
+        }
+    };
+    let (x0, x1) = (x.saturating_sub(1), (x + 1).min(width - 1));
+    let (y0, y1) = (y.saturating_sub(1), (y + 1).min(height - 1));
+    (
+        diff(at(x0, y), at(x1, y), x1 - x0),
+        diff(at(x, y0), at(x, y1), y1 - y0),
+    )
+}
+
+fn hsv_to_rgb(h: f32, s: f32, v: f32) -> u32 {
+    let h = h.rem_euclid(1.0) * 6.0;
+    let i = h.floor();
+    let f = h - i;
+    let p = v * (1.0 - s);
+    let q = v * (1.0 - s * f);
+    let t = v * (1.0 - s * (1.0 - f));
+    let (r, g, b) = match i as i32 {
+        0 => (v, t, p),
+        1 => (q, v, p),
+        2 => (p, v, t),
+        3 => (p, q, v),
+        4 => (t, p, v),
+        _ => (v, p, q),
+    };
+    let c = |x: f32| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+    rgb(c(r), c(g), c(b))
+}
+
+fn hue_color(gx: f32, gy: f32) -> u32 {
+    let mag = (gx * gx + gy * gy).sqrt();
+    if !mag.is_finite() {
+        return 0;
+    }
+    let angle = gy.atan2(gx) / std::f32::consts::TAU;
+    hsv_to_rgb(angle, 1.0, mag.min(1.0))
+}
+
+fn magnitude_color(gx: f32, gy: f32, tolerance: f32) -> u32 {
+    let mag = (gx * gx + gy * gy).sqrt();
+    if !mag.is_finite() {
+        return 0;
+    }
+    let t = ((mag - 1.0) / tolerance.max(f32::EPSILON)).clamp(-1.0, 1.0);
+    let fade = |x: f32| ((1.0 - x.abs()) * 255.0).round() as u8;
+    if t >= 0.0 {
+        rgb(255, fade(t), fade(t))
+    } else {
+        rgb(fade(t), fade(t), 255)
+    }
+}
+
+fn draw_arrows(canvas: &mut MiniFBCanvas, field: &[f32], opts: &MiniFBGradientOptions) {
+    let step = opts.arrow_spacing.max(4);
+    let half_len = step as f32 * 0.4;
+    let (w, h) = (canvas.width, canvas.height);
+    let mut y = step / 2;
+    while y < h {
+        let mut x = step / 2;
+        while x < w {
+            let (gx, gy) = gradient_at(field, w, h, x, y);
+            let mag = (gx * gx + gy * gy).sqrt();
+            if mag.is_finite() && mag > f32::EPSILON {
+                let (dx, dy) = (gx / mag, gy / mag);
+                let (cx, cy) = (x as f32, y as f32);
+                let tip = (cx + dx * half_len, cy + dy * half_len);
+                let tail = (cx - dx * half_len, cy - dy * half_len);
+                let p = |(px, py): (f32, f32)| (px.round() as i64, py.round() as i64);
+                let (tx, ty) = p(tip);
+                let (sx, sy) = p(tail);
+                canvas.line(sx, sy, tx, ty, opts.arrow_color);
+                // Two barbs at +-150 degrees from the shaft direction
+                let head = half_len * 0.5;
+                for angle in [2.618_f32, -2.618] {
+                    let (s, c) = angle.sin_cos();
+                    let (bx, by) = p((
+                        tip.0 + (dx * c - dy * s) * head,
+                        tip.1 + (dx * s + dy * c) * head,
+                    ));
+                    canvas.line(tx, ty, bx, by, opts.arrow_color);
+                }
+            }
+            x += step;
+        }
+        y += step;
+    }
+}
+
+/// Render the gradient of a sampled field into a canvas
+///
+/// # Safety
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+/// - `field` must point to at least `width * height` f32 values
+/// - `opts` must be a valid pointer to MiniFBGradientOptions
+///
+/// The field dimensions must match the canvas and `spacing` must be positive.
+/// Returns 0 on success, -1 on error
+#[no_mangle]
+pub unsafe extern "C" fn minifb_field_render_gradient(
+    canvas: *mut MiniFBCanvas,
+    field: *const f32,
+    width: usize,
+    height: usize,
+    opts: *const MiniFBGradientOptions,
+) -> i32 {
+    if canvas.is_null() || field.is_null() || opts.is_null() {
+        return -1;
+    }
+    let canvas = &mut *canvas;
+    let opts = &*opts;
+    if canvas.width != width || canvas.height != height {
+        set_last_error("field dimensions do not match canvas");
+        return -1;
+    }
+    if !(opts.spacing > 0.0 && opts.spacing.is_finite()) {
+        set_last_error("sample spacing must be positive");
+        return -1;
+    }
+    let field = slice::from_raw_parts(field, width * height);
+    // Differences are taken per sample, convert to per world unit
+    let scale = 1.0 / opts.spacing;
+
+    match opts.mode {
+        MiniFBGradientMode::GradientHue | MiniFBGradientMode::GradientMagnitude => {
+            for y in 0..height {
+                for x in 0..width {
+                    let (gx, gy) = gradient_at(field, width, height, x, y);
+                    let (gx, gy) = (gx * scale, gy * scale);
+                    canvas.pixels[y * width + x] = match opts.mode {
+                        MiniFBGradientMode::GradientHue => hue_color(gx, gy),
+                        _ => magnitude_color(gx, gy, opts.tolerance),
+                    };
+                }
+            }
+            canvas.mark_all_dirty();
+        }
+        MiniFBGradientMode::GradientArrows => draw_arrows(canvas, field, opts),
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plane(width: usize, height: usize, a: f32, b: f32) -> Vec<f32> {
+        (0..width * height)
+            .map(|i| (i % width) as f32 * a + (i / width) as f32 * b)
+            .collect()
+    }
+
+    #[test]
+    fn test_exact_distance_field_is_neutral() {
+        let field = plane(32, 32, 0.6, 0.8);
+        let mut canvas = MiniFBCanvas::new(32, 32);
+        let opts = MiniFBGradientOptions {
+            mode: MiniFBGradientMode::GradientMagnitude,
+            ..minifb_gradient_options_default()
+        };
+        let result =
+            unsafe { minifb_field_render_gradient(&mut canvas, field.as_ptr(), 32, 32, &opts) };
+        assert_eq!(result, 0);
+        assert_eq!(canvas.pixels[4 * 32 + 4], 0x00FFFFFF);
+    }
+
+    #[test]
+    fn test_scaled_field_overestimates() {
+        let field = plane(32, 32, 1.2, 1.6);
+        let mut canvas = MiniFBCanvas::new(32, 32);
+        let opts = MiniFBGradientOptions {
+            mode: MiniFBGradientMode::GradientMagnitude,
+            ..minifb_gradient_options_default()
+        };
+        unsafe { minifb_field_render_gradient(&mut canvas, field.as_ptr(), 32, 32, &opts) };
+        assert_eq!(canvas.pixels[4 * 32 + 4], 0x00FF0000);
+
+        let opts = MiniFBGradientOptions {
+            spacing: 0.0,
+            ..opts
+        };
+        let result =
+            unsafe { minifb_field_render_gradient(&mut canvas, field.as_ptr(), 32, 32, &opts) };
+        assert_eq!(result, -1);
+    }
+}
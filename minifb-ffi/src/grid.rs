@@ -0,0 +1,385 @@
+//! World-space grid, labeled axes and pixel rulers
+//!
+//! The grid follows a camera and picks its spacing from the 1-2-5 sequence
+//! so lines stay at least `min_spacing` pixels apart at any zoom level.
+//! Rulers along the top and left edges are in screen pixels, which makes
+//! the padding and target size of a `Scene.t` easy to read off.
+
+use crate::camera::{MiniFBCamera, MiniFBTransform};
+use crate::canvas::MiniFBCanvas;
+use crate::text::{MiniFBFont, MiniFBTextAlign, MiniFBTextBaseline, MiniFBTextStyle};
+use std::ops::RangeInclusive;
+
+/// Grid overlay options
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MiniFBGridOptions {
+    /// Minimum distance between minor grid lines, in pixels
+    pub min_spacing: f32,
+    pub minor_color: u32,
+    pub major_color: u32,
+    pub axis_color: u32,
+    pub label_color: u32,
+    pub ruler_color: u32,
+    /// Thickness of the rulers along the top and left edges, 0 hides them
+    pub ruler_size: u32,
+    pub show_grid: bool,
+    pub show_axes: bool,
+    pub show_labels: bool,
+}
+
+/// Create default grid options
+#[no_mangle]
+pub extern "C" fn minifb_grid_options_default() -> MiniFBGridOptions {
+    MiniFBGridOptions {
+        min_spacing: 12.0,
+        minor_color: 0x00282828,
+        major_color: 0x00484848,
+        axis_color: 0x00A0A0A0,
+        label_color: 0x00C8C8C8,
+        ruler_color: 0x00202020,
+        ruler_size: 16,
+        show_grid: true,
+        show_axes: true,
+        show_labels: true,
+    }
+}
+
+/// Smallest step from the 1-2-5 sequence that is at least `min_step`,
+/// together with how many minor steps make a major one
+pub(crate) fn nice_step(min_step: f64) -> (f64, u32) {
+    let exponent = min_step.log10().floor();
+    let base = 10f64.powf(exponent);
+    for (mantissa, per_major) in [(1.0, 5), (2.0, 5), (5.0, 2), (10.0, 5)] {
+        if mantissa * base >= min_step {
+            return (mantissa * base, per_major);
+        }
+    }
+    (10.0 * base, 5)
+}
+
+/// Format a tick value with just enough decimals for the given step
+fn format_tick(value: f64, step: f64) -> String {
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    let value = if value.abs() < step * 1e-6 {
+        0.0
+    } else {
+        value
+    };
+    format!("{:.*}", decimals, value)
+}
+
+fn label_style(
+    color: u32,
+    align: MiniFBTextAlign,
+    baseline: MiniFBTextBaseline,
+) -> MiniFBTextStyle {
+    MiniFBTextStyle {
+        color,
+        background: 0,
+        background_opacity: 0.6,
+        padding: 1,
+        align,
+        baseline,
+    }
+}
+
+/// Indices `i` of the multiples `i * step` covering [lo, hi]
+fn ticks(step: f64, lo: f64, hi: f64) -> RangeInclusive<i64> {
+    (lo / step).floor() as i64..=(hi / step).ceil() as i64
+}
+
+impl MiniFBCanvas {
+    fn draw_world_grid(
+        &mut self,
+        transform: &MiniFBTransform,
+        inverse: &MiniFBTransform,
+        font: &mut MiniFBFont,
+        opts: &MiniFBGridOptions,
+    ) {
+        let (w, h) = (self.width as f64, self.height as f64);
+        let min_spacing = opts.min_spacing.max(2.0) as f64;
+        let corners = [
+            inverse.apply(0.0, 0.0),
+            inverse.apply(w, 0.0),
+            inverse.apply(0.0, h),
+            inverse.apply(w, h),
+        ];
+        let min_x = corners.iter().map(|c| c.0).fold(f64::INFINITY, f64::min);
+        let max_x = corners
+            .iter()
+            .map(|c| c.0)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let min_y = corners.iter().map(|c| c.1).fold(f64::INFINITY, f64::min);
+        let max_y = corners
+            .iter()
+            .map(|c| c.1)
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        // Each axis gets its own step, from the screen length of one world
+        // unit along it, so a stretched camera doesn't crowd either. An axis
+        // that would still need more lines than fit on screen (a degenerate
+        // or extreme camera) gets none.
+        let max_lines = (w + h) / min_spacing + 2.0;
+        let axis_step = |pixels_per_unit: f64, lo: f64, hi: f64| {
+            let (step, per_major) = nice_step(min_spacing / pixels_per_unit);
+            let fits = pixels_per_unit > 0.0 && (hi - lo) / step <= max_lines;
+            fits.then_some((step, per_major))
+        };
+        let x_axis = axis_step(transform.m11.hypot(transform.m12), min_x, max_x);
+        let y_axis = axis_step(transform.m21.hypot(transform.m22), min_y, max_y);
+        let ruler = opts.ruler_size as f64;
+
+        // Minor lines first so major lines and axes draw on top
+        let passes: &[bool] = if opts.show_grid { &[false, true] } else { &[] };
+        for &pass_major in passes {
+            let color = if pass_major {
+                opts.major_color
+            } else {
+                opts.minor_color
+            };
+            if let Some((step, per_major)) = x_axis {
+                for i in ticks(step, min_x, max_x) {
+                    let is_major = i.rem_euclid(per_major as i64) == 0;
+                    if is_major != pass_major || (opts.show_axes && i == 0) {
+                        continue;
+                    }
+                    let x = i as f64 * step;
+                    self.line_f(transform.apply(x, min_y), transform.apply(x, max_y), color);
+                }
+            }
+            if let Some((step, per_major)) = y_axis {
+                for i in ticks(step, min_y, max_y) {
+                    let is_major = i.rem_euclid(per_major as i64) == 0;
+                    if is_major != pass_major || (opts.show_axes && i == 0) {
+                        continue;
+                    }
+                    let y = i as f64 * step;
+                    self.line_f(transform.apply(min_x, y), transform.apply(max_x, y), color);
+                }
+            }
+        }
+
+        if opts.show_axes {
+            self.line_f(
+                transform.apply(0.0, min_y),
+                transform.apply(0.0, max_y),
+                opts.axis_color,
+            );
+            self.line_f(
+                transform.apply(min_x, 0.0),
+                transform.apply(max_x, 0.0),
+                opts.axis_color,
+            );
+        }
+
+        // Labels sit on the axes, clamped inside the viewport when an axis is
+        // off screen; a viewport too small to hold them next to the rulers gets none
+        let fits = h - 12.0 >= ruler + 2.0 && w - 2.0 >= ruler + 2.0;
+        if opts.show_labels && fits {
+            let (ox, oy) = transform.apply(0.0, 0.0);
+            let label_y = oy.clamp(ruler + 2.0, h - 12.0);
+            let label_x = ox.clamp(ruler + 2.0, w - 2.0);
+            let style = label_style(
+                opts.label_color,
+                MiniFBTextAlign::AlignCenter,
+                MiniFBTextBaseline::BaselineTop,
+            );
+            if let Some((step, per_major)) = x_axis {
+                let major = step * per_major as f64;
+                for i in ticks(major, min_x, max_x) {
+                    let x = i as f64 * major;
+                    let (sx, _) = transform.apply(x, 0.0);
+                    if sx > ruler + 8.0 && sx < w - 8.0 {
+                        font.draw(
+                            self,
+                            &format_tick(x, major),
+                            sx.round() as i64,
+                            label_y.round() as i64 + 2,
+                            &style,
+                        );
+                    }
+                }
+            }
+            let style = label_style(
+                opts.label_color,
+                MiniFBTextAlign::AlignLeft,
+                MiniFBTextBaseline::BaselineMiddle,
+            );
+            if let Some((step, per_major)) = y_axis {
+                let major = step * per_major as f64;
+                for i in ticks(major, min_y, max_y) {
+                    let y = i as f64 * major;
+                    let (_, sy) = transform.apply(0.0, y);
+                    if i != 0 && sy > ruler + 8.0 && sy < h - 8.0 {
+                        font.draw(
+                            self,
+                            &format_tick(y, major),
+                            label_x.round() as i64 + 3,
+                            sy.round() as i64,
+                            &style,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw_rulers(&mut self, font: &mut MiniFBFont, opts: &MiniFBGridOptions) {
+        let size = opts.ruler_size as i64;
+        if size == 0 {
+            return;
+        }
+        let (w, h) = (self.width as i64, self.height as i64);
+        self.fill_rect(0, 0, w, size, opts.ruler_color, 255);
+        self.fill_rect(0, 0, size, h, opts.ruler_color, 255);
+        let style = MiniFBTextStyle {
+            color: opts.label_color,
+            background_opacity: 0.0,
+            ..label_style(
+                0,
+                MiniFBTextAlign::AlignLeft,
+                MiniFBTextBaseline::BaselineTop,
+            )
+        };
+        for x in (0..w).step_by(10) {
+            let tick = if x % 100 == 0 {
+                size
+            } else if x % 50 == 0 {
+                size / 2
+            } else {
+                size / 4
+            };
+            self.line(x, size - tick, x, size - 1, opts.label_color);
+            if x % 100 == 0 && x > 0 {
+                font.draw(self, &x.to_string(), x + 2, 1, &style);
+            }
+        }
+        for y in (0..h).step_by(10) {
+            let tick = if y % 100 == 0 {
+                size
+            } else if y % 50 == 0 {
+                size / 2
+            } else {
+                size / 4
+            };
+            self.line(size - tick, y, size - 1, y, opts.label_color);
+            if y % 100 == 0 && y > 0 {
+                // Vertical ruler labels are stacked digit by digit to fit the strip
+                let digits: Vec<String> = y.to_string().chars().map(String::from).collect();
+                font.draw(self, &digits.join("\n"), 1, y + 2, &style);
+            }
+        }
+    }
+}
+
+/// Draw a world-space grid with labeled axes and pixel rulers
+///
+/// # Safety
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+/// - `camera` must be a valid camera pointer, or null for pixel coordinates
+/// - `font` must be a valid font pointer, or null for the built-in font at scale 1
+/// - `opts` must be a valid pointer to MiniFBGridOptions
+#[no_mangle]
+pub unsafe extern "C" fn minifb_canvas_draw_grid(
+    canvas: *mut MiniFBCanvas,
+    camera: *const MiniFBCamera,
+    font: *mut MiniFBFont,
+    opts: *const MiniFBGridOptions,
+) {
+    if canvas.is_null() || opts.is_null() {
+        return;
+    }
+    let (transform, inverse) = if camera.is_null() {
+        (MiniFBTransform::IDENTITY, MiniFBTransform::IDENTITY)
+    } else {
+        ((*camera).world_to_screen, (*camera).screen_to_world)
+    };
+    let mut builtin;
+    let font = if font.is_null() {
+        builtin = MiniFBFont::builtin(1);
+        &mut builtin
+    } else {
+        &mut *font
+    };
+    let canvas = &mut *canvas;
+    canvas.draw_world_grid(&transform, &inverse, font, &*opts);
+    canvas.draw_rulers(font, &*opts);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nice_step() {
+        assert_eq!(nice_step(0.7), (1.0, 5));
+        assert_eq!(nice_step(1.5), (2.0, 5));
+        assert_eq!(nice_step(3.0), (5.0, 2));
+        assert_eq!(nice_step(30.0), (50.0, 2));
+        let (step, _) = nice_step(0.013);
+        assert!((step - 0.02).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_format_tick() {
+        assert_eq!(format_tick(250.0, 50.0), "250");
+        assert_eq!(format_tick(0.30000000000000004, 0.1), "0.3");
+        assert_eq!(format_tick(-1e-17, 0.1), "0.0");
+    }
+
+    #[test]
+    fn test_tiny_canvas_skips_labels() {
+        let opts = minifb_grid_options_default();
+        for (w, h) in [(64, 20), (10, 64), (1, 1)] {
+            let canvas = crate::canvas::minifb_canvas_new(w, h);
+            unsafe {
+                minifb_canvas_draw_grid(canvas, std::ptr::null(), std::ptr::null_mut(), &opts);
+                crate::canvas::minifb_canvas_free(canvas);
+            }
+        }
+    }
+    #[test]
+    fn test_stretched_camera_spaces_axes_separately() {
+        // x stretched 100 times more than y: each axis still gets lines at
+        // least `min_spacing` apart, and the y axis isn't skipped
+        let mut canvas = MiniFBCanvas::new(200, 200);
+        let transform = MiniFBTransform {
+            m11: 100.0,
+            m12: 0.0,
+            m21: 0.0,
+            m22: 1.0,
+            m31: 0.0,
+            m32: 0.0,
+        };
+        let inverse = transform.inverse().unwrap();
+        let mut opts = minifb_grid_options_default();
+        opts.show_axes = false;
+        opts.show_labels = false;
+        opts.minor_color = 0xFF;
+        opts.major_color = 0xFF;
+        let mut font = MiniFBFont::builtin(1);
+        canvas.draw_world_grid(&transform, &inverse, &mut font, &opts);
+        let row: Vec<usize> = (0..200)
+            .filter(|&x| canvas.pixels[5 * 200 + x] == 0xFF)
+            .collect();
+        let column: Vec<usize> = (0..200)
+            .filter(|&y| canvas.pixels[y * 200 + 5] == 0xFF)
+            .collect();
+        // Vertical lines cross row 5, horizontal ones column 5
+        assert!(row.len() > 2 && column.len() > 2);
+        assert!(row.windows(2).all(|p| p[1] - p[0] >= 12));
+        assert!(column.windows(2).all(|p| p[1] - p[0] >= 12));
+
+        // Under a strong shear the visible world spans thousands of steps on
+        // both axes, which are skipped instead of drawn line by line
+        let transform = MiniFBTransform {
+            m21: 1000.0,
+            ..MiniFBTransform::IDENTITY
+        };
+        let inverse = transform.inverse().unwrap();
+        let mut canvas = MiniFBCanvas::new(200, 200);
+        canvas.draw_world_grid(&transform, &inverse, &mut font, &opts);
+        assert!(canvas.pixels[5 * 200..6 * 200].iter().all(|&p| p != 0xFF));
+    }
+}
@@ -0,0 +1,504 @@
+//! Image decoding (PNG, binary PPM/PGM) and scaled blitting into canvases
+//!
+//! Images are stored as straight-alpha ARGB u32, so dropping the top byte
+//! gives minifb's 0RGB layout directly.
+
+use crate::camera::{MiniFBCamera, MiniFBTransform};
+use crate::canvas::MiniFBCanvas;
+use crate::draw::camera_transform;
+use crate::set_last_error;
+use std::ffi::CStr;
+use std::io::Read;
+use std::os::raw::c_char;
+use std::slice;
+
+/// Opaque Rust-owned image
+#[derive(Clone)]
+pub struct MiniFBImage {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    /// Straight-alpha ARGB, row-major
+    pub(crate) pixels: Vec<u32>,
+}
+
+/// Resampling filter used when blitting
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiniFBFilter {
+    FilterNearest = 0,
+    FilterBilinear = 1,
+}
+
+/// Image blit options
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MiniFBBlitOptions {
+    pub filter: MiniFBFilter,
+    /// Multiplied with the image's own alpha
+    pub opacity: f32,
+    /// Honor the image alpha channel; when false the image is treated as opaque
+    pub use_alpha: bool,
+}
+
+/// Create default blit options (bilinear, opaque, alpha honored)
+#[no_mangle]
+pub extern "C" fn minifb_blit_options_default() -> MiniFBBlitOptions {
+    MiniFBBlitOptions {
+        filter: MiniFBFilter::FilterBilinear,
+        opacity: 1.0,
+        use_alpha: true,
+    }
+}
+
+#[inline]
+fn argb(a: u8, r: u8, g: u8, b: u8) -> u32 {
+    ((a as u32) << 24) | ((r as u32) << 16) | ((g as u32) << 8) | b as u32
+}
+
+impl MiniFBImage {
+    pub(crate) fn decode(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.starts_with(b"\x89PNG") {
+            decode_png(bytes)
+        } else if bytes.starts_with(b"P5") || bytes.starts_with(b"P6") {
+            decode_pnm(bytes)
+        } else {
+            Err("unrecognized image format (expected PNG, binary PPM or PGM)".to_string())
+        }
+    }
+
+    /// Encode as an 8-bit RGBA PNG
+    pub(crate) fn encode_png(&self) -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let data: Vec<u8> = self
+            .pixels
+            .iter()
+            .flat_map(|&p| {
+                let [b, g, r, a] = p.to_le_bytes();
+                [r, g, b, a]
+            })
+            .collect();
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&data))
+            .map_err(|e| e.to_string())?;
+        Ok(out)
+    }
+
+    /// Sample at continuous image coordinates, returning (a, r, g, b) in 0-255,
+    /// with color premultiplied by alpha if `premultiply` is set
+    fn sample(&self, u: f64, v: f64, filter: MiniFBFilter, premultiply: bool) -> [f32; 4] {
+        let texel = |x: i64, y: i64| {
+            let x = x.clamp(0, self.width as i64 - 1) as usize;
+            let y = y.clamp(0, self.height as i64 - 1) as usize;
+            let p = self.pixels[y * self.width + x];
+            let a = (p >> 24) as f32;
+            let weight = if premultiply { a / 255.0 } else { 1.0 };
+            let pm = |shift: u32| ((p >> shift) & 0xFF) as f32 * weight;
+            [a, pm(16), pm(8), pm(0)]
+        };
+        match filter {
+            MiniFBFilter::FilterNearest => texel(u.floor() as i64, v.floor() as i64),
+            MiniFBFilter::FilterBilinear => {
+                let (fu, fv) = (u - 0.5, v - 0.5);
+                let (x0, y0) = (fu.floor(), fv.floor());
+                let (tx, ty) = ((fu - x0) as f32, (fv - y0) as f32);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let (a, b, c, d) = (
+                    texel(x0, y0),
+                    texel(x0 + 1, y0),
+                    texel(x0, y0 + 1),
+                    texel(x0 + 1, y0 + 1),
+                );
+                let mut out = [0.0; 4];
+                for i in 0..4 {
+                    let top = a[i] + (b[i] - a[i]) * tx;
+                    let bottom = c[i] + (d[i] - c[i]) * tx;
+                    out[i] = top + (bottom - top) * ty;
+                }
+                out
+            }
+        }
+    }
+}
+
+fn decode_png(bytes: &[u8]) -> Result<MiniFBImage, String> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(|e| e.to_string())?;
+    let data = &buf[..info.buffer_size()];
+    let pixels: Vec<u32> = match info.color_type {
+        png::ColorType::Grayscale => data.iter().map(|&l| argb(255, l, l, l)).collect(),
+        png::ColorType::GrayscaleAlpha => data
+            .chunks_exact(2)
+            .map(|p| argb(p[1], p[0], p[0], p[0]))
+            .collect(),
+        png::ColorType::Rgb => data
+            .chunks_exact(3)
+            .map(|p| argb(255, p[0], p[1], p[2]))
+            .collect(),
+        png::ColorType::Rgba => data
+            .chunks_exact(4)
+            .map(|p| argb(p[3], p[0], p[1], p[2]))
+            .collect(),
+        png::ColorType::Indexed => return Err("unexpanded indexed PNG".to_string()),
+    };
+    Ok(MiniFBImage {
+        width: info.width as usize,
+        height: info.height as usize,
+        pixels,
+    })
+}
+
+/// Binary PGM (P5) and PPM (P6), 8 or 16 bits per sample
+fn decode_pnm(bytes: &[u8]) -> Result<MiniFBImage, String> {
+    let channels = if bytes[1] == b'5' { 1 } else { 3 };
+    let mut pos = 2;
+    let mut header = [0usize; 3];
+    for value in header.iter_mut() {
+        // Skip whitespace and comments between header fields
+        loop {
+            match bytes.get(pos) {
+                Some(b) if b.is_ascii_whitespace() => pos += 1,
+                Some(b'#') => {
+                    while bytes.get(pos).is_some_and(|&b| b != b'\n') {
+                        pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+        let start = pos;
+        while bytes.get(pos).is_some_and(u8::is_ascii_digit) {
+            pos += 1;
+        }
+        *value = std::str::from_utf8(&bytes[start..pos])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or("malformed PNM header")?;
+    }
+    let [width, height, maxval] = header;
+    if maxval == 0 || maxval > 65535 {
+        return Err("invalid PNM maxval".to_string());
+    }
+    // Exactly one whitespace byte separates the header from the raster
+    pos += 1;
+    let sample_bytes = if maxval > 255 { 2 } else { 1 };
+    let needed = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(channels * sample_bytes))
+        .ok_or("PNM dimensions too large")?;
+    let end = pos.checked_add(needed).ok_or("PNM dimensions too large")?;
+    let data = bytes.get(pos..end).ok_or("truncated PNM raster")?;
+    let samples: Vec<u8> = data
+        .chunks_exact(sample_bytes)
+        .map(|s| {
+            let v = if sample_bytes == 2 {
+                u16::from_be_bytes([s[0], s[1]]) as usize
+            } else {
+                s[0] as usize
+            };
+            ((v * 255 + maxval / 2) / maxval) as u8
+        })
+        .collect();
+    let pixels = if channels == 1 {
+        samples.iter().map(|&l| argb(255, l, l, l)).collect()
+    } else {
+        samples
+            .chunks_exact(3)
+            .map(|p| argb(255, p[0], p[1], p[2]))
+            .collect()
+    };
+    Ok(MiniFBImage {
+        width,
+        height,
+        pixels,
+    })
+}
+
+impl MiniFBCanvas {
+    /// Draw `image` so that its full extent maps onto the rectangle
+    /// (x, y, w, h) under `transform`, by inverse-mapping every covered pixel
+    pub(crate) fn blit(
+        &mut self,
+        image: &MiniFBImage,
+        rect: (f64, f64, f64, f64),
+        transform: &MiniFBTransform,
+        opts: &MiniFBBlitOptions,
+    ) {
+        let (x, y, w, h) = rect;
+        if image.width == 0 || image.height == 0 || w == 0.0 || h == 0.0 {
+            return;
+        }
+        let image_to_screen =
+            MiniFBTransform::scale(w / image.width as f64, h / image.height as f64)
+                .then(&MiniFBTransform::translation(x, y))
+                .then(transform);
+        let screen_to_image = match image_to_screen.inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        let corners = [
+            image_to_screen.apply(0.0, 0.0),
+            image_to_screen.apply(image.width as f64, 0.0),
+            image_to_screen.apply(0.0, image.height as f64),
+            image_to_screen.apply(image.width as f64, image.height as f64),
+        ];
+        let x0 = corners
+            .iter()
+            .map(|c| c.0)
+            .fold(f64::INFINITY, f64::min)
+            .floor()
+            .max(0.0) as usize;
+        let y0 = corners
+            .iter()
+            .map(|c| c.1)
+            .fold(f64::INFINITY, f64::min)
+            .floor()
+            .max(0.0) as usize;
+        let x1 = corners
+            .iter()
+            .map(|c| c.0)
+            .fold(f64::NEG_INFINITY, f64::max)
+            .ceil()
+            .min(self.width as f64)
+            .max(0.0) as usize;
+        let y1 = corners
+            .iter()
+            .map(|c| c.1)
+            .fold(f64::NEG_INFINITY, f64::max)
+            .ceil()
+            .min(self.height as f64)
+            .max(0.0) as usize;
+        let opacity = opts.opacity.clamp(0.0, 1.0);
+        self.mark_dirty(x0, y0, x1, y1);
+
+        for py in y0..y1 {
+            for px in x0..x1 {
+                let (u, v) = screen_to_image.apply(px as f64 + 0.5, py as f64 + 0.5);
+                if u < 0.0 || v < 0.0 || u >= image.width as f64 || v >= image.height as f64 {
+                    continue;
+                }
+                let [a, r, g, b] = image.sample(u, v, opts.filter, opts.use_alpha);
+                let (alpha, unpremultiply) = if opts.use_alpha {
+                    (a / 255.0 * opacity, if a > 0.0 { 255.0 / a } else { 0.0 })
+                } else {
+                    (opacity, 1.0)
+                };
+                if alpha <= 0.0 {
+                    continue;
+                }
+                let channel = |c: f32| (c * unpremultiply).round().clamp(0.0, 255.0) as u8;
+                let color = crate::canvas::rgb(channel(r), channel(g), channel(b));
+                let index = py * self.width + px;
+                self.pixels[index] = crate::canvas::lerp_rgb(
+                    self.pixels[index],
+                    color,
+                    (alpha * 255.0).round() as u8,
+                );
+            }
+        }
+    }
+}
+
+/// Load a PNG, binary PPM (P6) or binary PGM (P5) file
+///
+/// # Safety
+/// - `path` must be a valid null-terminated C string
+///
+/// Returns null on failure (see `minifb_get_last_error`)
+#[no_mangle]
+pub unsafe extern "C" fn minifb_image_load(path: *const c_char) -> *mut MiniFBImage {
+    if path.is_null() {
+        return std::ptr::null_mut();
+    }
+    let path = match CStr::from_ptr(path).to_str() {
+        Ok(s) => s,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    let mut bytes = Vec::new();
+    let read = std::fs::File::open(path).and_then(|mut f| f.read_to_end(&mut bytes));
+    if let Err(e) = read {
+        set_last_error(&format!("{}: {}", path, e));
+        return std::ptr::null_mut();
+    }
+    match MiniFBImage::decode(&bytes) {
+        Ok(image) => Box::into_raw(Box::new(image)),
+        Err(e) => {
+            set_last_error(&format!("{}: {}", path, e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Decode an image from memory (same formats as `minifb_image_load`)
+///
+/// # Safety
+/// - `data` must point to at least `len` bytes
+///
+/// Returns null on failure (see `minifb_get_last_error`)
+#[no_mangle]
+pub unsafe extern "C" fn minifb_image_decode(data: *const u8, len: usize) -> *mut MiniFBImage {
+    if data.is_null() {
+        return std::ptr::null_mut();
+    }
+    match MiniFBImage::decode(slice::from_raw_parts(data, len)) {
+        Ok(image) => Box::into_raw(Box::new(image)),
+        Err(e) => {
+            set_last_error(&e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Free an image
+///
+/// # Safety
+/// - `image` must be a valid pointer returned by `minifb_image_load` or
+///   `minifb_image_decode`, or null
+/// - After calling this function, the pointer is invalid and must not be used
+#[no_mangle]
+pub unsafe extern "C" fn minifb_image_free(image: *mut MiniFBImage) {
+    if !image.is_null() {
+        drop(Box::from_raw(image));
+    }
+}
+
+/// Get the image size
+///
+/// # Safety
+/// - `image` must be a valid image pointer
+/// - `out_width` and `out_height` must be valid pointers
+#[no_mangle]
+pub unsafe extern "C" fn minifb_image_get_size(
+    image: *const MiniFBImage,
+    out_width: *mut usize,
+    out_height: *mut usize,
+) {
+    if image.is_null() || out_width.is_null() || out_height.is_null() {
+        return;
+    }
+    *out_width = (*image).width;
+    *out_height = (*image).height;
+}
+
+/// Copy the image into a 0RGB buffer, dropping the alpha channel
+///
+/// # Safety
+/// - `image` must be a valid image pointer
+/// - `out_buffer` must point to at least `width * height` u32 values
+#[no_mangle]
+pub unsafe extern "C" fn minifb_image_to_0rgb(image: *const MiniFBImage, out_buffer: *mut u32) {
+    if image.is_null() || out_buffer.is_null() {
+        return;
+    }
+    let image = &*image;
+    let out = slice::from_raw_parts_mut(out_buffer, image.pixels.len());
+    for (o, &p) in out.iter_mut().zip(&image.pixels) {
+        *o = p & 0x00FF_FFFF;
+    }
+}
+
+/// Blit an image into the rectangle (x, y, width, height)
+///
+/// The rectangle is in world units when a camera is given and in pixels
+/// otherwise, so a reference image can be pinned under world-space shapes.
+///
+/// # Safety
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+/// - `image` must be a valid image pointer
+/// - `camera` must be a valid camera pointer, or null
+/// - `opts` must be a valid pointer to MiniFBBlitOptions
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn minifb_canvas_blit_image(
+    canvas: *mut MiniFBCanvas,
+    image: *const MiniFBImage,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    camera: *const MiniFBCamera,
+    opts: *const MiniFBBlitOptions,
+) {
+    if canvas.is_null() || image.is_null() || opts.is_null() {
+        return;
+    }
+    (*canvas).blit(
+        &*image,
+        (x, y, width, height),
+        &camera_transform(camera),
+        &*opts,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_ppm_and_pgm() {
+        let ppm = b"P6\n# comment\n2 1\n255\n\xff\x00\x00\x00\x00\xff";
+        let image = MiniFBImage::decode(ppm).unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(image.pixels, vec![0xFFFF0000, 0xFF0000FF]);
+
+        let pgm = b"P5 1 1 65535\n\x80\x00";
+        let image = MiniFBImage::decode(pgm).unwrap();
+        assert_eq!(image.pixels, vec![0xFF808080]);
+
+        let hostile = b"P6 4294967296 4294967296 255\n\x00\x00\x00";
+        assert_eq!(
+            MiniFBImage::decode(hostile).err().as_deref(),
+            Some("PNM dimensions too large")
+        );
+        let hostile = b"P5 99999999999999999999 1 255\n\x00";
+        assert!(MiniFBImage::decode(hostile).is_err());
+    }
+
+    #[test]
+    fn test_nearest_upscale() {
+        let image = MiniFBImage {
+            width: 2,
+            height: 1,
+            pixels: vec![0xFFFF0000, 0xFF00FF00],
+        };
+        let mut canvas = MiniFBCanvas::new(4, 2);
+        let opts = MiniFBBlitOptions {
+            filter: MiniFBFilter::FilterNearest,
+            ..minifb_blit_options_default()
+        };
+        canvas.blit(
+            &image,
+            (0.0, 0.0, 4.0, 2.0),
+            &MiniFBTransform::IDENTITY,
+            &opts,
+        );
+        assert_eq!(
+            &canvas.pixels[4..8],
+            &[0xFF0000, 0xFF0000, 0x00FF00, 0x00FF00]
+        );
+
+        // Without alpha, fully transparent texels keep their stored color
+        let image = MiniFBImage {
+            width: 1,
+            height: 1,
+            pixels: vec![0x0000FF00],
+        };
+        let opts = MiniFBBlitOptions {
+            use_alpha: false,
+            ..opts
+        };
+        canvas.blit(
+            &image,
+            (0.0, 0.0, 1.0, 1.0),
+            &MiniFBTransform::IDENTITY,
+            &opts,
+        );
+        assert_eq!(canvas.pixels[0], 0x00FF00);
+    }
+}
@@ -0,0 +1,274 @@
+//! Keyboard and mouse state for backends without a native window
+//!
+//! minifb tracks input itself for real windows. Other backends receive input
+//! as a stream of events instead and feed them in here while the window is
+//! updated. Presses and releases are remembered per frame, so edge queries
+//! like `is_key_pressed` see a key that went down and up between two updates.
+
+use crate::MiniFBKey;
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode};
+
+/// One change to an `InputState`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum InputEvent {
+    Key(Key, bool),
+    MouseMove(Option<(f32, f32)>),
+    Button(MouseButton, bool),
+    Scroll(f32, f32),
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct InputState {
+    pub(crate) down: Vec<Key>,
+    /// Key presses since the last frame, true for repeats
+    pub(crate) pressed: Vec<(Key, bool)>,
+    pub(crate) released: Vec<Key>,
+    pub(crate) mouse: Option<(f32, f32)>,
+    mouse_moved: bool,
+    pub(crate) buttons: [bool; 3],
+    pub(crate) scroll: (f32, f32),
+}
+
+const LETTERS: [Key; 26] = [
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+];
+
+const DIGITS: [Key; 10] = [
+    Key::Key0,
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+];
+
+/// Key for a printable ASCII character on a US layout, and whether it needs shift
+pub(crate) fn char_key(c: u8) -> Option<(Key, bool)> {
+    let plain = |key| Some((key, false));
+    let shifted = |key| Some((key, true));
+    match c {
+        b'a'..=b'z' => plain(LETTERS[(c - b'a') as usize]),
+        b'A'..=b'Z' => shifted(LETTERS[(c - b'A') as usize]),
+        b'0'..=b'9' => plain(DIGITS[(c - b'0') as usize]),
+        b' ' => plain(Key::Space),
+        b'\'' => plain(Key::Apostrophe),
+        b'`' => plain(Key::Backquote),
+        b'\\' => plain(Key::Backslash),
+        b',' => plain(Key::Comma),
+        b'=' => plain(Key::Equal),
+        b'[' => plain(Key::LeftBracket),
+        b'-' => plain(Key::Minus),
+        b'.' => plain(Key::Period),
+        b']' => plain(Key::RightBracket),
+        b';' => plain(Key::Semicolon),
+        b'/' => plain(Key::Slash),
+        b')' => shifted(Key::Key0),
+        b'!' => shifted(Key::Key1),
+        b'@' => shifted(Key::Key2),
+        b'#' => shifted(Key::Key3),
+        b'$' => shifted(Key::Key4),
+        b'%' => shifted(Key::Key5),
+        b'^' => shifted(Key::Key6),
+        b'&' => shifted(Key::Key7),
+        b'*' => shifted(Key::Key8),
+        b'(' => shifted(Key::Key9),
+        b'"' => shifted(Key::Apostrophe),
+        b'~' => shifted(Key::Backquote),
+        b'|' => shifted(Key::Backslash),
+        b'<' => shifted(Key::Comma),
+        b'+' => shifted(Key::Equal),
+        b'{' => shifted(Key::LeftBracket),
+        b'_' => shifted(Key::Minus),
+        b'>' => shifted(Key::Period),
+        b'}' => shifted(Key::RightBracket),
+        b':' => shifted(Key::Semicolon),
+        b'?' => shifted(Key::Slash),
+        _ => None,
+    }
+}
+
+/// Every key minifb can report
+pub(crate) fn all_keys() -> impl Iterator<Item = Key> {
+    (0..MiniFBKey::Unknown as i32)
+        .filter_map(|code| MiniFBKey::from_code(code).map(MiniFBKey::to_minifb))
+}
+
+fn button_index(button: MouseButton) -> usize {
+    match button {
+        MouseButton::Left => 0,
+        MouseButton::Middle => 1,
+        MouseButton::Right => 2,
+    }
+}
+
+impl InputState {
+    /// Forget the edges and scrolling of the previous frame
+    pub(crate) fn begin_frame(&mut self) {
+        self.clear_edges();
+        self.mouse_moved = false;
+    }
+
+    /// Forget key presses, releases and scrolling, keeping what is held
+    pub(crate) fn clear_edges(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+        self.scroll = (0.0, 0.0);
+    }
+
+    pub(crate) fn apply(&mut self, event: InputEvent) {
+        match event {
+            InputEvent::Key(key, down) => self.key(key, down),
+            InputEvent::MouseMove(pos) => self.mouse_move(pos),
+            InputEvent::Button(button, down) => self.button(button, down),
+            InputEvent::Scroll(dx, dy) => self.scroll(dx, dy),
+        }
+    }
+
+    /// Record a key going down (or repeating while held) or up
+    pub(crate) fn key(&mut self, key: Key, down: bool) {
+        let held = self.down.contains(&key);
+        if down {
+            self.pressed.push((key, held));
+            if !held {
+                self.down.push(key);
+            }
+        } else if held {
+            self.down.retain(|&k| k != key);
+            self.released.push(key);
+        }
+    }
+
+    pub(crate) fn mouse_move(&mut self, pos: Option<(f32, f32)>) {
+        self.mouse = pos;
+        self.mouse_moved = true;
+    }
+
+    /// True if the mouse moved since the start of the frame
+    pub(crate) fn mouse_moved(&self) -> bool {
+        self.mouse_moved
+    }
+
+    pub(crate) fn button(&mut self, button: MouseButton, down: bool) {
+        self.buttons[button_index(button)] = down;
+    }
+
+    pub(crate) fn scroll(&mut self, dx: f32, dy: f32) {
+        self.scroll.0 += dx;
+        self.scroll.1 += dy;
+    }
+
+    pub(crate) fn is_key_down(&self, key: Key) -> bool {
+        self.down.contains(&key)
+    }
+
+    pub(crate) fn is_key_pressed(&self, key: Key, repeat: KeyRepeat) -> bool {
+        self.pressed
+            .iter()
+            .any(|&(k, is_repeat)| k == key && (!is_repeat || repeat == KeyRepeat::Yes))
+    }
+
+    pub(crate) fn is_key_released(&self, key: Key) -> bool {
+        self.released.contains(&key)
+    }
+
+    pub(crate) fn get_keys(&self) -> Vec<Key> {
+        self.down.clone()
+    }
+
+    /// Mouse position in a window of `size`, following minifb's modes
+    pub(crate) fn get_mouse_pos(
+        &self,
+        mode: MouseMode,
+        size: (usize, usize),
+    ) -> Option<(f32, f32)> {
+        let (x, y) = self.mouse?;
+        let (w, h) = (size.0 as f32, size.1 as f32);
+        match mode {
+            MouseMode::Pass => Some((x, y)),
+            MouseMode::Clamp => Some((
+                x.clamp(0.0, (w - 1.0).max(0.0)),
+                y.clamp(0.0, (h - 1.0).max(0.0)),
+            )),
+            MouseMode::Discard => (x >= 0.0 && y >= 0.0 && x < w && y < h).then_some((x, y)),
+        }
+    }
+
+    pub(crate) fn get_mouse_down(&self, button: MouseButton) -> bool {
+        self.buttons[button_index(button)]
+    }
+
+    pub(crate) fn get_scroll_wheel(&self) -> Option<(f32, f32)> {
+        (self.scroll != (0.0, 0.0)).then_some(self.scroll)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_edges_within_one_frame() {
+        let mut input = InputState::default();
+        input.begin_frame();
+        input.key(Key::A, true);
+        input.key(Key::A, true);
+        input.key(Key::B, true);
+        input.key(Key::B, false);
+        assert!(input.is_key_down(Key::A) && !input.is_key_down(Key::B));
+        assert!(input.is_key_pressed(Key::B, KeyRepeat::No));
+        assert!(input.is_key_released(Key::B));
+
+        input.begin_frame();
+        input.key(Key::A, true);
+        assert!(!input.is_key_pressed(Key::A, KeyRepeat::No));
+        assert!(input.is_key_pressed(Key::A, KeyRepeat::Yes));
+        assert!(!input.is_key_released(Key::B));
+    }
+
+    #[test]
+    fn test_mouse_modes() {
+        let mut input = InputState::default();
+        input.mouse_move(Some((12.0, -3.0)));
+        assert_eq!(
+            input.get_mouse_pos(MouseMode::Pass, (10, 10)),
+            Some((12.0, -3.0))
+        );
+        assert_eq!(
+            input.get_mouse_pos(MouseMode::Clamp, (10, 10)),
+            Some((9.0, 0.0))
+        );
+        assert_eq!(input.get_mouse_pos(MouseMode::Discard, (10, 10)), None);
+        assert_eq!(input.get_scroll_wheel(), None);
+        input.scroll(0.0, 1.0);
+        assert_eq!(input.get_scroll_wheel(), Some((0.0, 1.0)));
+    }
+}
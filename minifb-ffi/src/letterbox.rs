@@ -0,0 +1,225 @@
+//! Aspect-preserving presentation with letterbox bars
+//!
+//! minifb's own scale modes stretch or crop when the window and buffer sizes
+//! differ. In letterbox mode every present path scales the buffer into a
+//! window-sized frame here instead, centered between bars of a fixed color,
+//! and remembers the placement so mouse positions can be mapped back into
+//! buffer pixels.
+
+use crate::MiniFBWindow;
+use minifb::MouseMode;
+
+/// Letterbox presentation settings
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MiniFBLetterbox {
+    pub enabled: bool,
+    /// Only scale up by whole factors, keeping pixels square and sharp;
+    /// buffers larger than the window are still scaled down to fit
+    pub integer_scale: bool,
+    /// 0RGB color of the bars around the image
+    pub bar_color: u32,
+}
+
+/// Where a mapped mouse position lies relative to the image
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiniFBMouseBufferPos {
+    /// No mouse position available
+    MouseUnavailable = 0,
+    /// Position is over the image
+    MouseInside = 1,
+    /// Position is over a bar or outside the window
+    MouseOutside = 2,
+}
+
+/// Create default letterbox settings (disabled, fractional scaling, black bars)
+#[no_mangle]
+pub extern "C" fn minifb_letterbox_default() -> MiniFBLetterbox {
+    MiniFBLetterbox {
+        enabled: false,
+        integer_scale: false,
+        bar_color: 0,
+    }
+}
+
+/// Placement of the last presented buffer inside the window
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct Viewport {
+    pub buffer: (usize, usize),
+    pub window: (usize, usize),
+    /// Image rectangle in window pixels: x, y, width, height
+    pub image: (usize, usize, usize, usize),
+}
+
+impl Viewport {
+    /// Fit a buffer into a window, centered and aspect-preserving
+    pub(crate) fn fit(buffer: (usize, usize), window: (usize, usize), integer: bool) -> Self {
+        let (bw, bh) = (buffer.0.max(1), buffer.1.max(1));
+        let (ww, wh) = (window.0.max(1), window.1.max(1));
+        let scale = (ww as f64 / bw as f64).min(wh as f64 / bh as f64);
+        let scale = if integer && scale >= 1.0 {
+            scale.floor()
+        } else {
+            scale
+        };
+        let w = ((bw as f64 * scale).round() as usize).clamp(1, ww);
+        let h = ((bh as f64 * scale).round() as usize).clamp(1, wh);
+        Viewport {
+            buffer,
+            window,
+            image: ((ww - w) / 2, (wh - h) / 2, w, h),
+        }
+    }
+
+    /// Map a window position to buffer coordinates; true if over the image
+    pub(crate) fn window_to_buffer(&self, x: f32, y: f32) -> (f32, f32, bool) {
+        let (ix, iy, iw, ih) = self.image;
+        if iw == 0 || ih == 0 {
+            return (x, y, false);
+        }
+        let bx = (x - ix as f32) * self.buffer.0 as f32 / iw as f32;
+        let by = (y - iy as f32) * self.buffer.1 as f32 / ih as f32;
+        let inside =
+            bx >= 0.0 && by >= 0.0 && bx < self.buffer.0 as f32 && by < self.buffer.1 as f32;
+        (bx, by, inside)
+    }
+}
+
+/// Nearest-neighbor scale `src` into the viewport's image rectangle of `frame`
+pub(crate) fn compose(frame: &mut Vec<u32>, src: &[u32], viewport: &Viewport, bar_color: u32) {
+    let (bw, bh) = viewport.buffer;
+    let (ww, wh) = viewport.window;
+    let (ix, iy, iw, ih) = viewport.image;
+    frame.clear();
+    frame.resize(ww * wh, bar_color);
+    let columns: Vec<usize> = (0..iw).map(|x| x * bw / iw).collect();
+    for y in 0..ih {
+        let src_row = &src[(y * bh / ih) * bw..][..bw];
+        let dst_row = &mut frame[(iy + y) * ww + ix..][..iw];
+        if iw == bw {
+            dst_row.copy_from_slice(src_row);
+        } else {
+            for (dst, &x) in dst_row.iter_mut().zip(&columns) {
+                *dst = src_row[x];
+            }
+        }
+    }
+}
+
+impl MiniFBWindow {
+    /// Upload a buffer, letterboxing it first when enabled
+    pub(crate) fn present(
+        &mut self,
+        buffer: &[u32],
+        width: usize,
+        height: usize,
+    ) -> minifb::Result<()> {
+        let window_size = self.window.get_size();
+        if !self.letterbox.enabled || width * height == 0 || window_size.0 * window_size.1 == 0 {
+            self.viewport = Viewport {
+                buffer: (width, height),
+                window: window_size,
+                image: (0, 0, window_size.0, window_size.1),
+            };
+            return self.window.update_with_buffer(buffer, width, height);
+        }
+        self.viewport = Viewport::fit((width, height), window_size, self.letterbox.integer_scale);
+        compose(
+            &mut self.frame,
+            buffer,
+            &self.viewport,
+            self.letterbox.bar_color,
+        );
+        let (ww, wh) = self.viewport.window;
+        self.window.update_with_buffer(&self.frame, ww, wh)
+    }
+
+    /// True if the window was resized since the last letterboxed present,
+    /// so the frame has to be composed again
+    pub(crate) fn viewport_stale(&self) -> bool {
+        self.letterbox.enabled && self.window.get_size() != self.viewport.window
+    }
+}
+
+/// Set letterbox presentation for all later updates of a window
+///
+/// Creating the window with `resize` enabled lets the user change its size
+/// while the image keeps its aspect ratio.
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by `minifb_window_new`
+/// - `letterbox` must be a valid pointer to MiniFBLetterbox
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_set_letterbox(
+    window: *mut MiniFBWindow,
+    letterbox: *const MiniFBLetterbox,
+) {
+    if window.is_null() || letterbox.is_null() {
+        return;
+    }
+    (*window).letterbox = *letterbox;
+    (*window).presented_canvas = None;
+}
+
+/// Get the mouse position in pixels of the last presented buffer
+///
+/// Works in letterbox mode and with minifb's default stretching. The
+/// coordinates are written even when the cursor is outside the image.
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by `minifb_window_new`
+/// - `out_x` and `out_y` must be valid pointers
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_get_mouse_pos_buffer(
+    window: *const MiniFBWindow,
+    out_x: *mut f32,
+    out_y: *mut f32,
+) -> MiniFBMouseBufferPos {
+    if window.is_null() || out_x.is_null() || out_y.is_null() {
+        return MiniFBMouseBufferPos::MouseUnavailable;
+    }
+    let window = &*window;
+    let Some((x, y)) = window.window.get_mouse_pos(MouseMode::Pass) else {
+        return MiniFBMouseBufferPos::MouseUnavailable;
+    };
+    let (bx, by, inside) = window.viewport.window_to_buffer(x, y);
+    *out_x = bx;
+    *out_y = by;
+    if inside {
+        MiniFBMouseBufferPos::MouseInside
+    } else {
+        MiniFBMouseBufferPos::MouseOutside
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_and_map_back() {
+        let viewport = Viewport::fit((320, 200), (1000, 700), true);
+        assert_eq!(viewport.image, (20, 50, 960, 600));
+        assert_eq!(viewport.window_to_buffer(20.0, 50.0), (0.0, 0.0, true));
+        assert!(!viewport.window_to_buffer(500.0, 20.0).2);
+
+        let viewport = Viewport::fit((320, 200), (1000, 700), false);
+        assert_eq!(viewport.image, (0, 37, 1000, 625));
+
+        // Integer scaling never upsizes past the window; shrinking stays fractional
+        let viewport = Viewport::fit((400, 400), (300, 200), true);
+        assert_eq!(viewport.image, (50, 0, 200, 200));
+    }
+
+    #[test]
+    fn test_compose_bars() {
+        let viewport = Viewport::fit((2, 1), (6, 5), true);
+        let mut frame = Vec::new();
+        compose(&mut frame, &[1, 2], &viewport, 9);
+        assert_eq!(viewport.image, (0, 1, 6, 3));
+        assert_eq!(&frame[..6], &[9; 6]);
+        assert_eq!(&frame[6..12], &[1, 1, 1, 2, 2, 2]);
+        assert_eq!(&frame[24..], &[9; 6]);
+    }
+}
@@ -8,9 +8,42 @@ use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
 use std::slice;
 
+pub mod accum;
+mod backend;
+mod bitmap_font;
+pub mod camera;
+pub mod canvas;
+pub mod color;
+pub mod composite;
+pub mod dirty;
+pub mod display_list;
+pub mod draw;
+#[cfg(unix)]
+pub mod frame_server;
+pub mod gradient;
+pub mod grid;
+pub mod image;
+mod input;
+pub mod letterbox;
+pub mod present;
+pub mod pattern;
+pub mod proxy;
+pub mod raster;
+pub mod record;
+pub mod render_thread;
+pub mod run_loop;
+pub mod script;
+mod simd;
+pub mod stroke;
+pub mod svg;
+pub mod terminal;
+pub mod text;
+pub mod vnc;
+pub mod wakeup;
+
 // Thread-local storage for last error message
 thread_local! {
-    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
 }
 
 fn set_last_error(msg: &str) {
@@ -19,6 +52,14 @@ fn set_last_error(msg: &str) {
     });
 }
 
+fn last_error() -> Option<String> {
+    LAST_ERROR.with(|e| {
+        e.borrow()
+            .as_ref()
+            .map(|s| s.to_string_lossy().into_owned())
+    })
+}
+
 /// Get the last error message, or null if no error
 /// The returned pointer is valid until the next minifb call
 #[no_mangle]
@@ -146,6 +187,14 @@ pub enum MiniFBKey {
 }
 
 impl MiniFBKey {
+    /// Key for an integer key code as used in key arrays
+    fn from_code(code: i32) -> Option<Self> {
+        // SAFETY: MiniFBKey is a fieldless repr(C) enum numbered from 0 to Count
+        (0..=MiniFBKey::Count as i32)
+            .contains(&code)
+            .then(|| unsafe { std::mem::transmute::<i32, MiniFBKey>(code) })
+    }
+
     fn to_minifb(self) -> Key {
         match self {
             MiniFBKey::Key0 => Key::Key0,
@@ -440,14 +489,43 @@ impl MiniFBCursorStyle {
 
 /// Opaque window handle
 pub struct MiniFBWindow {
-    window: Window,
+    window: backend::Backend,
     // Store dimensions for buffer validation
+    #[allow(dead_code)]
     width: usize,
+    #[allow(dead_code)]
     height: usize,
+    // Conversion target reused across `minifb_window_update_with_pixels` calls
+    scratch: Vec<u32>,
+    // Canvas currently on screen as (id, present count), used to skip
+    // unchanged canvas presents
+    presented_canvas: Option<(u64, u64)>,
+    dirty_stats: dirty::MiniFBDirtyStats,
+    letterbox: letterbox::MiniFBLetterbox,
+    // Letterboxed frame in window size and where the image was placed in it
+    frame: Vec<u32>,
+    viewport: letterbox::Viewport,
+}
+
+impl MiniFBWindow {
+    pub(crate) fn with_backend(window: backend::Backend, width: usize, height: usize) -> Self {
+        MiniFBWindow {
+            window,
+            width,
+            height,
+            scratch: Vec::new(),
+            presented_canvas: None,
+            dirty_stats: dirty::MiniFBDirtyStats::default(),
+            letterbox: letterbox::minifb_letterbox_default(),
+            frame: Vec::new(),
+            viewport: letterbox::Viewport::default(),
+        }
+    }
 }
 
 /// Window creation options
 #[repr(C)]
+#[derive(Clone, Copy)]
 pub struct MiniFBWindowOptions {
     pub borderless: bool,
     pub title: bool,
@@ -470,6 +548,11 @@ pub extern "C" fn minifb_window_options_default() -> MiniFBWindowOptions {
 
 /// Create a new window
 ///
+/// With the environment variable `MINIFB_FFI_BACKEND=terminal` the window is
+/// drawn in the terminal instead (see `minifb_window_new_terminal`), and with
+/// `MINIFB_FFI_BACKEND=headless` it has no display at all (see
+/// `minifb_window_new_headless`).
+///
 /// # Safety
 /// - `name` must be a valid null-terminated C string
 /// - `opts` must be a valid pointer to MiniFBWindowOptions
@@ -485,6 +568,14 @@ pub unsafe extern "C" fn minifb_window_new(
     if name.is_null() || opts.is_null() {
         return std::ptr::null_mut();
     }
+    match std::env::var("MINIFB_FFI_BACKEND").as_deref() {
+        Ok("terminal") => {
+            let terminal_opts = terminal::minifb_terminal_options_default();
+            return terminal::minifb_window_new_terminal(name, width, height, &terminal_opts);
+        }
+        Ok("headless") => return backend::minifb_window_new_headless(width, height),
+        _ => {}
+    }
 
     let name = match CStr::from_ptr(name).to_str() {
         Ok(s) => s,
@@ -502,11 +593,11 @@ pub unsafe extern "C" fn minifb_window_new(
     };
 
     match Window::new(name, width, height, window_opts) {
-        Ok(window) => Box::into_raw(Box::new(MiniFBWindow {
-            window,
+        Ok(window) => Box::into_raw(Box::new(MiniFBWindow::with_backend(
+            backend::Backend::new(backend::Device::Native(window)),
             width,
             height,
-        })),
+        ))),
         Err(e) => {
             set_last_error(&format!("{:?}", e));
             std::ptr::null_mut()
@@ -569,8 +660,11 @@ pub unsafe extern "C" fn minifb_window_update_with_buffer(
     }
 
     let buffer_slice = slice::from_raw_parts(buffer, width * height);
-    match (*window).window.update_with_buffer(buffer_slice, width, height) {
-        Ok(_) => 0,
+    match (*window).present(buffer_slice, width, height) {
+        Ok(_) => {
+            (*window).record_present(None, dirty::DirtyRect::full(width, height));
+            0
+        }
         Err(_) => -1,
     }
 }
@@ -798,6 +892,95 @@ pub unsafe extern "C" fn minifb_window_get_scroll_wheel(
     }
 }
 
+// ============================================================================
+// Input Injection
+// ============================================================================
+
+// Injected input is queued and applied at the window's next update, as if it
+// had arrived just before it, so edge queries like `is_key_pressed` see it for
+// exactly that frame. For that frame, injected keys and buttons override real
+// input, so an injected release takes effect while the key is still held; in
+// later frames the two are combined again. An injected pointer position or
+// leave is reported until the real mouse moves.
+
+/// Inject a key press or release
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by one of the window constructors
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_inject_key(
+    window: *mut MiniFBWindow,
+    key: MiniFBKey,
+    down: bool,
+) {
+    if window.is_null() {
+        return;
+    }
+    (*window)
+        .window
+        .inject(input::InputEvent::Key(key.to_minifb(), down));
+}
+
+/// Inject a mouse move to window coordinates
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by one of the window constructors
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_inject_mouse_move(
+    window: *mut MiniFBWindow,
+    x: f32,
+    y: f32,
+) {
+    if window.is_null() {
+        return;
+    }
+    (*window)
+        .window
+        .inject(input::InputEvent::MouseMove(Some((x, y))));
+}
+
+/// Inject the mouse leaving the window, so no position is reported
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by one of the window constructors
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_inject_mouse_leave(window: *mut MiniFBWindow) {
+    if window.is_null() {
+        return;
+    }
+    (*window).window.inject(input::InputEvent::MouseMove(None));
+}
+
+/// Inject a mouse button press or release
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by one of the window constructors
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_inject_button(
+    window: *mut MiniFBWindow,
+    button: MiniFBMouseButton,
+    down: bool,
+) {
+    if window.is_null() {
+        return;
+    }
+    (*window)
+        .window
+        .inject(input::InputEvent::Button(button.to_minifb(), down));
+}
+
+/// Inject scroll wheel movement, added to any real scrolling in the same frame
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by one of the window constructors
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_inject_scroll(window: *mut MiniFBWindow, dx: f32, dy: f32) {
+    if window.is_null() {
+        return;
+    }
+    (*window).window.inject(input::InputEvent::Scroll(dx, dy));
+}
+
 // ============================================================================
 // Window Properties
 // ============================================================================
@@ -905,4 +1088,55 @@ mod tests {
         assert_eq!(MiniFBKey::from_minifb(Key::Space), MiniFBKey::Space);
         assert_eq!(MiniFBKey::A.to_minifb(), Key::A);
     }
+
+    #[test]
+    fn test_injected_input_edges() {
+        let window = backend::minifb_window_new_headless(16, 16);
+        unsafe {
+            minifb_window_set_target_fps(window, 0);
+            minifb_window_inject_key(window, MiniFBKey::Space, true);
+            minifb_window_inject_mouse_move(window, 4.0, 20.0);
+            minifb_window_inject_button(window, MiniFBMouseButton::MouseRight, true);
+            minifb_window_inject_scroll(window, 0.0, -2.0);
+            // Nothing changes before the next update
+            assert!(!minifb_window_is_key_down(window, MiniFBKey::Space));
+            minifb_window_update(window);
+            assert!(minifb_window_is_key_pressed(
+                window,
+                MiniFBKey::Space,
+                false
+            ));
+            assert!(minifb_window_get_mouse_down(
+                window,
+                MiniFBMouseButton::MouseRight
+            ));
+            let (mut x, mut y) = (0.0, 0.0);
+            assert!(!minifb_window_get_mouse_pos(
+                window,
+                MiniFBMouseMode::Discard,
+                &mut x,
+                &mut y
+            ));
+            assert!(minifb_window_get_mouse_pos(
+                window,
+                MiniFBMouseMode::Clamp,
+                &mut x,
+                &mut y
+            ));
+            assert_eq!((x, y), (4.0, 15.0));
+            assert!(minifb_window_get_scroll_wheel(window, &mut x, &mut y));
+            assert_eq!((x, y), (0.0, -2.0));
+
+            minifb_window_inject_key(window, MiniFBKey::Space, false);
+            minifb_window_update(window);
+            assert!(!minifb_window_is_key_pressed(
+                window,
+                MiniFBKey::Space,
+                false
+            ));
+            assert!(minifb_window_is_key_released(window, MiniFBKey::Space));
+            assert!(!minifb_window_get_scroll_wheel(window, &mut x, &mut y));
+            minifb_window_free(window);
+        }
+    }
 }
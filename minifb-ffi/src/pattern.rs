@@ -0,0 +1,262 @@
+//! Pattern fills for polygons: hatches, cross-hatches, dots and tiled images
+//!
+//! Patterns live in world space. Each pixel inside the polygon is mapped
+//! back through the camera and the pattern is evaluated there, so hatches
+//! pan and zoom with the scene. Hatch lines and dots get anti-aliased edges
+//! from their distance to the pixel center.
+
+use crate::camera::{MiniFBCamera, MiniFBTransform};
+use crate::canvas::MiniFBCanvas;
+use crate::draw::{camera_transform, fill_spans, read_rings};
+use crate::image::MiniFBImage;
+
+/// Pattern drawn inside a polygon
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiniFBPatternKind {
+    /// Parallel lines at `angle`
+    PatternHatch = 0,
+    /// Two sets of lines, at `angle` and `angle + 90`
+    PatternCrossHatch = 1,
+    /// Dots on a square grid rotated by `angle`
+    PatternDots = 2,
+    /// `image` repeated on a grid rotated by `angle`, one tile `spacing` wide
+    PatternImage = 3,
+}
+
+/// Pattern fill style
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MiniFBPattern {
+    pub kind: MiniFBPatternKind,
+    /// 0RGB color of lines and dots
+    pub color: u32,
+    pub opacity: f32,
+    /// 0RGB color laid under the pattern
+    pub background: u32,
+    /// Opacity of the background, 0.0 leaves the canvas showing through
+    pub background_opacity: f32,
+    /// Rotation in degrees
+    pub angle: f32,
+    /// Distance between lines, dots or tiles, in world units
+    pub spacing: f32,
+    /// Hatch line width, in world units
+    pub line_width: f32,
+    /// Dot radius, in world units
+    pub dot_radius: f32,
+    /// Tile for `PatternImage`; only read during the drawing call
+    pub image: *const MiniFBImage,
+}
+
+/// Create the default pattern (white 45 degree hatch, 8 units apart, 1 unit wide)
+#[no_mangle]
+pub extern "C" fn minifb_pattern_default() -> MiniFBPattern {
+    MiniFBPattern {
+        kind: MiniFBPatternKind::PatternHatch,
+        color: 0x00FFFFFF,
+        opacity: 1.0,
+        background: 0x00000000,
+        background_opacity: 0.0,
+        angle: 45.0,
+        spacing: 8.0,
+        line_width: 1.0,
+        dot_radius: 1.5,
+        image: std::ptr::null(),
+    }
+}
+
+/// Distance from `v` to the nearest multiple of `spacing`
+#[inline]
+fn grid_distance(v: f64, spacing: f64) -> f64 {
+    (v - spacing * (v / spacing).round()).abs()
+}
+
+/// Pixel coverage of a shape edge `distance` world units inside the shape
+#[inline]
+fn edge_coverage(distance: f64, pixels_per_unit: f64) -> f32 {
+    (distance * pixels_per_unit + 0.5).clamp(0.0, 1.0) as f32
+}
+
+struct Sampler<'a> {
+    pattern: &'a MiniFBPattern,
+    image: Option<&'a MiniFBImage>,
+    cos: f64,
+    sin: f64,
+    spacing: f64,
+    pixels_per_unit: f64,
+}
+
+impl Sampler<'_> {
+    /// Pattern color and coverage (0.0-1.0) at a world-space point
+    fn sample(&self, x: f64, y: f64) -> (u32, f32) {
+        let p = self.pattern;
+        // Coordinates along and across the pattern direction
+        let u = x * self.cos + y * self.sin;
+        let v = -x * self.sin + y * self.cos;
+        let half = p.line_width as f64 / 2.0;
+        let coverage = match p.kind {
+            MiniFBPatternKind::PatternHatch => {
+                edge_coverage(half - grid_distance(v, self.spacing), self.pixels_per_unit)
+            }
+            MiniFBPatternKind::PatternCrossHatch => {
+                let across = half - grid_distance(v, self.spacing);
+                let along = half - grid_distance(u, self.spacing);
+                edge_coverage(across.max(along), self.pixels_per_unit)
+            }
+            MiniFBPatternKind::PatternDots => {
+                let d = grid_distance(u, self.spacing).hypot(grid_distance(v, self.spacing));
+                edge_coverage(p.dot_radius as f64 - d, self.pixels_per_unit)
+            }
+            MiniFBPatternKind::PatternImage => {
+                let Some(image) = self.image else {
+                    return (0, 0.0);
+                };
+                let tile_w = self.spacing;
+                let tile_h = self.spacing * image.height as f64 / image.width as f64;
+                let ix = ((u.rem_euclid(tile_w) / tile_w) * image.width as f64) as usize;
+                let iy = ((v.rem_euclid(tile_h) / tile_h) * image.height as f64) as usize;
+                let argb =
+                    image.pixels[iy.min(image.height - 1) * image.width + ix.min(image.width - 1)];
+                return (argb & 0xFFFFFF, (argb >> 24) as f32 / 255.0);
+            }
+        };
+        (p.color, coverage)
+    }
+}
+
+impl MiniFBCanvas {
+    pub(crate) fn fill_rings_pattern(
+        &mut self,
+        rings: &[Vec<(f64, f64)>],
+        pattern: &MiniFBPattern,
+        image: Option<&MiniFBImage>,
+        transform: &MiniFBTransform,
+    ) {
+        let Some(inverse) = transform.inverse() else {
+            return;
+        };
+        if pattern.spacing.is_nan()
+            || pattern.spacing <= 0.0
+            || image.is_some_and(|i| i.width == 0 || i.height == 0)
+        {
+            return;
+        }
+        let angle = (pattern.angle as f64).to_radians();
+        let sampler = Sampler {
+            pattern,
+            image,
+            cos: angle.cos(),
+            sin: angle.sin(),
+            spacing: pattern.spacing as f64,
+            pixels_per_unit: transform.determinant().abs().sqrt(),
+        };
+        let background_alpha = (pattern.background_opacity.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let opacity = pattern.opacity.clamp(0.0, 1.0);
+
+        let mut spans = Vec::new();
+        fill_spans(self.width, self.height, rings, |y, x0, x1| {
+            spans.push((y, x0, x1))
+        });
+        for (y, x0, x1) in spans {
+            let y = y as i64;
+            for x in x0 as i64..x1 as i64 {
+                self.blend(x, y, pattern.background, background_alpha);
+                let (wx, wy) = inverse.apply(x as f64 + 0.5, y as f64 + 0.5);
+                let (color, coverage) = sampler.sample(wx, wy);
+                self.blend(x, y, color, (coverage * opacity * 255.0).round() as u8);
+            }
+        }
+    }
+}
+
+/// Fill a polygon with a pattern, using the even-odd rule like
+/// `minifb_canvas_fill_polygon`
+///
+/// # Safety
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+/// - `ring_sizes` must point to `ring_count` point counts
+/// - `points` must point to `2 * sum(ring_sizes)` f32 values, rings back to back
+/// - `pattern` must be a valid pointer to MiniFBPattern; for `PatternImage`
+///   its `image` must be a valid image pointer
+/// - `camera` must be a valid camera pointer, or null for pixel coordinates
+#[no_mangle]
+pub unsafe extern "C" fn minifb_canvas_fill_polygon_pattern(
+    canvas: *mut MiniFBCanvas,
+    points: *const f32,
+    ring_sizes: *const usize,
+    ring_count: usize,
+    pattern: *const MiniFBPattern,
+    camera: *const MiniFBCamera,
+) {
+    if canvas.is_null() || points.is_null() || ring_sizes.is_null() || pattern.is_null() {
+        return;
+    }
+    let pattern = &*pattern;
+    let image = if pattern.kind == MiniFBPatternKind::PatternImage {
+        if pattern.image.is_null() {
+            return;
+        }
+        Some(&*pattern.image)
+    } else {
+        None
+    };
+    let transform = camera_transform(camera);
+    let rings = read_rings(points, ring_sizes, ring_count, &transform);
+    (*canvas).fill_rings_pattern(&rings, pattern, image, &transform);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(size: f64) -> Vec<Vec<(f64, f64)>> {
+        vec![vec![(0.0, 0.0), (size, 0.0), (size, size), (0.0, size)]]
+    }
+
+    #[test]
+    fn test_hatch_is_periodic_and_clipped() {
+        let pattern = MiniFBPattern {
+            angle: 0.0,
+            spacing: 4.0,
+            line_width: 1.0,
+            color: 0xFF,
+            ..minifb_pattern_default()
+        };
+        let mut canvas = MiniFBCanvas::new(16, 16);
+        canvas.fill_rings_pattern(&square(12.0), &pattern, None, &MiniFBTransform::IDENTITY);
+        let column: Vec<u32> = (0..16).map(|y| canvas.pixels[y * 16 + 5]).collect();
+        // Lines sit on y = 0, 4, 8, straddling the pixel rows around them
+        assert_eq!(column[1], 0);
+        assert_eq!(column[3], column[7]);
+        assert_ne!(column[3], 0);
+        assert!(canvas.pixels[13 * 16 + 5] == 0 && canvas.pixels[3 * 16 + 13] == 0);
+
+        // Zooming the camera scales the pattern with the world
+        let zoom = MiniFBTransform::scale(2.0, 2.0);
+        let mut zoomed = MiniFBCanvas::new(32, 32);
+        let rings = vec![square(12.0)[0]
+            .iter()
+            .map(|&(x, y)| (x * 2.0, y * 2.0))
+            .collect()];
+        zoomed.fill_rings_pattern(&rings, &pattern, None, &zoom);
+        assert_eq!(zoomed.pixels[4 * 32 + 10], 0);
+        assert_eq!(zoomed.pixels[8 * 32 + 10], 0xFF);
+    }
+
+    #[test]
+    fn test_dots() {
+        let pattern = MiniFBPattern {
+            kind: MiniFBPatternKind::PatternDots,
+            angle: 0.0,
+            spacing: 8.0,
+            dot_radius: 2.0,
+            background: 0x010101,
+            background_opacity: 1.0,
+            ..minifb_pattern_default()
+        };
+        let mut canvas = MiniFBCanvas::new(16, 16);
+        canvas.fill_rings_pattern(&square(16.0), &pattern, None, &MiniFBTransform::IDENTITY);
+        assert_eq!(canvas.pixels[8 * 16 + 8], 0xFFFFFF);
+        assert_eq!(canvas.pixels[4 * 16 + 4], 0x010101);
+    }
+}
@@ -0,0 +1,251 @@
+//! Presenting buffers in formats other than minifb's native 0RGB u32
+//!
+//! Conversion happens in Rust into a scratch buffer owned by the window, so
+//! OCaml can hand over a `Float_bigarray.t` or raw RGBA8 data directly.
+//! The 8-bit formats convert several pixels per instruction through the
+//! SSE2 kernels in `simd`.
+
+use crate::composite::linear_to_srgb;
+use crate::dirty::DirtyRect;
+use crate::simd;
+use crate::{set_last_error, MiniFBWindow};
+use std::os::raw::c_void;
+use std::slice;
+use std::sync::OnceLock;
+
+/// Layout of a source pixel buffer
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiniFBPixelFormat {
+    /// One u8 luminance value per pixel
+    FormatGray8 = 0,
+    /// Four u8 per pixel: red, green, blue, alpha (alpha ignored)
+    FormatRgba8 = 1,
+    /// Four u8 per pixel: blue, green, red, alpha (alpha ignored)
+    FormatBgra8 = 2,
+    /// One f32 per pixel, mapped linearly from [range_min, range_max] to black-white
+    FormatGrayF32 = 3,
+    /// Three f32 per pixel (red, green, blue), exposure-scaled and tone mapped
+    FormatRgbF32 = 4,
+}
+
+/// Tone mapping operator for `FormatRgbF32`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiniFBToneMap {
+    /// Clamp to [0, 1]
+    ToneMapClamp = 0,
+    /// x / (1 + x)
+    ToneMapReinhard = 1,
+    /// Narkowicz's fit of the ACES filmic curve
+    ToneMapAces = 2,
+}
+
+/// Source format description and conversion parameters
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MiniFBPixelOptions {
+    pub format: MiniFBPixelFormat,
+    /// Value range for `FormatGrayF32`
+    pub range_min: f32,
+    pub range_max: f32,
+    /// Multiplier applied to `FormatRgbF32` values before tone mapping
+    pub exposure: f32,
+    pub tone_map: MiniFBToneMap,
+    /// `FormatRgbF32` values are linear light and get sRGB-encoded
+    pub linear: bool,
+}
+
+/// Create default pixel options for a format
+#[no_mangle]
+pub extern "C" fn minifb_pixel_options_default(format: MiniFBPixelFormat) -> MiniFBPixelOptions {
+    MiniFBPixelOptions {
+        format,
+        range_min: 0.0,
+        range_max: 1.0,
+        exposure: 1.0,
+        tone_map: MiniFBToneMap::ToneMapClamp,
+        linear: true,
+    }
+}
+
+/// Linear [0, 1] to 8-bit sRGB, tabulated at 12-bit input precision
+pub(crate) fn linear_to_srgb8(v: f32) -> u32 {
+    static TABLE: OnceLock<[u8; 4096]> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        let mut table = [0; 4096];
+        for (i, out) in table.iter_mut().enumerate() {
+            *out = linear_to_srgb(i as f32 / 4095.0);
+        }
+        table
+    });
+    // NaN saturates to index 0
+    table[(v.clamp(0.0, 1.0) * 4095.0 + 0.5) as usize] as u32
+}
+
+#[inline]
+fn tone_map(v: f32, op: MiniFBToneMap) -> f32 {
+    match op {
+        MiniFBToneMap::ToneMapClamp => v,
+        MiniFBToneMap::ToneMapReinhard => v / (1.0 + v),
+        MiniFBToneMap::ToneMapAces => (v * (2.51 * v + 0.03)) / (v * (2.43 * v + 0.59) + 0.14),
+    }
+}
+
+/// Convert `width * height` source pixels into 0RGB
+///
+/// # Safety
+/// `data` must point to `width * height` pixels of `opts.format`, suitably
+/// aligned for the format's element type
+pub(crate) unsafe fn convert(
+    data: *const c_void,
+    width: usize,
+    height: usize,
+    opts: &MiniFBPixelOptions,
+    out: &mut [u32],
+) {
+    let n = width * height;
+    match opts.format {
+        MiniFBPixelFormat::FormatGray8 => {
+            let src = slice::from_raw_parts(data as *const u8, n);
+            let done = simd::gray_to_0rgb(src, out);
+            for (o, &l) in out[done..].iter_mut().zip(&src[done..]) {
+                *o = (l as u32) * 0x010101;
+            }
+        }
+        MiniFBPixelFormat::FormatRgba8 => {
+            let src = slice::from_raw_parts(data as *const u8, n * 4);
+            let done = simd::rgba_to_0rgb(src, out);
+            for (o, p) in out[done..].iter_mut().zip(src[done * 4..].chunks_exact(4)) {
+                // Swap red and blue within the word
+                let p = u32::from_le_bytes([p[0], p[1], p[2], p[3]]);
+                *o = ((p & 0xFF) << 16) | (p & 0xFF00) | ((p >> 16) & 0xFF);
+            }
+        }
+        MiniFBPixelFormat::FormatBgra8 => {
+            // Little-endian BGRA8 is already 0RGB once alpha is masked off
+            let src = slice::from_raw_parts(data as *const u8, n * 4);
+            let done = simd::bgra_to_0rgb(src, out);
+            for (o, p) in out[done..].iter_mut().zip(src[done * 4..].chunks_exact(4)) {
+                *o = u32::from_le_bytes([p[0], p[1], p[2], p[3]]) & 0x00FFFFFF;
+            }
+        }
+        MiniFBPixelFormat::FormatGrayF32 => {
+            let src = slice::from_raw_parts(data as *const f32, n);
+            let span = opts.range_max - opts.range_min;
+            let scale = if span != 0.0 { 255.0 / span } else { 0.0 };
+            for (o, &v) in out.iter_mut().zip(src) {
+                // NaN casts to 0
+                let l = ((v - opts.range_min) * scale + 0.5).clamp(0.0, 255.0) as u32;
+                *o = l * 0x010101;
+            }
+        }
+        MiniFBPixelFormat::FormatRgbF32 => {
+            let src = slice::from_raw_parts(data as *const f32, n * 3);
+            for (o, p) in out.iter_mut().zip(src.chunks_exact(3)) {
+                let channel = |v: f32| {
+                    let v = tone_map((v * opts.exposure).max(0.0), opts.tone_map);
+                    if opts.linear {
+                        linear_to_srgb8(v)
+                    } else {
+                        (v.clamp(0.0, 1.0) * 255.0 + 0.5) as u32
+                    }
+                };
+                *o = (channel(p[0]) << 16) | (channel(p[1]) << 8) | channel(p[2]);
+            }
+        }
+    }
+}
+
+/// Convert a buffer in any supported format to 0RGB
+///
+/// # Safety
+/// - `data` must point to `width * height` pixels of the format in `opts`
+/// - `opts` must be a valid pointer to MiniFBPixelOptions
+/// - `out_buffer` must point to at least `width * height` u32 values
+#[no_mangle]
+pub unsafe extern "C" fn minifb_convert_pixels(
+    data: *const c_void,
+    width: usize,
+    height: usize,
+    opts: *const MiniFBPixelOptions,
+    out_buffer: *mut u32,
+) {
+    if data.is_null() || opts.is_null() || out_buffer.is_null() {
+        return;
+    }
+    let out = slice::from_raw_parts_mut(out_buffer, width * height);
+    convert(data, width, height, &*opts, out);
+}
+
+/// Update the window with a buffer in any supported format
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by `minifb_window_new`
+/// - `data` must point to `width * height` pixels of the format in `opts`
+/// - `opts` must be a valid pointer to MiniFBPixelOptions
+///
+/// Returns 0 on success, -1 on error
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_update_with_pixels(
+    window: *mut MiniFBWindow,
+    data: *const c_void,
+    width: usize,
+    height: usize,
+    opts: *const MiniFBPixelOptions,
+) -> i32 {
+    if window.is_null() || data.is_null() || opts.is_null() {
+        return -1;
+    }
+    let window = &mut *window;
+    let mut scratch = std::mem::take(&mut window.scratch);
+    scratch.resize(width * height, 0);
+    convert(data, width, height, &*opts, &mut scratch);
+    let result = window.present(&scratch, width, height);
+    window.scratch = scratch;
+    match result {
+        Ok(_) => {
+            window.record_present(None, DirtyRect::full(width, height));
+            0
+        }
+        Err(e) => {
+            set_last_error(&format!("{:?}", e));
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run<T>(data: &[T], opts: MiniFBPixelOptions) -> Vec<u32> {
+        let mut out = vec![0; 2];
+        unsafe { convert(data.as_ptr() as *const c_void, 2, 1, &opts, &mut out) };
+        out
+    }
+
+    #[test]
+    fn test_byte_formats() {
+        let rgba = [1u8, 2, 3, 255, 4, 5, 6, 0];
+        let opts = minifb_pixel_options_default(MiniFBPixelFormat::FormatRgba8);
+        assert_eq!(run(&rgba, opts), vec![0x010203, 0x040506]);
+        let opts = minifb_pixel_options_default(MiniFBPixelFormat::FormatBgra8);
+        assert_eq!(run(&rgba, opts), vec![0x030201, 0x060504]);
+    }
+
+    #[test]
+    fn test_float_formats() {
+        let opts = MiniFBPixelOptions {
+            range_min: -1.0,
+            range_max: 1.0,
+            ..minifb_pixel_options_default(MiniFBPixelFormat::FormatGrayF32)
+        };
+        assert_eq!(run(&[-2.0f32, f32::NAN], opts), vec![0, 0]);
+        assert_eq!(run(&[1.0f32, 0.0], opts), vec![0xFFFFFF, 0x808080]);
+
+        let opts = minifb_pixel_options_default(MiniFBPixelFormat::FormatRgbF32);
+        let out = run(&[1.0f32, 0.0, 0.5, 4.0, 4.0, 4.0], opts);
+        assert_eq!(out, vec![0xFF00BC, 0xFFFFFF]);
+    }
+}
@@ -0,0 +1,278 @@
+//! Thread-safe window proxies
+//!
+//! A window may only be used from the thread that created it, but worker
+//! threads often need to report progress in its title or shut the viewer
+//! down. A `MiniFBWindowProxy` can be cloned and used from any thread; it
+//! queues title changes, close requests and user messages, and the window
+//! applies them at its next update (or pump). Each request also signals the
+//! window's wakeup descriptor, so an event loop watching it notices at once.
+//!
+//! A close request doesn't free the window: it makes `minifb_window_is_open`
+//! return false, as if the user had closed it, and the owning thread frees
+//! it as usual.
+
+use crate::backend::Backend;
+use crate::wakeup::Waker;
+use crate::{set_last_error, MiniFBWindow};
+use std::collections::VecDeque;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+#[derive(Default)]
+struct Pending {
+    /// Latest title asked for; earlier ones are never shown
+    title: Option<String>,
+    close: bool,
+    messages: VecDeque<u64>,
+    /// The window was freed, so nothing will ever be applied
+    gone: bool,
+}
+
+/// Requests queued by proxies for one window
+pub(crate) struct Requests {
+    pending: Mutex<Pending>,
+    waker: Arc<Waker>,
+}
+
+impl Requests {
+    fn lock(&self) -> MutexGuard<'_, Pending> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Queue a request unless the window is gone; false if it is
+    fn queue(&self, request: impl FnOnce(&mut Pending)) -> bool {
+        let mut pending = self.lock();
+        if pending.gone {
+            return false;
+        }
+        request(&mut pending);
+        drop(pending);
+        self.waker.wake();
+        true
+    }
+
+    /// Apply queued requests to the window, moving messages to its inbox
+    pub(crate) fn apply(&self, backend: &mut Backend) {
+        let mut pending = self.lock();
+        if let Some(title) = pending.title.take() {
+            backend.set_title(&title);
+        }
+        if pending.close {
+            backend.close_requested = true;
+        }
+        backend.messages.extend(pending.messages.drain(..));
+    }
+
+    /// Make every proxy fail from now on
+    pub(crate) fn window_gone(&self) {
+        self.lock().gone = true;
+    }
+}
+
+/// Opaque, thread-safe handle for sending requests to a window
+pub struct MiniFBWindowProxy {
+    requests: Arc<Requests>,
+}
+
+/// Create a proxy for a window
+///
+/// Proxies stay valid after the window is freed; their requests fail from
+/// then on.
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by `minifb_window_new`, used
+///   on the thread that owns it
+///
+/// Returns null if the window is null
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_proxy_new(
+    window: *mut MiniFBWindow,
+) -> *mut MiniFBWindowProxy {
+    if window.is_null() {
+        return std::ptr::null_mut();
+    }
+    let backend = &mut (*window).window;
+    let waker = backend.waker.clone();
+    let requests = backend.requests.get_or_insert_with(|| {
+        Arc::new(Requests {
+            pending: Mutex::default(),
+            waker,
+        })
+    });
+    Box::into_raw(Box::new(MiniFBWindowProxy {
+        requests: requests.clone(),
+    }))
+}
+
+/// Create another proxy for the same window, e.g. to hand to a new thread
+///
+/// # Safety
+/// - `proxy` must be a valid pointer returned by `minifb_window_proxy_new`
+///   or `minifb_window_proxy_clone`
+///
+/// Returns null if the proxy is null
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_proxy_clone(
+    proxy: *const MiniFBWindowProxy,
+) -> *mut MiniFBWindowProxy {
+    if proxy.is_null() {
+        return std::ptr::null_mut();
+    }
+    Box::into_raw(Box::new(MiniFBWindowProxy {
+        requests: (*proxy).requests.clone(),
+    }))
+}
+
+/// Free a proxy; the window and other proxies are unaffected
+///
+/// # Safety
+/// - `proxy` must be a valid pointer returned by `minifb_window_proxy_new`
+///   or `minifb_window_proxy_clone`, or null
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_proxy_free(proxy: *mut MiniFBWindowProxy) {
+    if !proxy.is_null() {
+        drop(Box::from_raw(proxy));
+    }
+}
+
+/// Change the window title at its next update
+///
+/// Only the latest title queued before an update is shown.
+///
+/// # Safety
+/// - `proxy` must be a valid proxy pointer
+/// - `title` must be a valid null-terminated C string
+///
+/// Returns 0 on success, -1 if the window was freed
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_proxy_set_title(
+    proxy: *const MiniFBWindowProxy,
+    title: *const c_char,
+) -> i32 {
+    if proxy.is_null() || title.is_null() {
+        return -1;
+    }
+    let title = match CStr::from_ptr(title).to_str() {
+        Ok(title) => title.to_owned(),
+        Err(_) => {
+            set_last_error("title is not valid UTF-8");
+            return -1;
+        }
+    };
+    if (*proxy).requests.queue(|p| p.title = Some(title)) {
+        0
+    } else {
+        set_last_error("window was freed");
+        -1
+    }
+}
+
+/// Close the window at its next update
+///
+/// From then on `minifb_window_is_open` returns false; the owning thread
+/// still has to free the window.
+///
+/// # Safety
+/// - `proxy` must be a valid proxy pointer
+///
+/// Returns 0 on success, -1 if the window was freed
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_proxy_request_close(proxy: *const MiniFBWindowProxy) -> i32 {
+    if proxy.is_null() {
+        return -1;
+    }
+    if (*proxy).requests.queue(|p| p.close = true) {
+        0
+    } else {
+        set_last_error("window was freed");
+        -1
+    }
+}
+
+/// Send a user-defined message, delivered at the window's next update
+///
+/// The owning thread receives it with `minifb_window_take_message`.
+///
+/// # Safety
+/// - `proxy` must be a valid proxy pointer
+///
+/// Returns 0 on success, -1 if the window was freed
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_proxy_send(
+    proxy: *const MiniFBWindowProxy,
+    message: u64,
+) -> i32 {
+    if proxy.is_null() {
+        return -1;
+    }
+    if (*proxy).requests.queue(|p| p.messages.push_back(message)) {
+        0
+    } else {
+        set_last_error("window was freed");
+        -1
+    }
+}
+
+/// Take the oldest message delivered by an update
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by `minifb_window_new`
+/// - `out_message` must be a valid pointer
+///
+/// Returns true and writes the message if there was one
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_take_message(
+    window: *mut MiniFBWindow,
+    out_message: *mut u64,
+) -> bool {
+    if window.is_null() || out_message.is_null() {
+        return false;
+    }
+    match (*window).window.messages.pop_front() {
+        Some(message) => {
+            *out_message = message;
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{minifb_window_free, minifb_window_is_open, minifb_window_update};
+
+    #[test]
+    fn test_requests_apply_at_update() {
+        let window = crate::backend::minifb_window_new_headless(4, 4);
+        unsafe {
+            let proxy = minifb_window_proxy_new(window);
+            let worker = minifb_window_proxy_clone(proxy) as usize;
+            std::thread::spawn(move || {
+                let worker = worker as *mut MiniFBWindowProxy;
+                assert_eq!(minifb_window_proxy_send(worker, 7), 0);
+                assert_eq!(minifb_window_proxy_send(worker, 8), 0);
+                assert_eq!(minifb_window_proxy_request_close(worker), 0);
+                minifb_window_proxy_free(worker);
+            })
+            .join()
+            .unwrap();
+
+            let mut message = 0;
+            assert!(!minifb_window_take_message(window, &mut message));
+            assert!(minifb_window_is_open(window));
+            minifb_window_update(window);
+            assert!(!minifb_window_is_open(window));
+            assert!(minifb_window_take_message(window, &mut message));
+            assert_eq!(message, 7);
+            assert!(minifb_window_take_message(window, &mut message));
+            assert_eq!(message, 8);
+            assert!(!minifb_window_take_message(window, &mut message));
+
+            minifb_window_free(window);
+            assert_eq!(minifb_window_proxy_send(proxy, 9), -1);
+            minifb_window_proxy_free(proxy);
+        }
+    }
+}
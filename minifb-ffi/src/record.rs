@@ -0,0 +1,494 @@
+//! Input recording and deterministic replay
+//!
+//! While recording, every update compares the window's input (as the query
+//! functions would report it) with the previous frame and writes what
+//! changed, keyed by the number of updates since recording started. Replaying
+//! counts updates the same way and substitutes the recorded state for live
+//! input until the recording ends, so a session can be reproduced exactly as
+//! long as the program updates its window the same way.
+//!
+//! The file is the magic `MFBREC`, a version byte and a sequence of records:
+//! a LEB128 frame delta, a byte of changed-field flags and the new values of
+//! those fields. A record without flags marks the end of the recording.
+
+use crate::input::{all_keys, InputState};
+use crate::{set_last_error, MiniFBKey, MiniFBWindow};
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode};
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::os::raw::c_char;
+
+const MAGIC: &[u8] = b"MFBREC";
+const VERSION: u8 = 1;
+
+const FIELD_DOWN: u8 = 1;
+const FIELD_PRESSED: u8 = 2;
+const FIELD_RELEASED: u8 = 4;
+const FIELD_MOUSE: u8 = 8;
+const FIELD_BUTTONS: u8 = 16;
+const FIELD_SCROLL: u8 = 32;
+
+/// Input of one frame as seen through the query functions
+pub(crate) fn observe(backend: &crate::backend::Backend) -> InputState {
+    let mut state = InputState::default();
+    state.down = backend.get_keys();
+    state.mouse = backend.get_mouse_pos(MouseMode::Pass);
+    state.scroll = backend.get_scroll_wheel().unwrap_or((0.0, 0.0));
+    for key in all_keys() {
+        if backend.is_key_pressed(key, KeyRepeat::No) {
+            state.pressed.push((key, false));
+        } else if backend.is_key_pressed(key, KeyRepeat::Yes) {
+            state.pressed.push((key, true));
+        }
+        if backend.is_key_released(key) {
+            state.released.push(key);
+        }
+    }
+    for (down, button) in
+        state
+            .buttons
+            .iter_mut()
+            .zip([MouseButton::Left, MouseButton::Middle, MouseButton::Right])
+    {
+        *down = backend.get_mouse_down(button);
+    }
+    state
+}
+
+fn write_varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn write_keys(out: &mut Vec<u8>, keys: impl ExactSizeIterator<Item = u8>) {
+    out.push(keys.len().min(255) as u8);
+    out.extend(keys.take(255));
+}
+
+/// Append a record of the fields of `new` that differ from `old`
+fn encode_change(out: &mut Vec<u8>, delta: u64, old: &InputState, new: &InputState) {
+    let code = |key: Key| MiniFBKey::from_minifb(key) as u8;
+    let mut flags = 0;
+    let mut fields = Vec::new();
+    if new.down != old.down {
+        flags |= FIELD_DOWN;
+        write_keys(&mut fields, new.down.iter().map(|&k| code(k)));
+    }
+    if new.pressed != old.pressed {
+        flags |= FIELD_PRESSED;
+        let pressed = new.pressed.iter();
+        write_keys(
+            &mut fields,
+            pressed.map(|&(k, repeat)| code(k) | (repeat as u8) << 7),
+        );
+    }
+    if new.released != old.released {
+        flags |= FIELD_RELEASED;
+        write_keys(&mut fields, new.released.iter().map(|&k| code(k)));
+    }
+    if new.mouse != old.mouse {
+        flags |= FIELD_MOUSE;
+        match new.mouse {
+            Some((x, y)) => {
+                fields.push(1);
+                fields.extend(x.to_le_bytes());
+                fields.extend(y.to_le_bytes());
+            }
+            None => fields.push(0),
+        }
+    }
+    if new.buttons != old.buttons {
+        flags |= FIELD_BUTTONS;
+        let mask = new.buttons.iter().enumerate().map(|(i, &b)| (b as u8) << i);
+        fields.push(mask.sum());
+    }
+    if new.scroll != old.scroll {
+        flags |= FIELD_SCROLL;
+        fields.extend(new.scroll.0.to_le_bytes());
+        fields.extend(new.scroll.1.to_le_bytes());
+    }
+    if flags != 0 {
+        write_varint(out, delta);
+        out.push(flags);
+        out.extend(fields);
+    }
+}
+
+/// Bounds-checked reader over a recording
+struct Cursor<'a> {
+    data: &'a [u8],
+}
+
+impl Cursor<'_> {
+    fn bytes(&mut self, n: usize) -> Result<&[u8], String> {
+        if self.data.len() < n {
+            return Err("recording is truncated".into());
+        }
+        let (head, tail) = self.data.split_at(n);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn f32(&mut self) -> Result<f32, String> {
+        let b = self.bytes(4)?;
+        Ok(f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut v = 0;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            v |= ((b & 0x7F) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err("invalid frame number in recording".into())
+    }
+
+    fn keys(&mut self) -> Result<Vec<(Key, bool)>, String> {
+        let count = self.byte()? as usize;
+        self.bytes(count)?
+            .iter()
+            .map(|&b| {
+                let key =
+                    MiniFBKey::from_code((b & 0x7F) as i32).ok_or("invalid key in recording")?;
+                Ok((key.to_minifb(), b & 0x80 != 0))
+            })
+            .collect()
+    }
+}
+
+/// Frame number and new state of each change, ending with the end marker
+fn decode(data: &[u8]) -> Result<Vec<(u64, InputState)>, String> {
+    if data.len() < MAGIC.len() + 1 || !data.starts_with(MAGIC) {
+        return Err("not an input recording".into());
+    }
+    if data[MAGIC.len()] != VERSION {
+        return Err(format!(
+            "unsupported recording version {}",
+            data[MAGIC.len()]
+        ));
+    }
+    let mut cursor = Cursor {
+        data: &data[MAGIC.len() + 1..],
+    };
+    let mut frames = Vec::new();
+    let mut frame = 0u64;
+    let mut state = InputState::default();
+    while !cursor.data.is_empty() {
+        frame = frame.saturating_add(cursor.varint()?);
+        let flags = cursor.byte()?;
+        let plain = |keys: Vec<(Key, bool)>| keys.into_iter().map(|(k, _)| k).collect();
+        if flags & FIELD_DOWN != 0 {
+            state.down = plain(cursor.keys()?);
+        }
+        if flags & FIELD_PRESSED != 0 {
+            state.pressed = cursor.keys()?;
+        }
+        if flags & FIELD_RELEASED != 0 {
+            state.released = plain(cursor.keys()?);
+        }
+        if flags & FIELD_MOUSE != 0 {
+            state.mouse = match cursor.byte()? {
+                0 => None,
+                _ => Some((cursor.f32()?, cursor.f32()?)),
+            };
+        }
+        if flags & FIELD_BUTTONS != 0 {
+            let mask = cursor.byte()?;
+            state.buttons = [mask & 1 != 0, mask & 2 != 0, mask & 4 != 0];
+        }
+        if flags & FIELD_SCROLL != 0 {
+            state.scroll = (cursor.f32()?, cursor.f32()?);
+        }
+        frames.push((frame, state.clone()));
+        if flags == 0 {
+            return Ok(frames);
+        }
+    }
+    // Recordings cut short (the program crashed) replay up to where they stop
+    frames.push((frame + 1, state));
+    Ok(frames)
+}
+
+pub(crate) struct Recorder {
+    file: BufWriter<File>,
+    frame: u64,
+    last_frame: u64,
+    last: InputState,
+    buffer: Vec<u8>,
+}
+
+impl Recorder {
+    fn create(path: &str) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(MAGIC)?;
+        file.write_all(&[VERSION])?;
+        Ok(Recorder {
+            file,
+            frame: 0,
+            last_frame: 0,
+            last: InputState::default(),
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Write the changes in one frame's input
+    pub(crate) fn record(&mut self, state: InputState) -> io::Result<()> {
+        self.buffer.clear();
+        encode_change(
+            &mut self.buffer,
+            self.frame - self.last_frame,
+            &self.last,
+            &state,
+        );
+        if !self.buffer.is_empty() {
+            self.file.write_all(&self.buffer)?;
+            self.last_frame = self.frame;
+            self.last = state;
+        }
+        self.frame += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        self.buffer.clear();
+        write_varint(&mut self.buffer, self.frame - self.last_frame);
+        self.buffer.push(0);
+        self.file.write_all(&self.buffer)?;
+        self.file.flush()
+    }
+}
+
+pub(crate) struct Replay {
+    changes: Vec<(u64, InputState)>,
+    next: usize,
+    frame: u64,
+    /// Input of the current frame
+    pub(crate) state: InputState,
+}
+
+impl Replay {
+    fn new(changes: Vec<(u64, InputState)>) -> Self {
+        Replay {
+            changes,
+            next: 0,
+            frame: 0,
+            state: InputState::default(),
+        }
+    }
+
+    /// Move to the next frame's input; false once the recording has ended
+    pub(crate) fn advance(&mut self) -> bool {
+        while let Some((frame, state)) = self.changes.get(self.next) {
+            if *frame > self.frame {
+                break;
+            }
+            if self.next + 1 == self.changes.len() {
+                return false;
+            }
+            self.state = state.clone();
+            self.next += 1;
+        }
+        self.frame += 1;
+        true
+    }
+}
+
+unsafe fn path_arg<'a>(path: *const c_char) -> Option<&'a str> {
+    if path.is_null() {
+        return None;
+    }
+    match CStr::from_ptr(path).to_str() {
+        Ok(p) => Some(p),
+        Err(_) => {
+            set_last_error("path is not valid UTF-8");
+            None
+        }
+    }
+}
+
+/// Start recording a window's input to a file
+///
+/// Frame 0 is the first update after this call. A recording already in
+/// progress is finished first.
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by one of the window constructors
+/// - `path` must be a valid null-terminated C string
+///
+/// Returns 0 on success, -1 on error (see `minifb_get_last_error`)
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_record_start(
+    window: *mut MiniFBWindow,
+    path: *const c_char,
+) -> i32 {
+    if window.is_null() {
+        return -1;
+    }
+    let Some(path) = path_arg(path) else {
+        return -1;
+    };
+    if minifb_window_record_stop(window) != 0 {
+        return -1;
+    }
+    match Recorder::create(path) {
+        Ok(recorder) => {
+            (*window).window.recorder = Some(recorder);
+            0
+        }
+        Err(e) => {
+            set_last_error(&format!("{}: {}", path, e));
+            -1
+        }
+    }
+}
+
+/// Finish the current recording, if any, and close its file
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by one of the window constructors
+///
+/// Returns 0 on success, -1 if writing the recording failed at any point
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_record_stop(window: *mut MiniFBWindow) -> i32 {
+    if window.is_null() {
+        return -1;
+    }
+    let backend = &mut (*window).window;
+    let failed = std::mem::take(&mut backend.record_failed);
+    match backend.recorder.take().map(Recorder::finish) {
+        Some(Err(e)) => {
+            set_last_error(&format!("recording: {}", e));
+            -1
+        }
+        _ if failed => {
+            set_last_error("recording: write failed");
+            -1
+        }
+        _ => 0,
+    }
+}
+
+/// Replay a recording in place of live input
+///
+/// Starting with the next update, the key, mouse and scroll queries report
+/// the recorded input frame by frame and live input is ignored. When the
+/// recording ends, live input takes over again.
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by one of the window constructors
+/// - `path` must be a valid null-terminated C string
+///
+/// Returns 0 on success, -1 on error (see `minifb_get_last_error`)
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_replay_start(
+    window: *mut MiniFBWindow,
+    path: *const c_char,
+) -> i32 {
+    if window.is_null() {
+        return -1;
+    }
+    let Some(path) = path_arg(path) else {
+        return -1;
+    };
+    let changes = std::fs::read(path)
+        .map_err(|e| e.to_string())
+        .and_then(|data| decode(&data));
+    match changes {
+        Ok(changes) => {
+            (*window).window.replay = Some(Replay::new(changes));
+            0
+        }
+        Err(e) => {
+            set_last_error(&format!("{}: {}", path, e));
+            -1
+        }
+    }
+}
+
+/// Stop replaying and return to live input
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by one of the window constructors
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_replay_stop(window: *mut MiniFBWindow) {
+    if !window.is_null() {
+        (*window).window.replay = None;
+    }
+}
+
+/// Check whether a replay is still running
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by one of the window constructors
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_is_replaying(window: *const MiniFBWindow) -> bool {
+    !window.is_null() && (*window).window.replay.is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Backend, Device};
+    use crate::input::InputEvent;
+
+    #[test]
+    fn test_record_and_replay() {
+        let path =
+            std::env::temp_dir().join(format!("minifb-ffi-record-{}.rec", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut recorded = Vec::new();
+        {
+            let mut backend = Backend::new(Device::Headless { size: (8, 8) });
+            backend.set_target_fps(0);
+            backend.recorder = Some(Recorder::create(path).unwrap());
+            let frames: [&[InputEvent]; 4] = [
+                &[InputEvent::Key(Key::A, true)],
+                &[],
+                &[
+                    InputEvent::MouseMove(Some((2.0, 5.0))),
+                    InputEvent::Scroll(0.0, 1.0),
+                ],
+                &[
+                    InputEvent::Key(Key::A, false),
+                    InputEvent::Button(MouseButton::Left, true),
+                ],
+            ];
+            for events in frames {
+                for &event in events {
+                    backend.inject(event);
+                }
+                backend.update();
+                recorded.push(observe(&backend));
+            }
+            backend.recorder.take().unwrap().finish().unwrap();
+        }
+        let data = std::fs::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        // Only changes are stored
+        assert!(data.len() < 64, "{} bytes", data.len());
+
+        let mut backend = Backend::new(Device::Headless { size: (8, 8) });
+        backend.set_target_fps(0);
+        backend.replay = Some(Replay::new(decode(&data).unwrap()));
+        for expected in &recorded {
+            // Live input is ignored while replaying
+            backend.inject(InputEvent::Key(Key::Z, true));
+            backend.update();
+            assert_eq!(&observe(&backend), expected);
+        }
+        backend.update();
+        assert!(backend.replay.is_none());
+        assert!(backend.is_key_down(Key::Z));
+    }
+}
@@ -0,0 +1,452 @@
+//! Window loop on a dedicated render thread
+//!
+//! minifb windows can only be used from the thread that created them, so a
+//! program whose main thread is busy elsewhere (an async scheduler, a long
+//! computation) can hand the whole window to a render thread instead. The
+//! thread keeps the window responsive on its own and shows frames as they
+//! arrive; any thread talks to it through a `MiniFBRenderThread` handle.
+//!
+//! Frames go through a one-slot mailbox: pushing a frame while the previous
+//! one has not been shown yet replaces it, so a slow window never builds up
+//! latency. Input is published as snapshots after every window update, with
+//! key edges and scrolling accumulated until the next read.
+//!
+//! macOS only allows windows on the main thread, so there this works with the
+//! terminal and headless backends only.
+
+use crate::canvas::MiniFBCanvas;
+use crate::dirty::DirtyRect;
+use crate::input::all_keys;
+use crate::{set_last_error, MiniFBKey, MiniFBWindow, MiniFBWindowOptions};
+use minifb::{KeyRepeat, MouseButton, MouseMode};
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::sync::{mpsc, Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Maximum number of keys in each list of `MiniFBInputSnapshot`
+pub const MINIFB_SNAPSHOT_MAX_KEYS: usize = 16;
+
+/// Longest the render thread waits for a frame before updating the window anyway
+const IDLE_UPDATE: Duration = Duration::from_millis(16);
+
+/// Input state published by a render thread
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MiniFBInputSnapshot {
+    /// False once the window was closed, by the user or on request
+    pub open: bool,
+    pub has_mouse: bool,
+    pub mouse_x: f32,
+    pub mouse_y: f32,
+    /// Held buttons: bit 0 left, bit 1 middle, bit 2 right
+    pub mouse_buttons: u32,
+    /// Scrolling since the previous read
+    pub scroll_x: f32,
+    pub scroll_y: f32,
+    /// Held keys as MiniFBKey codes
+    pub key_count: u32,
+    pub keys: [i32; MINIFB_SNAPSHOT_MAX_KEYS],
+    /// Keys pressed since the previous read, not counting repeats
+    pub pressed_count: u32,
+    pub pressed: [i32; MINIFB_SNAPSHOT_MAX_KEYS],
+    /// Keys released since the previous read
+    pub released_count: u32,
+    pub released: [i32; MINIFB_SNAPSHOT_MAX_KEYS],
+    /// Window updates so far
+    pub frame: u64,
+    /// Frames that were replaced in the mailbox before being shown
+    pub frames_dropped: u64,
+}
+
+/// Append a key code to one of the snapshot's lists unless it is full or
+/// already there
+fn add_key(list: &mut [i32; MINIFB_SNAPSHOT_MAX_KEYS], count: &mut u32, code: i32) {
+    let n = *count as usize;
+    if n < MINIFB_SNAPSHOT_MAX_KEYS && !list[..n].contains(&code) {
+        list[n] = code;
+        *count += 1;
+    }
+}
+
+struct Frame {
+    pixels: Vec<u32>,
+    width: usize,
+    height: usize,
+}
+
+#[derive(Default)]
+struct State {
+    /// Frame waiting to be shown
+    pending: Option<Frame>,
+    /// Buffer of the last shown frame, reused for the next push
+    spare: Vec<u32>,
+    input: MiniFBInputSnapshot,
+    close_requested: bool,
+    running: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    wake: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Opaque, thread-safe handle to a render thread
+pub struct MiniFBRenderThread {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Fold the window's input after an update into the published snapshot
+fn publish_input(window: &MiniFBWindow, snapshot: &mut MiniFBInputSnapshot) {
+    let backend = &window.window;
+    let mouse = backend.get_mouse_pos(MouseMode::Pass);
+    snapshot.open = backend.is_open();
+    snapshot.has_mouse = mouse.is_some();
+    (snapshot.mouse_x, snapshot.mouse_y) = mouse.unwrap_or((0.0, 0.0));
+    snapshot.mouse_buttons = [MouseButton::Left, MouseButton::Middle, MouseButton::Right]
+        .iter()
+        .enumerate()
+        .filter(|(_, &b)| backend.get_mouse_down(b))
+        .fold(0, |mask, (i, _)| mask | 1 << i);
+    if let Some((x, y)) = backend.get_scroll_wheel() {
+        snapshot.scroll_x += x;
+        snapshot.scroll_y += y;
+    }
+    snapshot.key_count = 0;
+    for key in backend.get_keys() {
+        let code = MiniFBKey::from_minifb(key) as i32;
+        add_key(&mut snapshot.keys, &mut snapshot.key_count, code);
+    }
+    for key in all_keys() {
+        let code = MiniFBKey::from_minifb(key) as i32;
+        if backend.is_key_pressed(key, KeyRepeat::No) {
+            add_key(&mut snapshot.pressed, &mut snapshot.pressed_count, code);
+        }
+        if backend.is_key_released(key) {
+            add_key(&mut snapshot.released, &mut snapshot.released_count, code);
+        }
+    }
+    snapshot.frame += 1;
+}
+
+fn run(shared: &Shared, window: &mut MiniFBWindow) {
+    loop {
+        let frame = {
+            let mut state = shared.lock();
+            if state.pending.is_none() && !state.close_requested {
+                state = shared
+                    .wake
+                    .wait_timeout(state, IDLE_UPDATE)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0;
+            }
+            if state.close_requested {
+                return;
+            }
+            state.pending.take()
+        };
+        match frame {
+            Some(frame) => {
+                if window
+                    .present(&frame.pixels, frame.width, frame.height)
+                    .is_ok()
+                {
+                    window.record_present(None, DirtyRect::full(frame.width, frame.height));
+                }
+                shared.lock().spare = frame.pixels;
+            }
+            None => window.window.update(),
+        }
+        let mut state = shared.lock();
+        publish_input(window, &mut state.input);
+        if !state.input.open {
+            return;
+        }
+    }
+}
+
+impl MiniFBRenderThread {
+    /// Start a thread that creates its window with `open` and runs it
+    fn spawn(open: impl FnOnce() -> *mut MiniFBWindow + Send + 'static) -> Result<Self, String> {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            wake: Condvar::new(),
+        });
+        let (created_tx, created) = mpsc::channel();
+        let thread_shared = shared.clone();
+        let thread = std::thread::spawn(move || {
+            let window = open();
+            if window.is_null() {
+                let error = crate::last_error().unwrap_or_else(|| "window creation failed".into());
+                let _ = created_tx.send(Err(error));
+                return;
+            }
+            // SAFETY: freshly created by a window constructor on this thread
+            let mut window = unsafe { Box::from_raw(window) };
+            {
+                let mut state = thread_shared.lock();
+                state.running = true;
+                state.input.open = true;
+            }
+            let _ = created_tx.send(Ok(()));
+            run(&thread_shared, &mut window);
+            drop(window);
+            let mut state = thread_shared.lock();
+            state.running = false;
+            state.input.open = false;
+        });
+        match created.recv() {
+            Ok(Ok(())) => Ok(MiniFBRenderThread {
+                shared,
+                thread: Some(thread),
+            }),
+            Ok(Err(e)) => {
+                let _ = thread.join();
+                Err(e)
+            }
+            Err(_) => {
+                let _ = thread.join();
+                Err("render thread failed to start".into())
+            }
+        }
+    }
+
+    /// Put a frame in the mailbox; false if the thread has stopped
+    fn push(&self, pixels: &[u32], width: usize, height: usize) -> bool {
+        let mut state = self.shared.lock();
+        if !state.running {
+            return false;
+        }
+        let mut buffer = match state.pending.take() {
+            Some(dropped) => {
+                state.input.frames_dropped += 1;
+                dropped.pixels
+            }
+            None => std::mem::take(&mut state.spare),
+        };
+        buffer.clear();
+        buffer.extend_from_slice(pixels);
+        state.pending = Some(Frame {
+            pixels: buffer,
+            width,
+            height,
+        });
+        self.shared.wake.notify_one();
+        true
+    }
+
+    fn request_close(&self) {
+        self.shared.lock().close_requested = true;
+        self.shared.wake.notify_one();
+    }
+}
+
+impl Drop for MiniFBRenderThread {
+    fn drop(&mut self) {
+        self.request_close();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Create a window on a new render thread
+///
+/// The window is created like `minifb_window_new` (including the
+/// `MINIFB_FFI_BACKEND` override) but on the render thread, which then
+/// updates it continuously until it is closed.
+///
+/// # Safety
+/// - `name` must be a valid null-terminated C string
+/// - `opts` must be a valid pointer to MiniFBWindowOptions
+///
+/// Returns null on failure (see `minifb_get_last_error`)
+#[no_mangle]
+pub unsafe extern "C" fn minifb_render_thread_new(
+    name: *const c_char,
+    width: usize,
+    height: usize,
+    opts: *const MiniFBWindowOptions,
+) -> *mut MiniFBRenderThread {
+    if name.is_null() || opts.is_null() {
+        return std::ptr::null_mut();
+    }
+    let name = CStr::from_ptr(name).to_owned();
+    let opts = *opts;
+    let open = move || {
+        let name: CString = name;
+        // SAFETY: both pointers are valid for the duration of the call
+        unsafe { crate::minifb_window_new(name.as_ptr(), width, height, &opts) }
+    };
+    match MiniFBRenderThread::spawn(open) {
+        Ok(handle) => Box::into_raw(Box::new(handle)),
+        Err(e) => {
+            set_last_error(&e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Close the window if it is still open, wait for the render thread to end
+/// and free the handle
+///
+/// # Safety
+/// - `handle` must be a valid pointer returned by `minifb_render_thread_new`, or null
+/// - No other thread may use the handle during or after this call
+#[no_mangle]
+pub unsafe extern "C" fn minifb_render_thread_free(handle: *mut MiniFBRenderThread) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Queue a 0RGB buffer to be shown, replacing a queued frame not yet shown
+///
+/// The buffer is copied, so it can be reused as soon as this returns.
+///
+/// # Safety
+/// - `handle` must be a valid pointer returned by `minifb_render_thread_new`
+/// - `buffer` must point to at least `width * height` u32 values
+///
+/// Returns 0 on success, -1 on invalid dimensions (see `minifb_get_last_error`)
+/// or if the window has been closed
+#[no_mangle]
+pub unsafe extern "C" fn minifb_render_thread_push(
+    handle: *const MiniFBRenderThread,
+    buffer: *const u32,
+    width: usize,
+    height: usize,
+) -> i32 {
+    if handle.is_null() || buffer.is_null() {
+        return -1;
+    }
+    let count = match width.checked_mul(height) {
+        Some(count) if count > 0 => count,
+        _ => {
+            set_last_error("buffer dimensions are zero or too large");
+            return -1;
+        }
+    };
+    let pixels = std::slice::from_raw_parts(buffer, count);
+    if (*handle).push(pixels, width, height) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Queue a copy of a canvas to be shown, replacing a queued frame not yet shown
+///
+/// # Safety
+/// - `handle` must be a valid pointer returned by `minifb_render_thread_new`
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+///
+/// Returns 0 on success, -1 if the window has been closed
+#[no_mangle]
+pub unsafe extern "C" fn minifb_render_thread_push_canvas(
+    handle: *const MiniFBRenderThread,
+    canvas: *const MiniFBCanvas,
+) -> i32 {
+    if handle.is_null() || canvas.is_null() {
+        return -1;
+    }
+    let canvas = &*canvas;
+    if (*handle).push(&canvas.pixels, canvas.width, canvas.height) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Read the latest input snapshot
+///
+/// Key edges and scrolling are accumulated from every window update since
+/// the previous read and reset by this call.
+///
+/// # Safety
+/// - `handle` must be a valid pointer returned by `minifb_render_thread_new`
+/// - `out_snapshot` must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn minifb_render_thread_get_input(
+    handle: *const MiniFBRenderThread,
+    out_snapshot: *mut MiniFBInputSnapshot,
+) {
+    if handle.is_null() || out_snapshot.is_null() {
+        return;
+    }
+    let mut state = (*handle).shared.lock();
+    *out_snapshot = state.input;
+    let input = &mut state.input;
+    (input.scroll_x, input.scroll_y) = (0.0, 0.0);
+    input.pressed_count = 0;
+    input.released_count = 0;
+}
+
+/// Check whether the render thread's window is still open
+///
+/// # Safety
+/// - `handle` must be a valid pointer returned by `minifb_render_thread_new`
+#[no_mangle]
+pub unsafe extern "C" fn minifb_render_thread_is_open(handle: *const MiniFBRenderThread) -> bool {
+    !handle.is_null() && (*handle).shared.lock().input.open
+}
+
+/// Ask the render thread to close its window and stop
+///
+/// Returns immediately; the window is closed at the thread's next iteration.
+///
+/// # Safety
+/// - `handle` must be a valid pointer returned by `minifb_render_thread_new`
+#[no_mangle]
+pub unsafe extern "C" fn minifb_render_thread_request_close(handle: *const MiniFBRenderThread) {
+    if !handle.is_null() {
+        (*handle).request_close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::InputEvent;
+    use minifb::Key;
+
+    #[test]
+    fn test_frames_input_and_close() {
+        let handle = MiniFBRenderThread::spawn(|| {
+            let window = crate::backend::minifb_window_new_headless(4, 4);
+            unsafe { (*window).window.inject(InputEvent::Key(Key::E, true)) };
+            window
+        })
+        .unwrap();
+        assert!(handle.push(&[0x00FF00; 16], 4, 4));
+        let pixels = [0u32; 16];
+        for (width, height) in [(usize::MAX, 2), (0, 4)] {
+            let result =
+                unsafe { minifb_render_thread_push(&handle, pixels.as_ptr(), width, height) };
+            assert_eq!(result, -1);
+        }
+        let mut snapshot = MiniFBInputSnapshot::default();
+        while snapshot.pressed_count == 0 {
+            unsafe { minifb_render_thread_get_input(&handle, &mut snapshot) };
+        }
+        assert_eq!(snapshot.pressed[0], MiniFBKey::E as i32);
+        assert!(snapshot.open && snapshot.key_count == 1);
+        unsafe { minifb_render_thread_get_input(&handle, &mut snapshot) };
+        assert_eq!(snapshot.pressed_count, 0);
+        assert_eq!(snapshot.key_count, 1);
+
+        handle.request_close();
+        while unsafe { minifb_render_thread_is_open(&handle) } {
+            std::thread::yield_now();
+        }
+        assert!(!handle.push(&[0; 16], 4, 4));
+    }
+}
@@ -0,0 +1,396 @@
+//! Callback-driven main loop
+//!
+//! `minifb_run` owns the usual `while open { step; draw; present }` loop.
+//! Game logic goes in an update callback called at a fixed rate, so it
+//! behaves the same at any frame rate; drawing goes in a render callback
+//! called once per frame, at most `max_fps` times a second. When updates
+//! fall behind (a slow machine, a long render) the loop runs several per
+//! frame to catch up, up to `max_updates_per_frame`, and drops the time it
+//! can't make up rather than spiralling.
+
+use crate::{set_last_error, MiniFBWindow};
+use std::os::raw::c_void;
+use std::time::{Duration, Instant};
+
+/// How long before a frame deadline the loop stops sleeping and yields
+/// instead, since sleeps tend to overshoot
+const SPIN: Duration = Duration::from_millis(1);
+
+/// Run loop settings
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MiniFBRunConfig {
+    /// Fixed update callbacks per second
+    pub update_rate: f64,
+    /// Most render callbacks per second, 0 for no limit
+    pub max_fps: f64,
+    /// Most update callbacks between two renders, at least 1; further
+    /// updates are skipped
+    pub max_updates_per_frame: u32,
+}
+
+/// Create a default run config: 60 updates and at most 60 frames a second,
+/// catching up by up to 5 updates per frame
+#[no_mangle]
+pub extern "C" fn minifb_run_config_default() -> MiniFBRunConfig {
+    MiniFBRunConfig {
+        update_rate: 60.0,
+        max_fps: 60.0,
+        max_updates_per_frame: 5,
+    }
+}
+
+/// Timing passed to run loop callbacks
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MiniFBFrameTiming {
+    /// Seconds to advance by: the fixed step in updates, the time since the
+    /// previous frame in renders
+    pub dt: f64,
+    /// Seconds since the loop started: simulated time after this step in
+    /// updates, wall time in renders
+    pub time: f64,
+    /// Update callbacks so far, counting the current one
+    pub update_count: u64,
+    /// Frames so far, counting the current one
+    pub frame_count: u64,
+    /// In renders, how far into the next update step the simulation is
+    /// (0 to 1), for interpolating between the last two states
+    pub alpha: f64,
+    /// Updates skipped so far because the loop fell too far behind
+    pub skipped_updates: u64,
+}
+
+/// Fixed-rate update callback; return false to stop the loop
+pub type MiniFBUpdateCallback = Option<
+    unsafe extern "C" fn(
+        window: *mut MiniFBWindow,
+        timing: *const MiniFBFrameTiming,
+        user_data: *mut c_void,
+    ) -> bool,
+>;
+
+/// Per-frame render callback; return false to stop the loop
+///
+/// It normally presents a frame (`minifb_window_update_with_buffer` and
+/// friends); if it doesn't, the loop updates the window itself.
+pub type MiniFBRenderCallback = Option<
+    unsafe extern "C" fn(
+        window: *mut MiniFBWindow,
+        timing: *const MiniFBFrameTiming,
+        user_data: *mut c_void,
+    ) -> bool,
+>;
+
+/// Sleep until `deadline`, finishing the last stretch by yielding
+fn sleep_until(deadline: Instant) {
+    let now = Instant::now();
+    if deadline > now + SPIN {
+        std::thread::sleep(deadline - now - SPIN);
+    }
+    while Instant::now() < deadline {
+        std::thread::yield_now();
+    }
+}
+
+/// Run the window's main loop until it is closed
+///
+/// Every frame calls `update` as many times as the fixed update rate asks
+/// for (none on the first frame), then `render` once, then waits for the
+/// next frame. Both callbacks see the input of the last window update, but
+/// its key presses, releases and scrolling only reach the first update of
+/// the frame: later catch-up updates and the render after them see held
+/// keys and buttons only, so one press isn't handled several times. The
+/// window's target FPS is ignored while the loop runs, as the loop does its
+/// own pacing.
+///
+/// The loop ends when the window closes (including through
+/// `minifb_window_proxy_request_close`) or a callback returns false.
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by `minifb_window_new`
+/// - `config` must be a valid pointer to MiniFBRunConfig
+/// - The callbacks may be null; `user_data` is passed to them untouched
+///
+/// Returns 0 once the loop ended, -1 on invalid arguments (see
+/// `minifb_get_last_error`)
+#[no_mangle]
+pub unsafe extern "C" fn minifb_run(
+    window: *mut MiniFBWindow,
+    update: MiniFBUpdateCallback,
+    render: MiniFBRenderCallback,
+    user_data: *mut c_void,
+    config: *const MiniFBRunConfig,
+) -> i32 {
+    if window.is_null() || config.is_null() {
+        return -1;
+    }
+    let config = *config;
+    let step = match update {
+        Some(_) => match Duration::try_from_secs_f64(1.0 / config.update_rate) {
+            Ok(step) if !step.is_zero() => step,
+            _ => {
+                set_last_error("update_rate must be a positive, representable rate");
+                return -1;
+            }
+        },
+        None => Duration::ZERO,
+    };
+    if update.is_some() && config.max_updates_per_frame == 0 {
+        set_last_error("max_updates_per_frame must be at least 1");
+        return -1;
+    }
+    let frame_time = if config.max_fps == 0.0 {
+        None
+    } else {
+        match Duration::try_from_secs_f64(1.0 / config.max_fps) {
+            Ok(frame_time) => Some(frame_time),
+            Err(_) => {
+                set_last_error("max_fps must be 0 or a positive, representable rate");
+                return -1;
+            }
+        }
+    };
+
+    let target_fps = (*window).window.target_fps;
+    (*window).window.set_target_fps(0);
+    let start = Instant::now();
+    let mut last = start;
+    let mut lag = Duration::ZERO;
+    let mut timing = MiniFBFrameTiming::default();
+    'frames: while (*window).window.is_open() {
+        let frame_start = Instant::now();
+        let elapsed = frame_start - last;
+        last = frame_start;
+
+        if let Some(update) = update {
+            lag += elapsed;
+            let mut run = 0;
+            while lag >= step {
+                if run == config.max_updates_per_frame {
+                    let (lag_ns, step_ns) = (lag.as_nanos(), step.as_nanos());
+                    timing.skipped_updates += (lag_ns / step_ns) as u64;
+                    lag = Duration::from_nanos((lag_ns % step_ns) as u64);
+                    break;
+                }
+                timing.update_count += 1;
+                let update_timing = MiniFBFrameTiming {
+                    dt: step.as_secs_f64(),
+                    time: timing.update_count as f64 * step.as_secs_f64(),
+                    alpha: 0.0,
+                    ..timing
+                };
+                lag -= step;
+                run += 1;
+                if !update(window, &update_timing, user_data) {
+                    break 'frames;
+                }
+                if run == 1 {
+                    (*window).window.take_edges();
+                }
+                if !(*window).window.is_open() {
+                    break 'frames;
+                }
+            }
+        }
+
+        timing.frame_count += 1;
+        let updates = (*window).window.updates;
+        if let Some(render) = render {
+            let render_timing = MiniFBFrameTiming {
+                dt: elapsed.as_secs_f64(),
+                time: (frame_start - start).as_secs_f64(),
+                alpha: match update {
+                    Some(_) => lag.as_secs_f64() / step.as_secs_f64(),
+                    None => 0.0,
+                },
+                ..timing
+            };
+            if !render(window, &render_timing, user_data) {
+                break;
+            }
+        }
+        if (*window).window.updates == updates {
+            (*window).window.update();
+        }
+
+        if let Some(frame_time) = frame_time {
+            sleep_until(frame_start + frame_time);
+        }
+    }
+    (*window).window.set_target_fps(target_fps);
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Counts {
+        updates: Vec<MiniFBFrameTiming>,
+        renders: Vec<MiniFBFrameTiming>,
+    }
+
+    unsafe extern "C" fn count_update(
+        _window: *mut MiniFBWindow,
+        timing: *const MiniFBFrameTiming,
+        user_data: *mut c_void,
+    ) -> bool {
+        (*user_data.cast::<Counts>()).updates.push(*timing);
+        true
+    }
+
+    unsafe extern "C" fn present(
+        window: *mut MiniFBWindow,
+        timing: *const MiniFBFrameTiming,
+        user_data: *mut c_void,
+    ) -> bool {
+        let counts = &mut *user_data.cast::<Counts>();
+        counts.renders.push(*timing);
+        crate::minifb_window_update_with_buffer(window, [0u32; 16].as_ptr(), 4, 4);
+        counts.renders.len() < 10
+    }
+
+    #[test]
+    fn test_fixed_updates_and_paced_renders() {
+        let window = crate::backend::minifb_window_new_headless(4, 4);
+        let mut counts = Counts::default();
+        let config = MiniFBRunConfig {
+            update_rate: 1000.0,
+            max_fps: 200.0,
+            max_updates_per_frame: 3,
+        };
+        let started = Instant::now();
+        let result = unsafe {
+            minifb_run(
+                window,
+                Some(count_update),
+                Some(present),
+                (&mut counts as *mut Counts).cast(),
+                &config,
+            )
+        };
+        assert_eq!(result, 0);
+        // Ten frames paced at 5 ms each
+        assert!(started.elapsed() >= Duration::from_millis(45));
+        assert_eq!(counts.renders.len(), 10);
+        assert!(counts.renders.windows(2).all(|w| w[1].dt >= 0.004));
+        assert!(counts.renders.iter().all(|r| (0.0..1.0).contains(&r.alpha)));
+
+        // At most 3 updates per 5 ms frame, the rest skipped
+        let last = counts.renders[9];
+        assert_eq!(counts.updates.len() as u64, last.update_count);
+        assert!(last.update_count <= 27);
+        assert!(last.skipped_updates > 0);
+        for (i, update) in counts.updates.iter().enumerate() {
+            assert_eq!(update.update_count, i as u64 + 1);
+            assert_eq!(update.dt, 0.001);
+        }
+        unsafe { crate::minifb_window_free(window) };
+    }
+
+    #[test]
+    fn test_invalid_config_rejected() {
+        let window = crate::backend::minifb_window_new_headless(4, 4);
+        let defaults = minifb_run_config_default();
+        let bad = [
+            MiniFBRunConfig {
+                update_rate: 1e-20,
+                ..defaults
+            },
+            MiniFBRunConfig {
+                update_rate: 0.0,
+                ..defaults
+            },
+            MiniFBRunConfig {
+                update_rate: f64::MAX,
+                ..defaults
+            },
+            MiniFBRunConfig {
+                max_fps: 1e-20,
+                ..defaults
+            },
+            MiniFBRunConfig {
+                max_fps: -1.0,
+                ..defaults
+            },
+            MiniFBRunConfig {
+                max_updates_per_frame: 0,
+                ..defaults
+            },
+        ];
+        let mut counts = Counts::default();
+        for config in bad {
+            let result = unsafe {
+                minifb_run(
+                    window,
+                    Some(count_update),
+                    Some(present),
+                    (&mut counts as *mut Counts).cast(),
+                    &config,
+                )
+            };
+            assert_eq!(result, -1, "{:?}", config);
+        }
+        assert!(counts.updates.is_empty() && counts.renders.is_empty());
+        unsafe { crate::minifb_window_free(window) };
+    }
+
+    /// Frame count, is_key_pressed and is_key_down of Space, per update
+    type KeyLog = Vec<(u64, bool, bool)>;
+
+    unsafe extern "C" fn log_space(
+        window: *mut MiniFBWindow,
+        timing: *const MiniFBFrameTiming,
+        user_data: *mut c_void,
+    ) -> bool {
+        let key = crate::MiniFBKey::Space;
+        (*user_data.cast::<KeyLog>()).push((
+            (*timing).frame_count,
+            crate::minifb_window_is_key_pressed(window, key, false),
+            crate::minifb_window_is_key_down(window, key),
+        ));
+        true
+    }
+
+    unsafe extern "C" fn press_space(
+        window: *mut MiniFBWindow,
+        timing: *const MiniFBFrameTiming,
+        _user_data: *mut c_void,
+    ) -> bool {
+        if (*timing).frame_count == 2 {
+            crate::minifb_window_inject_key(window, crate::MiniFBKey::Space, true);
+        }
+        crate::minifb_window_update_with_buffer(window, [0u32; 16].as_ptr(), 4, 4);
+        (*timing).frame_count < 5
+    }
+
+    #[test]
+    fn test_key_presses_reach_first_update_only() {
+        let window = crate::backend::minifb_window_new_headless(4, 4);
+        let mut log = KeyLog::new();
+        let config = MiniFBRunConfig {
+            update_rate: 1000.0,
+            max_fps: 100.0,
+            max_updates_per_frame: 5,
+        };
+        let result = unsafe {
+            minifb_run(
+                window,
+                Some(log_space),
+                Some(press_space),
+                (&mut log as *mut KeyLog).cast(),
+                &config,
+            )
+        };
+        assert_eq!(result, 0);
+        // Frame 3 catches up on 10 ms of updates after the press
+        let frame: Vec<_> = log.iter().filter(|u| u.0 == 2).collect();
+        assert!(frame.len() > 1, "{:?}", log);
+        assert_eq!(*frame[0], (2, true, true));
+        assert!(frame[1..].iter().all(|&&u| u == (2, false, true)));
+        assert!(log.iter().filter(|u| u.0 != 2).all(|u| !u.1));
+        unsafe { crate::minifb_window_free(window) };
+    }
+}
@@ -0,0 +1,577 @@
+//! Line-oriented JSON protocol for driving a window from scripts
+//!
+//! Each input line is one command object with a `"cmd"` field; each command
+//! is answered by one reply line, `{"ok":true,...}` or
+//! `{"ok":false,"error":"..."}`, carrying the command's `"id"` if it had one.
+//! Commands that update the window first write an event line for every input
+//! change seen during the update, so a script that waits for the reply has
+//! all of that frame's events.
+//!
+//! | Command | Arguments |
+//! |---|---|
+//! | `create` | `width`, `height`, `title`, `headless` |
+//! | `clear` | `color` |
+//! | `rect` | `x`, `y`, `w`, `h`, `color`, `opacity` |
+//! | `line` | `x0`, `y0`, `x1`, `y1`, `color` |
+//! | `polygon` | `points` (`[[x, y], ...]`), `color` |
+//! | `text` | `x`, `y`, `text`, `color`, `scale` |
+//! | `image` | `path`, `x`, `y`, `w`, `h` |
+//! | `present` | draw the canvas and update the window |
+//! | `update` | update the window without drawing |
+//! | `key` | `key` (minifb name such as `"A"` or `"Escape"`), `down` |
+//! | `mouse` | `x`, `y` |
+//! | `button` | `button` (`"left"`, `"middle"` or `"right"`), `down` |
+//! | `scroll` | `dx`, `dy` |
+//! | `input` | replies with `open`, `keys`, `mouse` and `buttons` |
+//! | `screenshot` | `path`: saves the last presented frame as PNG |
+//! | `quit` | |
+//!
+//! Colors are 0xRRGGBB numbers or `"#rrggbb"` strings. Injected input takes
+//! effect at the next `present` or `update`; without `down`, `key` presses
+//! and releases within that frame and `button` holds the button for it. Events are
+//! `{"event":"key","key":"A","down":true}`, `{"event":"mouse","x":..,"y":..}`,
+//! `{"event":"button","button":"left","down":true}`,
+//! `{"event":"scroll","dx":..,"dy":..}` and `{"event":"closed"}`.
+
+use crate::camera::MiniFBTransform;
+use crate::canvas::{minifb_window_update_with_canvas, MiniFBCanvas};
+use crate::image::{minifb_blit_options_default, MiniFBImage};
+use crate::input::{all_keys, InputEvent};
+use crate::text::{minifb_text_style_default, MiniFBFont};
+use crate::{last_error, MiniFBWindow};
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode};
+use serde_json::{json, Map, Value};
+use std::ffi::CString;
+use std::io::{self, BufRead, Write};
+
+/// Largest window width or height a script can create
+const MAX_SIZE: u64 = 16384;
+
+/// Largest coordinate or extent, in either direction, a drawing command accepts
+const MAX_COORD: f64 = 1e9;
+
+/// Largest text scale a script can ask for
+const MAX_TEXT_SCALE: f64 = 256.0;
+
+const BUTTONS: [(&str, MouseButton); 3] = [
+    ("left", MouseButton::Left),
+    ("middle", MouseButton::Middle),
+    ("right", MouseButton::Right),
+];
+
+fn key_name(key: Key) -> String {
+    format!("{:?}", key)
+}
+
+fn key_by_name(name: &str) -> Option<Key> {
+    all_keys().find(|&key| key_name(key) == name)
+}
+
+fn number(cmd: &Value, name: &str) -> Result<f64, String> {
+    cmd.get(name)
+        .and_then(Value::as_f64)
+        .ok_or_else(|| format!("\"{}\" must be a number", name))
+}
+
+/// A window dimension: an integer from 1 to `MAX_SIZE`
+fn size(cmd: &Value, name: &str) -> Result<usize, String> {
+    cmd.get(name)
+        .and_then(Value::as_u64)
+        .filter(|n| (1..=MAX_SIZE).contains(n))
+        .map(|n| n as usize)
+        .ok_or_else(|| format!("\"{}\" must be an integer from 1 to {}", name, MAX_SIZE))
+}
+
+fn number_or(cmd: &Value, name: &str, default: f64) -> Result<f64, String> {
+    match cmd.get(name) {
+        None => Ok(default),
+        Some(_) => number(cmd, name),
+    }
+}
+
+/// A canvas position or extent: a number from -`MAX_COORD` to `MAX_COORD`
+fn coord(cmd: &Value, name: &str) -> Result<f64, String> {
+    cmd.get(name)
+        .and_then(Value::as_f64)
+        .filter(|v| v.abs() <= MAX_COORD)
+        .ok_or_else(|| {
+            format!(
+                "\"{}\" must be a number from -{} to {}",
+                name, MAX_COORD, MAX_COORD
+            )
+        })
+}
+
+fn coord_or(cmd: &Value, name: &str, default: f64) -> Result<f64, String> {
+    match cmd.get(name) {
+        None => Ok(default),
+        Some(_) => coord(cmd, name),
+    }
+}
+
+fn string<'a>(cmd: &'a Value, name: &str) -> Result<&'a str, String> {
+    cmd.get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("\"{}\" must be a string", name))
+}
+
+fn color(cmd: &Value, default: u32) -> Result<u32, String> {
+    match cmd.get("color") {
+        None => Ok(default),
+        Some(Value::Number(n)) => n
+            .as_u64()
+            .filter(|&c| c <= 0xFFFFFF)
+            .map(|c| c as u32)
+            .ok_or_else(|| "\"color\" out of range".to_string()),
+        Some(Value::String(s)) => s
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6)
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| format!("invalid color \"{}\"", s)),
+        Some(_) => Err("\"color\" must be a number or \"#rrggbb\"".to_string()),
+    }
+}
+
+/// Optional `down` flag: None means press and release
+fn down(cmd: &Value) -> Result<Option<bool>, String> {
+    match cmd.get("down") {
+        None => Ok(None),
+        Some(v) => v
+            .as_bool()
+            .map(Some)
+            .ok_or_else(|| "\"down\" must be a boolean".to_string()),
+    }
+}
+
+#[derive(Default)]
+struct Session {
+    window: Option<Box<MiniFBWindow>>,
+    canvas: Option<MiniFBCanvas>,
+    /// Copy of the last presented canvas, for screenshots
+    screen: Option<MiniFBImage>,
+    frames: u64,
+    open: bool,
+    mouse: Option<(f32, f32)>,
+    buttons: [bool; 3],
+    /// Button releases to inject once the next update has seen the press
+    releases: Vec<InputEvent>,
+}
+
+impl Session {
+    fn window(&mut self) -> Result<&mut MiniFBWindow, String> {
+        self.window
+            .as_deref_mut()
+            .ok_or_else(|| "no window; send \"create\" first".to_string())
+    }
+
+    fn canvas(&mut self) -> Result<&mut MiniFBCanvas, String> {
+        self.canvas
+            .as_mut()
+            .ok_or_else(|| "no window; send \"create\" first".to_string())
+    }
+
+    fn create(&mut self, cmd: &Value) -> Result<(), String> {
+        let width = size(cmd, "width")?;
+        let height = size(cmd, "height")?;
+        let title = cmd.get("title").and_then(Value::as_str).unwrap_or("minifb");
+        let headless = cmd
+            .get("headless")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let window = if headless {
+            crate::backend::minifb_window_new_headless(width, height)
+        } else {
+            let title = CString::new(title).map_err(|_| "title contains a null byte")?;
+            // SAFETY: both pointers are valid for the duration of the call
+            unsafe {
+                crate::minifb_window_new(
+                    title.as_ptr(),
+                    width,
+                    height,
+                    &crate::minifb_window_options_default(),
+                )
+            }
+        };
+        if window.is_null() {
+            return Err(last_error().unwrap_or_else(|| "window creation failed".into()));
+        }
+        // SAFETY: freshly created by a window constructor
+        self.window = Some(unsafe { Box::from_raw(window) });
+        self.canvas = Some(MiniFBCanvas::new(width, height));
+        self.screen = None;
+        self.frames = 0;
+        self.open = true;
+        self.mouse = None;
+        self.buttons = [false; 3];
+        self.releases.clear();
+        Ok(())
+    }
+
+    /// Report what changed in the window's input during the last update
+    fn collect_events(&mut self, events: &mut Vec<Value>) {
+        let Some(window) = self.window.as_deref_mut() else {
+            return;
+        };
+        for event in self.releases.drain(..) {
+            window.window.inject(event);
+        }
+        let backend = &window.window;
+        for key in all_keys() {
+            if backend.is_key_pressed(key, KeyRepeat::No) {
+                events.push(json!({"event": "key", "key": key_name(key), "down": true}));
+            }
+            if backend.is_key_released(key) {
+                events.push(json!({"event": "key", "key": key_name(key), "down": false}));
+            }
+        }
+        let mouse = backend.get_mouse_pos(MouseMode::Pass);
+        if mouse != self.mouse {
+            if let Some((x, y)) = mouse {
+                events.push(json!({"event": "mouse", "x": x, "y": y}));
+            }
+            self.mouse = mouse;
+        }
+        for (i, (name, button)) in BUTTONS.iter().enumerate() {
+            let down = backend.get_mouse_down(*button);
+            if down != self.buttons[i] {
+                events.push(json!({"event": "button", "button": name, "down": down}));
+                self.buttons[i] = down;
+            }
+        }
+        if let Some((dx, dy)) = backend.get_scroll_wheel() {
+            events.push(json!({"event": "scroll", "dx": dx, "dy": dy}));
+        }
+        if self.open && !backend.is_open() {
+            events.push(json!({"event": "closed"}));
+            self.open = false;
+        }
+    }
+
+    /// Run one command, returning the reply's fields besides `ok`
+    fn execute(
+        &mut self,
+        cmd: &Value,
+        events: &mut Vec<Value>,
+    ) -> Result<Map<String, Value>, String> {
+        let mut reply = Map::new();
+        match string(cmd, "cmd")? {
+            "create" => self.create(cmd)?,
+            "clear" => {
+                let color = color(cmd, 0)?;
+                let canvas = self.canvas()?;
+                canvas.pixels.fill(color);
+                canvas.mark_all_dirty();
+            }
+            "rect" => {
+                let (x, y) = (coord(cmd, "x")?, coord(cmd, "y")?);
+                let (w, h) = (coord(cmd, "w")?, coord(cmd, "h")?);
+                let color = color(cmd, 0xFFFFFF)?;
+                let alpha = (number_or(cmd, "opacity", 1.0)?.clamp(0.0, 1.0) * 255.0).round() as u8;
+                self.canvas()?
+                    .fill_rect(x as i64, y as i64, w as i64, h as i64, color, alpha);
+            }
+            "line" => {
+                let a = (coord(cmd, "x0")?, coord(cmd, "y0")?);
+                let b = (coord(cmd, "x1")?, coord(cmd, "y1")?);
+                let color = color(cmd, 0xFFFFFF)?;
+                self.canvas()?.line_f(a, b, color);
+            }
+            "polygon" => {
+                let points = cmd
+                    .get("points")
+                    .and_then(Value::as_array)
+                    .ok_or("\"points\" must be an array")?
+                    .iter()
+                    .map(|p| match p.as_array().map(Vec::as_slice) {
+                        Some([x, y]) => x.as_f64().zip(y.as_f64()),
+                        _ => None,
+                    })
+                    .map(|p| p.filter(|(x, y)| x.abs() <= MAX_COORD && y.abs() <= MAX_COORD))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| {
+                        format!(
+                            "\"points\" must hold [x, y] pairs from -{} to {}",
+                            MAX_COORD, MAX_COORD
+                        )
+                    })?;
+                let color = color(cmd, 0xFFFFFF)?;
+                self.canvas()?.fill_rings(&[points], color);
+            }
+            "text" => {
+                let (x, y) = (coord(cmd, "x")?, coord(cmd, "y")?);
+                let text = string(cmd, "text")?;
+                let scale = number_or(cmd, "scale", 1.0)?;
+                if !(1.0..=MAX_TEXT_SCALE).contains(&scale) {
+                    return Err(format!("\"scale\" must be from 1 to {}", MAX_TEXT_SCALE));
+                }
+                let style = crate::text::MiniFBTextStyle {
+                    color: color(cmd, 0xFFFFFF)?,
+                    ..minifb_text_style_default()
+                };
+                let mut font = MiniFBFont::builtin(scale as u32);
+                font.draw(self.canvas()?, text, x as i64, y as i64, &style);
+            }
+            "image" => {
+                let path = string(cmd, "path")?;
+                let (x, y) = (coord_or(cmd, "x", 0.0)?, coord_or(cmd, "y", 0.0)?);
+                let w = cmd.get("w").map(|_| coord(cmd, "w")).transpose()?;
+                let h = cmd.get("h").map(|_| coord(cmd, "h")).transpose()?;
+                let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+                let image = MiniFBImage::decode(&bytes).map_err(|e| format!("{}: {}", path, e))?;
+                let rect = (
+                    x,
+                    y,
+                    w.unwrap_or(image.width as f64),
+                    h.unwrap_or(image.height as f64),
+                );
+                let opts = minifb_blit_options_default();
+                self.canvas()?
+                    .blit(&image, rect, &MiniFBTransform::IDENTITY, &opts);
+            }
+            "present" => {
+                let (Some(window), Some(canvas)) =
+                    (self.window.as_deref_mut(), self.canvas.as_mut())
+                else {
+                    return Err("no window; send \"create\" first".into());
+                };
+                // SAFETY: both references are valid for the duration of the call
+                if unsafe { minifb_window_update_with_canvas(window, canvas) } != 0 {
+                    return Err(last_error().unwrap_or_else(|| "present failed".into()));
+                }
+                self.screen = Some(MiniFBImage {
+                    width: canvas.width,
+                    height: canvas.height,
+                    pixels: canvas.pixels.iter().map(|&p| p | 0xFF000000).collect(),
+                });
+                self.frames += 1;
+                self.collect_events(events);
+                reply.insert("frame".into(), self.frames.into());
+            }
+            "update" => {
+                self.window()?.window.update();
+                self.frames += 1;
+                self.collect_events(events);
+                reply.insert("frame".into(), self.frames.into());
+            }
+            "key" => {
+                let name = string(cmd, "key")?;
+                let key = key_by_name(name).ok_or_else(|| format!("unknown key \"{}\"", name))?;
+                let down = down(cmd)?;
+                let backend = &mut self.window()?.window;
+                for state in down.map_or(vec![true, false], |d| vec![d]) {
+                    backend.inject(InputEvent::Key(key, state));
+                }
+            }
+            "mouse" => {
+                let pos = (number(cmd, "x")? as f32, number(cmd, "y")? as f32);
+                self.window()?
+                    .window
+                    .inject(InputEvent::MouseMove(Some(pos)));
+            }
+            "button" => {
+                let name = string(cmd, "button")?;
+                let (_, button) = BUTTONS
+                    .into_iter()
+                    .find(|(n, _)| *n == name)
+                    .ok_or_else(|| format!("unknown button \"{}\"", name))?;
+                let down = down(cmd)?;
+                self.window()?
+                    .window
+                    .inject(InputEvent::Button(button, down.unwrap_or(true)));
+                if down.is_none() {
+                    self.releases.push(InputEvent::Button(button, false));
+                }
+            }
+            "scroll" => {
+                let (dx, dy) = (number_or(cmd, "dx", 0.0)?, number_or(cmd, "dy", 0.0)?);
+                self.window()?
+                    .window
+                    .inject(InputEvent::Scroll(dx as f32, dy as f32));
+            }
+            "input" => {
+                let open = self.open;
+                let backend = &self.window()?.window;
+                let keys: Vec<String> = backend.get_keys().into_iter().map(key_name).collect();
+                let mouse = backend
+                    .get_mouse_pos(MouseMode::Pass)
+                    .map(|(x, y)| json!([x, y]));
+                let buttons: Map<String, Value> = BUTTONS
+                    .iter()
+                    .map(|(name, b)| (name.to_string(), backend.get_mouse_down(*b).into()))
+                    .collect();
+                reply.insert("open".into(), open.into());
+                reply.insert("keys".into(), keys.into());
+                reply.insert("mouse".into(), mouse.unwrap_or(Value::Null));
+                reply.insert("buttons".into(), buttons.into());
+            }
+            "screenshot" => {
+                let path = string(cmd, "path")?;
+                let png = self
+                    .screen
+                    .as_ref()
+                    .ok_or("nothing presented yet")?
+                    .encode_png()?;
+                std::fs::write(path, png).map_err(|e| format!("{}: {}", path, e))?;
+            }
+            "quit" => {}
+            other => return Err(format!("unknown command \"{}\"", other)),
+        }
+        Ok(reply)
+    }
+}
+
+/// Execute commands from `input` until it ends or a `quit` command
+pub(crate) fn run(input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut session = Session::default();
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut events = Vec::new();
+        let mut reply = Map::new();
+        let mut quit = false;
+        match serde_json::from_str::<Value>(&line) {
+            Ok(cmd) => {
+                let result = session.execute(&cmd, &mut events);
+                reply.insert("ok".into(), result.is_ok().into());
+                match result {
+                    Ok(fields) => reply.extend(fields),
+                    Err(e) => {
+                        reply.insert("error".into(), e.into());
+                    }
+                }
+                if let Some(id) = cmd.get("id") {
+                    reply.insert("id".into(), id.clone());
+                }
+                quit = cmd.get("cmd").and_then(Value::as_str) == Some("quit");
+            }
+            Err(e) => {
+                reply.insert("ok".into(), false.into());
+                reply.insert("error".into(), format!("invalid JSON: {}", e).into());
+            }
+        }
+        for event in events {
+            writeln!(output, "{}", event)?;
+        }
+        writeln!(output, "{}", Value::Object(reply))?;
+        output.flush()?;
+        if quit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Run the JSON command protocol on stdin and stdout until stdin closes or
+/// a `quit` command arrives
+///
+/// This is what the `minifb-script` executable does; combine it with
+/// `MINIFB_FFI_BACKEND=headless` (or `"headless": true` in `create`) for
+/// scripts that should not need a display.
+///
+/// Returns 0 on success, -1 on an I/O error (see `minifb_get_last_error`)
+#[no_mangle]
+pub extern "C" fn minifb_script_run_stdio() -> i32 {
+    match run(io::stdin().lock(), io::stdout().lock()) {
+        Ok(()) => 0,
+        Err(e) => {
+            crate::set_last_error(&format!("script: {}", e));
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_session() {
+        let path =
+            std::env::temp_dir().join(format!("minifb-ffi-script-{}.png", std::process::id()));
+        let script = format!(
+            concat!(
+                "{{\"cmd\":\"create\",\"width\":4,\"height\":4,\"headless\":true}}\n",
+                "{{\"cmd\":\"rect\",\"x\":1,\"y\":1,\"w\":2,\"h\":2,\"color\":\"#ff0000\"}}\n",
+                "{{\"cmd\":\"key\",\"key\":\"Space\"}}\n",
+                "{{\"cmd\":\"mouse\",\"x\":2,\"y\":3}}\n",
+                "{{\"cmd\":\"present\",\"id\":7}}\n",
+                "{{\"cmd\":\"input\"}}\n",
+                "{{\"cmd\":\"screenshot\",\"path\":{:?}}}\n",
+                "{{\"cmd\":\"key\",\"key\":\"Nope\"}}\n",
+                "{{\"cmd\":\"quit\"}}\n",
+                "{{\"cmd\":\"update\"}}\n",
+            ),
+            path.to_str().unwrap()
+        );
+        let mut out = Vec::new();
+        run(script.as_bytes(), &mut out).unwrap();
+        let lines: Vec<Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 12);
+        assert_eq!(
+            lines[4],
+            json!({"event": "key", "key": "Space", "down": true})
+        );
+        assert_eq!(
+            lines[5],
+            json!({"event": "key", "key": "Space", "down": false})
+        );
+        assert_eq!(lines[6], json!({"event": "mouse", "x": 2.0, "y": 3.0}));
+        assert_eq!(lines[7], json!({"ok": true, "frame": 1, "id": 7}));
+        assert_eq!(lines[8]["mouse"], json!([2.0, 3.0]));
+        assert_eq!(lines[9], json!({"ok": true}));
+        assert_eq!(lines[10]["ok"], json!(false));
+
+        let png = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let image = MiniFBImage::decode(&png).unwrap();
+        assert_eq!(image.pixels[5], 0xFFFF0000);
+        assert_eq!(image.pixels[0], 0xFF000000);
+    }
+
+    #[test]
+    fn test_bad_create_size() {
+        let script = concat!(
+            "{\"cmd\":\"create\",\"width\":1e10,\"height\":1e10,\"headless\":true}\n",
+            "{\"cmd\":\"create\",\"width\":-4,\"height\":4,\"headless\":true}\n",
+            "{\"cmd\":\"create\",\"width\":2.5,\"height\":4,\"headless\":true}\n",
+        );
+        let mut out = Vec::new();
+        run(script.as_bytes(), &mut out).unwrap();
+        let replies: Vec<Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(replies.len(), 3);
+        assert!(replies.iter().all(|r| r["ok"] == json!(false)));
+    }
+
+    #[test]
+    fn test_bad_draw_arguments() {
+        let script = concat!(
+            "{\"cmd\":\"create\",\"width\":8,\"height\":8,\"headless\":true}\n",
+            "{\"cmd\":\"rect\",\"x\":1e19,\"y\":0,\"w\":1e19,\"h\":2}\n",
+            "{\"cmd\":\"line\",\"x0\":0,\"y0\":0,\"x1\":-1e300,\"y1\":4}\n",
+            "{\"cmd\":\"polygon\",\"points\":[[0,0],[1e19,0],[0,4]]}\n",
+            "{\"cmd\":\"text\",\"x\":0,\"y\":0,\"text\":\"a\",\"scale\":1e12}\n",
+            "{\"cmd\":\"image\",\"path\":\"-\",\"w\":1e300}\n",
+            "{\"cmd\":\"rect\",\"x\":-5,\"y\":0,\"w\":1e9,\"h\":2}\n",
+        );
+        let mut out = Vec::new();
+        run(script.as_bytes(), &mut out).unwrap();
+        let replies: Vec<Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        let ok: Vec<bool> = replies.iter().map(|r| r["ok"] == json!(true)).collect();
+        assert_eq!(ok, [true, false, false, false, false, false, true]);
+        assert!(replies[5]["error"].as_str().unwrap().contains("\"w\""));
+    }
+}
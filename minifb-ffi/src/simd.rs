@@ -0,0 +1,155 @@
+//! SSE2 kernels for the hottest per-pixel loops
+//!
+//! Each kernel handles the longest prefix it can process in whole vectors
+//! and returns its length; callers finish the remainder with their scalar
+//! loop, which doubles as the reference implementation. SSE2 is part of the
+//! x86_64 baseline, so no runtime detection is needed. On other targets the
+//! kernels process nothing and the scalar loops do all the work.
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+/// Blend `color` over `row` with coverage `alpha`, 4 pixels at a time, with
+/// the same rounding as `raster::blend_span`
+#[allow(unused_variables)]
+pub(crate) fn blend(row: &mut [u32], color: u32, alpha: u8) -> usize {
+    let len = row.len() & !3;
+    #[cfg(target_arch = "x86_64")]
+    // SAFETY: SSE2 is always available on x86_64, and every access stays
+    // within the first `len` pixels of `row`
+    unsafe {
+        let zero = _mm_setzero_si128();
+        let t = alpha as i16;
+        // Channels widened to 16 bits; products and sums stay below 65536
+        let src = _mm_unpacklo_epi8(_mm_set1_epi32(color as i32), zero);
+        let src = _mm_add_epi16(
+            _mm_mullo_epi16(src, _mm_set1_epi16(t)),
+            _mm_set1_epi16(0x7F),
+        );
+        let inv = _mm_set1_epi16(255 - t);
+        let one = _mm_set1_epi16(1);
+        let lerp = |d: __m128i| {
+            let v = _mm_add_epi16(_mm_mullo_epi16(d, inv), src);
+            _mm_srli_epi16(
+                _mm_add_epi16(_mm_add_epi16(v, one), _mm_srli_epi16(v, 8)),
+                8,
+            )
+        };
+        let mask = _mm_set1_epi32(0x00FFFFFF);
+        for chunk in row[..len].chunks_exact_mut(4) {
+            let p = chunk.as_mut_ptr() as *mut __m128i;
+            let d = _mm_loadu_si128(p);
+            let lo = lerp(_mm_unpacklo_epi8(d, zero));
+            let hi = lerp(_mm_unpackhi_epi8(d, zero));
+            _mm_storeu_si128(p, _mm_and_si128(_mm_packus_epi16(lo, hi), mask));
+        }
+        len
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        0
+    }
+}
+
+/// Convert RGBA8 bytes to 0RGB, 4 pixels at a time
+#[allow(unused_variables)]
+pub(crate) fn rgba_to_0rgb(src: &[u8], out: &mut [u32]) -> usize {
+    let len = out.len().min(src.len() / 4) & !3;
+    #[cfg(target_arch = "x86_64")]
+    // SAFETY: as in `blend`; `src` holds at least `4 * len` bytes
+    unsafe {
+        let low = _mm_set1_epi32(0xFF);
+        let green = _mm_set1_epi32(0xFF00);
+        for i in (0..len).step_by(4) {
+            let p = _mm_loadu_si128(src.as_ptr().add(i * 4) as *const __m128i);
+            let r = _mm_slli_epi32(_mm_and_si128(p, low), 16);
+            let g = _mm_and_si128(p, green);
+            let b = _mm_and_si128(_mm_srli_epi32(p, 16), low);
+            let rgb = _mm_or_si128(_mm_or_si128(r, g), b);
+            _mm_storeu_si128(out.as_mut_ptr().add(i) as *mut __m128i, rgb);
+        }
+        len
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        0
+    }
+}
+
+/// Convert BGRA8 bytes to 0RGB, 4 pixels at a time
+#[allow(unused_variables)]
+pub(crate) fn bgra_to_0rgb(src: &[u8], out: &mut [u32]) -> usize {
+    let len = out.len().min(src.len() / 4) & !3;
+    #[cfg(target_arch = "x86_64")]
+    // SAFETY: as in `rgba_to_0rgb`
+    unsafe {
+        let mask = _mm_set1_epi32(0x00FFFFFF);
+        for i in (0..len).step_by(4) {
+            let p = _mm_loadu_si128(src.as_ptr().add(i * 4) as *const __m128i);
+            _mm_storeu_si128(
+                out.as_mut_ptr().add(i) as *mut __m128i,
+                _mm_and_si128(p, mask),
+            );
+        }
+        len
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        0
+    }
+}
+
+/// Convert 8-bit luminance to gray 0RGB, 16 pixels at a time
+#[allow(unused_variables)]
+pub(crate) fn gray_to_0rgb(src: &[u8], out: &mut [u32]) -> usize {
+    let len = out.len().min(src.len()) & !15;
+    #[cfg(target_arch = "x86_64")]
+    // SAFETY: as in `blend`; `src` holds at least `len` bytes
+    unsafe {
+        let zero = _mm_setzero_si128();
+        let gray =
+            |l: __m128i| _mm_or_si128(_mm_or_si128(l, _mm_slli_epi32(l, 8)), _mm_slli_epi32(l, 16));
+        for i in (0..len).step_by(16) {
+            let l = _mm_loadu_si128(src.as_ptr().add(i) as *const __m128i);
+            let halves = [_mm_unpacklo_epi8(l, zero), _mm_unpackhi_epi8(l, zero)];
+            for (j, half) in halves.into_iter().enumerate() {
+                let o = out.as_mut_ptr().add(i + j * 8) as *mut __m128i;
+                _mm_storeu_si128(o, gray(_mm_unpacklo_epi16(half, zero)));
+                _mm_storeu_si128(o.add(1), gray(_mm_unpackhi_epi16(half, zero)));
+            }
+        }
+        len
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions_match_scalar() {
+        let bytes: Vec<u8> = (0..151u32).map(|i| (i * 97 + 13) as u8).collect();
+        let mut out = [0u32; 37];
+
+        let done = rgba_to_0rgb(&bytes, &mut out);
+        for (o, p) in out[..done].iter().zip(bytes.chunks_exact(4)) {
+            let p = u32::from_le_bytes([p[0], p[1], p[2], p[3]]);
+            assert_eq!(*o, ((p & 0xFF) << 16) | (p & 0xFF00) | ((p >> 16) & 0xFF));
+        }
+        let done = bgra_to_0rgb(&bytes, &mut out);
+        for (o, p) in out[..done].iter().zip(bytes.chunks_exact(4)) {
+            assert_eq!(
+                *o,
+                u32::from_le_bytes([p[0], p[1], p[2], p[3]]) & 0x00FFFFFF
+            );
+        }
+        let done = gray_to_0rgb(&bytes, &mut out);
+        for (o, &l) in out[..done].iter().zip(&bytes) {
+            assert_eq!(*o, l as u32 * 0x010101);
+        }
+    }
+}
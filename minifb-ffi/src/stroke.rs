@@ -0,0 +1,617 @@
+//! Thick polyline stroking with joins, caps and dash patterns
+//!
+//! A stroke is turned into outline polygons in screen space: one quad per
+//! segment plus join and cap pieces, all oriented the same way. The pieces
+//! are filled together under the nonzero rule so overlaps are painted once,
+//! with anti-aliased edges from `draw::fill_coverage`.
+
+use crate::camera::MiniFBCamera;
+use crate::canvas::MiniFBCanvas;
+use crate::draw::{camera_transform, clip_range, fill_coverage, read_points};
+use std::f64::consts::{PI, SQRT_2};
+use std::slice;
+
+type Point = (f64, f64);
+
+/// Shape drawn where two segments meet
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiniFBLineJoin {
+    JoinMiter = 0,
+    JoinRound = 1,
+    JoinBevel = 2,
+}
+
+/// Shape drawn at the ends of open polylines and dashes
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiniFBLineCap {
+    CapButt = 0,
+    CapRound = 1,
+    CapSquare = 2,
+}
+
+/// Unit of stroke widths and dash lengths
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiniFBStrokeUnits {
+    /// Screen pixels, independent of the camera zoom
+    UnitsPixels = 0,
+    /// World units, scaled with the camera
+    UnitsWorld = 1,
+}
+
+/// Stroke style
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MiniFBStrokeStyle {
+    pub color: u32,
+    pub opacity: f32,
+    pub width: f32,
+    pub units: MiniFBStrokeUnits,
+    pub join: MiniFBLineJoin,
+    pub cap: MiniFBLineCap,
+    /// Miter joins longer than `miter_limit * width` fall back to bevels
+    pub miter_limit: f32,
+    /// Alternating dash and gap lengths (repeated twice when odd, as in SVG);
+    /// only read during the drawing call, may be null when `dash_count` is 0
+    pub dash: *const f32,
+    pub dash_count: usize,
+    /// Distance into the dash pattern at which the stroke starts
+    pub dash_offset: f32,
+    pub antialias: bool,
+}
+
+/// Create the default stroke style (white, 1 pixel, miter joins, butt caps, solid)
+#[no_mangle]
+pub extern "C" fn minifb_stroke_style_default() -> MiniFBStrokeStyle {
+    MiniFBStrokeStyle {
+        color: 0x00FFFFFF,
+        opacity: 1.0,
+        width: 1.0,
+        units: MiniFBStrokeUnits::UnitsPixels,
+        join: MiniFBLineJoin::JoinMiter,
+        cap: MiniFBLineCap::CapButt,
+        miter_limit: 4.0,
+        dash: std::ptr::null(),
+        dash_count: 0,
+        dash_offset: 0.0,
+        antialias: true,
+    }
+}
+
+fn sub(a: Point, b: Point) -> Point {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn offset(p: Point, d: Point, k: f64) -> Point {
+    (p.0 + d.0 * k, p.1 + d.1 * k)
+}
+
+fn length(d: Point) -> f64 {
+    d.0.hypot(d.1)
+}
+
+/// Unit direction and left normal of the segment a -> b
+fn frame(a: Point, b: Point) -> (Point, Point) {
+    let d = sub(b, a);
+    let len = length(d);
+    let d = (d.0 / len, d.1 / len);
+    (d, (-d.1, d.0))
+}
+
+/// Shortest dash period in pixels; finer patterns can't show their gaps, so
+/// they are stroked solid instead of being split into countless pieces
+const MIN_DASH_PERIOD: f64 = 1.0;
+
+/// Most dash pieces a stroke may be split into before it is drawn solid
+const MAX_DASH_PIECES: usize = 100_000;
+
+/// Position within a dash pattern
+struct Phase<'a> {
+    pattern: &'a [f64],
+    total: f64,
+    index: usize,
+    /// Length left in the current dash or gap
+    left: f64,
+}
+
+impl Phase<'_> {
+    fn on(&self) -> bool {
+        self.index.is_multiple_of(2)
+    }
+
+    /// Move to the start of the next dash or gap
+    fn next(&mut self) {
+        self.index = (self.index + 1) % self.pattern.len();
+        self.left = self.pattern[self.index];
+    }
+
+    /// Advance by `d` without drawing, skipping whole periods at once
+    ///
+    /// Stops at the start of a zero-length dash rather than stepping past it.
+    fn skip(&mut self, d: f64) {
+        if d <= 0.0 {
+            return;
+        }
+        if d < self.left {
+            self.left -= d;
+            return;
+        }
+        let mut d = d - self.left;
+        self.next();
+        d = d.rem_euclid(self.total);
+        while d > 0.0 && d >= self.left {
+            d -= self.left;
+            self.next();
+        }
+        self.left -= d;
+    }
+}
+
+/// Split a polyline into the pieces covered by the "on" parts of a dash
+/// pattern, keeping only what lies inside `bounds`
+///
+/// The pattern still advances along the clipped-off parts, so dashes stay
+/// where they would be without clipping. Each piece comes with the unit
+/// direction of the path where it ends, which orients the caps of
+/// zero-length dashes.
+pub(crate) fn dash_pieces(
+    points: &[Point],
+    dashes: &[f64],
+    dash_offset: f64,
+    bounds: (f64, f64, f64, f64),
+) -> Vec<(Vec<Point>, Point)> {
+    let pattern: Vec<f64> = if dashes.len() % 2 == 1 {
+        dashes.iter().chain(dashes).map(|d| d.max(0.0)).collect()
+    } else {
+        dashes.iter().map(|d| d.max(0.0)).collect()
+    };
+    let total: f64 = pattern.iter().sum();
+    if pattern.is_empty() || !(MIN_DASH_PERIOD..f64::INFINITY).contains(&total) {
+        return vec![(points.to_vec(), (1.0, 0.0))];
+    }
+
+    // Find the dash the stroke starts in
+    let mut phase = Phase {
+        pattern: &pattern,
+        total,
+        index: 0,
+        left: pattern[0],
+    };
+    if dash_offset.is_finite() {
+        phase.skip(dash_offset.rem_euclid(total));
+    }
+
+    let mut pieces = Vec::new();
+    let mut current: Vec<Point> = Vec::new();
+    let mut direction = (1.0, 0.0);
+    let mut end_visible = false;
+    let finish = |pieces: &mut Vec<_>, current: &mut Vec<Point>, direction: Point| {
+        if current.len() >= 2 {
+            pieces.push((std::mem::take(current), direction));
+        }
+        current.clear();
+    };
+    for pair in points.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let len = length(sub(b, a));
+        if len > 0.0 {
+            direction = frame(a, b).0;
+        }
+        let at = |t: f64| match t {
+            t if t <= 0.0 => a,
+            t if t >= len => b,
+            t => offset(a, sub(b, a), t / len),
+        };
+        let Some((t0, t1)) = clip_range(a, b, bounds) else {
+            end_visible = false;
+            finish(&mut pieces, &mut current, direction);
+            phase.skip(len);
+            continue;
+        };
+        let (t0, t1) = (t0 * len, t1 * len);
+        end_visible = t1 >= len;
+        if t0 > 0.0 {
+            finish(&mut pieces, &mut current, direction);
+            phase.skip(t0);
+        }
+        if phase.on() && current.is_empty() {
+            current.push(at(t0));
+        }
+        let mut t = t0;
+        while t1 - t > phase.left {
+            t += phase.left;
+            let p = at(t);
+            if phase.on() {
+                current.push(p);
+                pieces.push((std::mem::take(&mut current), direction));
+            } else {
+                current = vec![p];
+            }
+            phase.next();
+            if pieces.len() > MAX_DASH_PIECES {
+                return vec![(points.to_vec(), (1.0, 0.0))];
+            }
+        }
+        phase.left -= t1 - t;
+        if phase.on() {
+            current.push(at(t1));
+        }
+        if t1 < len {
+            finish(&mut pieces, &mut current, direction);
+            phase.skip(len - t1);
+        }
+    }
+    // A zero-length dash right at the end still gets its caps, so a dotted
+    // line that fits exactly ends in a dot
+    if end_visible && !phase.on() && phase.left <= 0.0 {
+        phase.next();
+        if phase.left <= 0.0 {
+            let end = points[points.len() - 1];
+            current = vec![end, end];
+        }
+    }
+    finish(&mut pieces, &mut current, direction);
+    pieces
+}
+
+/// Polygon approximating a circle to within a tenth of a pixel
+fn disc(center: Point, radius: f64) -> Vec<Point> {
+    let step = 2.0 * (1.0 - 0.1 / radius).clamp(-1.0, 1.0).acos();
+    let n = ((2.0 * PI / step).ceil() as usize).clamp(8, 128);
+    (0..n)
+        .map(|i| {
+            let a = 2.0 * PI * i as f64 / n as f64;
+            (center.0 + radius * a.cos(), center.1 + radius * a.sin())
+        })
+        .collect()
+}
+
+/// Push a polygon with positive signed area so all pieces wind the same way
+fn push_oriented(out: &mut Vec<Vec<Point>>, mut poly: Vec<Point>) {
+    let area: f64 = poly
+        .iter()
+        .zip(poly.iter().cycle().skip(1))
+        .map(|(a, b)| a.0 * b.1 - b.0 * a.1)
+        .sum();
+    if area < 0.0 {
+        poly.reverse();
+    }
+    out.push(poly);
+}
+
+struct Outline {
+    half_width: f64,
+    join: MiniFBLineJoin,
+    cap: MiniFBLineCap,
+    miter_limit: f64,
+}
+
+impl Outline {
+    fn join(
+        &self,
+        out: &mut Vec<Vec<Point>>,
+        p: Point,
+        (d0, n0): (Point, Point),
+        (d1, n1): (Point, Point),
+    ) {
+        let hw = self.half_width;
+        let cross = d0.0 * d1.1 - d0.1 * d1.0;
+        if cross.abs() < 1e-12 && d0.0 * d1.0 + d0.1 * d1.1 > 0.0 {
+            return;
+        }
+        if self.join == MiniFBLineJoin::JoinRound {
+            push_oriented(out, disc(p, hw));
+            return;
+        }
+        // The offset edges diverge on the side away from the turn
+        let side = if cross > 0.0 { -1.0 } else { 1.0 };
+        let a = offset(p, n0, side * hw);
+        let b = offset(p, n1, side * hw);
+        let bisector = (n0.0 + n1.0, n0.1 + n1.1);
+        let bisector_len = length(bisector);
+        // Miter length relative to the stroke width is 1 / cos(theta / 2)
+        let ratio = 2.0 / bisector_len;
+        if self.join == MiniFBLineJoin::JoinMiter
+            && bisector_len > 1e-9
+            && ratio <= self.miter_limit
+        {
+            let tip = offset(p, bisector, side * hw * ratio / bisector_len);
+            push_oriented(out, vec![p, a, tip, b]);
+        } else {
+            push_oriented(out, vec![p, a, b]);
+        }
+    }
+
+    fn cap(&self, out: &mut Vec<Vec<Point>>, p: Point, (d, n): (Point, Point)) {
+        let hw = self.half_width;
+        match self.cap {
+            MiniFBLineCap::CapButt => {}
+            MiniFBLineCap::CapRound => push_oriented(out, disc(p, hw)),
+            MiniFBLineCap::CapSquare => {
+                let q = offset(p, d, hw);
+                push_oriented(
+                    out,
+                    vec![
+                        offset(p, n, hw),
+                        offset(q, n, hw),
+                        offset(q, n, -hw),
+                        offset(p, n, -hw),
+                    ],
+                );
+            }
+        }
+    }
+
+    /// Caps on both sides of a zero-length piece heading along `d`, which
+    /// is how dotted lines are drawn
+    fn dot(&self, out: &mut Vec<Vec<Point>>, p: Point, d: Point) {
+        let n = (-d.1, d.0);
+        self.cap(out, p, (d, n));
+        if self.cap == MiniFBLineCap::CapSquare {
+            self.cap(out, p, ((-d.0, -d.1), n));
+        }
+    }
+
+    /// Append the outline polygons of one polyline; `direction` orients the
+    /// caps if it turns out to have zero length
+    fn stroke(&self, out: &mut Vec<Vec<Point>>, points: &[Point], closed: bool, direction: Point) {
+        let mut points: Vec<Point> = points.to_vec();
+        points.dedup_by(|a, b| length(sub(*a, *b)) < 1e-9);
+        if closed && points.len() > 2 && length(sub(points[0], points[points.len() - 1])) < 1e-9 {
+            points.pop();
+        }
+        if points.len() == 1 {
+            self.dot(out, points[0], direction);
+        }
+        if points.len() < 2 {
+            return;
+        }
+        let closed = closed && points.len() > 2;
+        let count = if closed {
+            points.len()
+        } else {
+            points.len() - 1
+        };
+        let frames: Vec<(Point, Point)> = (0..count)
+            .map(|i| frame(points[i], points[(i + 1) % points.len()]))
+            .collect();
+
+        let hw = self.half_width;
+        for (i, &(_, n)) in frames.iter().enumerate() {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            push_oriented(
+                out,
+                vec![
+                    offset(a, n, hw),
+                    offset(b, n, hw),
+                    offset(b, n, -hw),
+                    offset(a, n, -hw),
+                ],
+            );
+        }
+        for i in 1..count {
+            self.join(out, points[i], frames[i - 1], frames[i]);
+        }
+        if closed {
+            self.join(out, points[0], frames[count - 1], frames[0]);
+        } else {
+            let (d, n) = frames[0];
+            self.cap(out, points[0], ((-d.0, -d.1), n));
+            self.cap(out, points[count], frames[count - 1]);
+        }
+    }
+}
+
+impl MiniFBCanvas {
+    pub(crate) fn stroke_polyline(
+        &mut self,
+        points: &[Point],
+        closed: bool,
+        style: &MiniFBStrokeStyle,
+        scale: f64,
+        dashes: &[f64],
+    ) {
+        let outline = Outline {
+            half_width: style.width.max(0.0) as f64 * scale / 2.0,
+            join: style.join,
+            cap: style.cap,
+            miter_limit: style.miter_limit.max(1.0) as f64,
+        };
+        if outline.half_width <= 0.0 || points.len() < 2 {
+            return;
+        }
+        let mut rings = Vec::new();
+        let dashes: Vec<f64> = dashes.iter().map(|d| d * scale).collect();
+        if dashes.is_empty() {
+            outline.stroke(&mut rings, points, closed, (1.0, 0.0));
+        } else {
+            let mut path = points.to_vec();
+            if closed {
+                path.push(points[0]);
+            }
+            // Dashes outside the canvas, with room for caps and miters
+            // reaching back in, are never drawn
+            let reach = outline.half_width * outline.miter_limit.max(SQRT_2) + 1.0;
+            let bounds = (
+                -reach,
+                -reach,
+                self.width as f64 + reach,
+                self.height as f64 + reach,
+            );
+            let dash_offset = style.dash_offset as f64 * scale;
+            let mut pieces = dash_pieces(&path, &dashes, dash_offset, bounds);
+            // A dash running through the start of a closed path is one
+            // piece with a join there, not two pieces with caps
+            let mut whole = false;
+            let (start, end) = (path[0], path[path.len() - 1]);
+            if closed
+                && !pieces.is_empty()
+                && pieces[0].0[0] == start
+                && pieces[pieces.len() - 1].0.last() == Some(&end)
+            {
+                if pieces.len() == 1 {
+                    whole = true;
+                } else {
+                    let (first, direction) = pieces.remove(0);
+                    let last = pieces.last_mut().unwrap();
+                    last.0.extend_from_slice(&first[1..]);
+                    last.1 = direction;
+                }
+            }
+            for (piece, direction) in pieces {
+                outline.stroke(&mut rings, &piece, whole, direction);
+            }
+        }
+
+        let opacity = style.opacity.clamp(0.0, 1.0);
+        let (width, height) = (self.width, self.height);
+        fill_coverage(width, height, &rings, style.antialias, |y, x0, coverage| {
+            for (i, &c) in coverage.iter().enumerate() {
+                let alpha = (c.min(1.0) * opacity * 255.0).round() as u8;
+                self.blend((x0 + i) as i64, y as i64, style.color, alpha);
+            }
+        });
+    }
+}
+
+/// Stroke a polyline with width, joins, caps and dashes
+///
+/// # Safety
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+/// - `points` must point to at least `2 * count` f32 values
+/// - `style` must be a valid pointer to MiniFBStrokeStyle whose `dash` points
+///   to `dash_count` f32 values
+/// - `camera` must be a valid camera pointer, or null for pixel coordinates
+#[no_mangle]
+pub unsafe extern "C" fn minifb_canvas_stroke_polyline(
+    canvas: *mut MiniFBCanvas,
+    points: *const f32,
+    count: usize,
+    closed: bool,
+    style: *const MiniFBStrokeStyle,
+    camera: *const MiniFBCamera,
+) {
+    if canvas.is_null() || points.is_null() || style.is_null() || count < 2 {
+        return;
+    }
+    let style = &*style;
+    let transform = camera_transform(camera);
+    let scale = match style.units {
+        MiniFBStrokeUnits::UnitsPixels => 1.0,
+        MiniFBStrokeUnits::UnitsWorld => transform.determinant().abs().sqrt(),
+    };
+    let dashes: Vec<f64> = if style.dash.is_null() {
+        Vec::new()
+    } else {
+        slice::from_raw_parts(style.dash, style.dash_count)
+            .iter()
+            .map(|&d| d as f64)
+            .collect()
+    };
+    let points = read_points(points, count, &transform);
+    (*canvas).stroke_polyline(&points, closed, style, scale, &dashes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVERYWHERE: (f64, f64, f64, f64) = (-1e9, -1e9, 1e9, 1e9);
+
+    #[test]
+    fn test_dash_pieces() {
+        let line = [(0.0, 0.0), (10.0, 0.0)];
+        let pieces = dash_pieces(&line, &[3.0, 2.0], 0.0, EVERYWHERE);
+        let starts: Vec<(f64, f64)> = pieces
+            .iter()
+            .map(|(p, _)| (p[0].0, p[p.len() - 1].0))
+            .collect();
+        assert_eq!(starts, vec![(0.0, 3.0), (5.0, 8.0)]);
+
+        // Odd arrays repeat, and the offset shifts into the pattern
+        let pieces = dash_pieces(&line, &[4.0], 2.0, EVERYWHERE);
+        let starts: Vec<(f64, f64)> = pieces
+            .iter()
+            .map(|(p, _)| (p[0].0, p[p.len() - 1].0))
+            .collect();
+        assert_eq!(starts, vec![(0.0, 2.0), (6.0, 10.0)]);
+
+        // Patterns far below a pixel are stroked solid
+        let line = [(0.0, 0.0), (1000.0, 0.0)];
+        assert_eq!(
+            dash_pieces(&line, &[1e-6, 1e-6], 0.0, EVERYWHERE),
+            vec![(line.to_vec(), (1.0, 0.0))]
+        );
+    }
+
+    #[test]
+    fn test_dashes_clipped_to_bounds() {
+        // Millions of pixels long, but only the stretch over x in [0, 12]
+        // is split, with the pattern still in phase there
+        let line = [(-1e7, 0.0), (1e7, 0.0)];
+        let pieces = dash_pieces(&line, &[3.0, 2.0], 0.0, (0.0, -1.0, 12.0, 1.0));
+        let starts: Vec<(f64, f64)> = pieces
+            .iter()
+            .map(|(p, _)| (p[0].0, p[p.len() - 1].0))
+            .collect();
+        assert_eq!(starts, vec![(0.0, 3.0), (5.0, 8.0), (10.0, 12.0)]);
+    }
+
+    #[test]
+    fn test_wide_stroke_caps_and_joins() {
+        let mut style = minifb_stroke_style_default();
+        style.color = 0xFF;
+        style.width = 4.0;
+        let mut canvas = MiniFBCanvas::new(24, 24);
+        canvas.stroke_polyline(&[(4.0, 10.0), (16.0, 10.0)], false, &style, 1.0, &[]);
+        // Rows 8-11 fully covered between the butt ends
+        for y in 8..12 {
+            assert_eq!(canvas.pixels[y * 24 + 4], 0xFF);
+            assert_eq!(canvas.pixels[y * 24 + 15], 0xFF);
+        }
+        assert_eq!(canvas.pixels[7 * 24 + 10], 0);
+        assert_eq!(canvas.pixels[10 * 24 + 3], 0);
+
+        style.cap = MiniFBLineCap::CapSquare;
+        let mut canvas = MiniFBCanvas::new(24, 24);
+        canvas.stroke_polyline(&[(4.0, 10.0), (16.0, 10.0)], false, &style, 1.0, &[]);
+        assert_eq!(canvas.pixels[10 * 24 + 3], 0xFF);
+
+        // A right-angle miter fills the outer corner, a bevel cuts it off
+        let corner = [(4.0, 4.0), (12.0, 4.0), (12.0, 12.0)];
+        let mut canvas = MiniFBCanvas::new(24, 24);
+        canvas.stroke_polyline(&corner, false, &style, 1.0, &[]);
+        assert_eq!(canvas.pixels[2 * 24 + 13], 0xFF);
+        style.join = MiniFBLineJoin::JoinBevel;
+        let mut canvas = MiniFBCanvas::new(24, 24);
+        canvas.stroke_polyline(&corner, false, &style, 1.0, &[]);
+        assert_eq!(canvas.pixels[2 * 24 + 13], 0);
+    }
+
+    #[test]
+    fn test_dots_and_closed_dash_seam() {
+        // Zero-length dashes with round caps make a dotted line
+        let mut style = minifb_stroke_style_default();
+        style.color = 0xFF;
+        style.width = 4.0;
+        style.cap = MiniFBLineCap::CapRound;
+        let mut canvas = MiniFBCanvas::new(32, 16);
+        canvas.stroke_polyline(&[(4.0, 8.0), (28.0, 8.0)], false, &style, 1.0, &[0.0, 8.0]);
+        for x in [4, 12, 20, 28] {
+            assert_eq!(canvas.pixels[8 * 32 + x], 0xFF);
+        }
+        assert_eq!(canvas.pixels[8 * 32 + 8], 0);
+
+        // The dash over the start of a closed square gets a miter join, so
+        // the outer corner is filled
+        let mut style = minifb_stroke_style_default();
+        style.color = 0xFF;
+        style.width = 4.0;
+        let square = [(10.0, 10.0), (30.0, 10.0), (30.0, 30.0), (10.0, 30.0)];
+        let mut canvas = MiniFBCanvas::new(40, 40);
+        canvas.stroke_polyline(&square, true, &style, 1.0, &[30.0, 20.0]);
+        assert_eq!(canvas.pixels[8 * 40 + 8], 0xFF);
+    }
+}
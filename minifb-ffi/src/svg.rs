@@ -0,0 +1,440 @@
+//! SVG export of retained display lists
+//!
+//! The exported file is built from the same items the canvas renders, so the
+//! window and the vector output cannot drift apart. Coordinates stay in
+//! world units. Strokes use `vector-effect="non-scaling-stroke"` to keep the
+//! one-pixel look at any zoom, and the items of each layer are gathered into
+//! one `<g>` carrying the layer id, in the order the layers first appear,
+//! hidden with `display="none"` when the layer is switched off. Points with
+//! non-finite coordinates are left out rather than written as `NaN`.
+
+use crate::bitmap_font::GLYPH_HEIGHT;
+use crate::display_list::{Item, ItemKind, MiniFBDisplayList};
+use crate::image::{MiniFBFilter, MiniFBImage};
+use crate::set_last_error;
+use crate::text::{MiniFBFont, MiniFBTextAlign, MiniFBTextBaseline, MiniFBTextStyle};
+use std::ffi::CStr;
+use std::fmt::Write;
+use std::os::raw::c_char;
+
+/// SVG export options
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MiniFBSvgOptions {
+    /// viewBox in world units; a non-positive width or height, or any
+    /// non-finite value, fits the bounds of all items instead
+    pub min_x: f64,
+    pub min_y: f64,
+    pub width: f64,
+    pub height: f64,
+    /// Font size of text elements, in world units
+    pub font_size: f64,
+    /// Export items on hidden layers too (inside `display="none"` groups)
+    pub include_hidden: bool,
+}
+
+/// Create default SVG options (fit to content, 8-unit text, visible layers only)
+#[no_mangle]
+pub extern "C" fn minifb_svg_options_default() -> MiniFBSvgOptions {
+    MiniFBSvgOptions {
+        min_x: 0.0,
+        min_y: 0.0,
+        width: 0.0,
+        height: 0.0,
+        font_size: 8.0,
+        include_hidden: false,
+    }
+}
+
+fn hex(color: u32) -> String {
+    format!("#{:06x}", color & 0xFFFFFF)
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 63] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn finite(&(x, y): &(f64, f64)) -> bool {
+    x.is_finite() && y.is_finite()
+}
+
+fn path_data(rings: &[Vec<(f64, f64)>], close: bool) -> String {
+    let mut d = String::new();
+    for ring in rings {
+        for (i, (x, y)) in ring.iter().filter(|p| finite(p)).enumerate() {
+            let _ = write!(d, "{}{} {} ", if i == 0 { 'M' } else { 'L' }, x, y);
+        }
+        if close && ring.iter().any(finite) {
+            d.push_str("Z ");
+        }
+    }
+    d.pop();
+    d
+}
+
+/// Background box of a text item in world units: x, y, width, height
+///
+/// The canvas measures text with the builtin font in pixels; the box uses
+/// the same metrics with one glyph height scaled to `font_size`.
+fn text_box(
+    text: &str,
+    anchor: (f64, f64),
+    style: &MiniFBTextStyle,
+    font_size: f64,
+) -> (f64, f64, f64, f64) {
+    let unit = font_size / GLYPH_HEIGHT as f64;
+    let (width, height) = MiniFBFont::builtin(1).measure(text);
+    let (width, height) = (width as f64 * unit, height as f64 * unit);
+    let left = match style.align {
+        MiniFBTextAlign::AlignLeft => anchor.0,
+        MiniFBTextAlign::AlignCenter => anchor.0 - width / 2.0,
+        MiniFBTextAlign::AlignRight => anchor.0 - width,
+    };
+    let top = match style.baseline {
+        MiniFBTextBaseline::BaselineTop => anchor.1,
+        MiniFBTextBaseline::BaselineMiddle => anchor.1 - height / 2.0,
+        MiniFBTextBaseline::BaselineBottom => anchor.1 - height,
+    };
+    let pad = style.padding as f64 * unit;
+    (left - pad, top - pad, width + 2.0 * pad, height + 2.0 * pad)
+}
+
+fn image_href(image: &MiniFBImage) -> Result<String, String> {
+    Ok(format!(
+        "data:image/png;base64,{}",
+        base64(&image.encode_png()?)
+    ))
+}
+
+/// Append one item's SVG element(s) to its layer's buffer
+fn write_item(out: &mut String, item: &Item, opts: &MiniFBSvgOptions) -> Result<(), String> {
+    let stroke = format!(
+        r#"fill="none" stroke="{}" stroke-opacity="{}" stroke-width="1" vector-effect="non-scaling-stroke""#,
+        hex(item.color),
+        item.opacity
+    );
+    let _ = match &item.kind {
+        ItemKind::Segments(segments) => {
+            let rings: Vec<Vec<(f64, f64)>> = segments
+                .iter()
+                .filter(|(a, b)| finite(a) && finite(b))
+                .map(|&(a, b)| vec![a, b])
+                .collect();
+            let d = path_data(&rings, false);
+            if d.is_empty() {
+                return Ok(());
+            }
+            writeln!(out, r#"<path d="{}" {} />"#, d, stroke)
+        }
+        ItemKind::Polyline { points, closed } => {
+            let d = path_data(std::slice::from_ref(points), *closed);
+            if d.is_empty() {
+                return Ok(());
+            }
+            writeln!(out, r#"<path d="{}" {} />"#, d, stroke)
+        }
+        ItemKind::Fill(rings) => {
+            let d = path_data(rings, true);
+            if d.is_empty() {
+                return Ok(());
+            }
+            writeln!(
+                out,
+                r#"<path fill-rule="evenodd" d="{}" fill="{}" fill-opacity="{}" />"#,
+                d,
+                hex(item.color),
+                item.opacity
+            )
+        }
+        ItemKind::Text {
+            text,
+            anchor,
+            style,
+        } => {
+            if !finite(anchor) {
+                return Ok(());
+            }
+            if style.background_opacity > 0.0 {
+                let (x, y, w, h) = text_box(text, *anchor, style, opts.font_size);
+                let _ = writeln!(
+                    out,
+                    r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" fill-opacity="{}" />"#,
+                    x,
+                    y,
+                    w,
+                    h,
+                    hex(style.background),
+                    style.background_opacity.clamp(0.0, 1.0)
+                );
+            }
+            let text_anchor = match style.align {
+                MiniFBTextAlign::AlignLeft => "start",
+                MiniFBTextAlign::AlignCenter => "middle",
+                MiniFBTextAlign::AlignRight => "end",
+            };
+            let baseline = match style.baseline {
+                MiniFBTextBaseline::BaselineTop => "hanging",
+                MiniFBTextBaseline::BaselineMiddle => "middle",
+                MiniFBTextBaseline::BaselineBottom => "text-after-edge",
+            };
+            let _ = write!(
+                out,
+                r#"<text x="{}" y="{}" fill="{}" font-family="monospace" font-size="{}" text-anchor="{}" dominant-baseline="{}">"#,
+                anchor.0,
+                anchor.1,
+                hex(style.color),
+                opts.font_size,
+                text_anchor,
+                baseline
+            );
+            for (i, line) in text.split('\n').enumerate() {
+                let dy = if i == 0 { "0" } else { "1.2em" };
+                let _ = write!(
+                    out,
+                    r#"<tspan x="{}" dy="{}">{}</tspan>"#,
+                    anchor.0,
+                    dy,
+                    escape(line)
+                );
+            }
+            out.push_str("</text>\n");
+            Ok(())
+        }
+        ItemKind::Image { image, rect, opts } => {
+            if ![rect.0, rect.1, rect.2, rect.3]
+                .iter()
+                .all(|v| v.is_finite())
+            {
+                return Ok(());
+            }
+            let rendering = match opts.filter {
+                MiniFBFilter::FilterNearest => r#" style="image-rendering:pixelated""#,
+                MiniFBFilter::FilterBilinear => "",
+            };
+            writeln!(
+                out,
+                r#"<image x="{}" y="{}" width="{}" height="{}" opacity="{}" preserveAspectRatio="none"{} href="{}" />"#,
+                rect.0,
+                rect.1,
+                rect.2,
+                rect.3,
+                opts.opacity,
+                rendering,
+                image_href(image)?
+            )
+        }
+    };
+    Ok(())
+}
+
+impl MiniFBDisplayList {
+    /// World-space bounding box of all items: min_x, min_y, max_x, max_y
+    fn bounds(&self, include_hidden: bool) -> Option<(f64, f64, f64, f64)> {
+        let mut points: Vec<(f64, f64)> = Vec::new();
+        for item in &self.items {
+            if !include_hidden && !self.is_visible(item.layer) {
+                continue;
+            }
+            match &item.kind {
+                ItemKind::Segments(segments) => {
+                    points.extend(segments.iter().flat_map(|&(a, b)| [a, b]))
+                }
+                ItemKind::Polyline { points: p, .. } => points.extend(p),
+                ItemKind::Fill(rings) => points.extend(rings.iter().flatten()),
+                ItemKind::Text { anchor, .. } => points.push(*anchor),
+                ItemKind::Image { rect, .. } => {
+                    points.push((rect.0, rect.1));
+                    points.push((rect.0 + rect.2, rect.1 + rect.3));
+                }
+            }
+        }
+        points
+            .iter()
+            .filter(|p| finite(p))
+            .fold(None, |acc, &(x, y)| {
+                let (x0, y0, x1, y1) = acc.unwrap_or((x, y, x, y));
+                Some((x0.min(x), y0.min(y), x1.max(x), y1.max(y)))
+            })
+    }
+
+    pub(crate) fn to_svg(&self, opts: &MiniFBSvgOptions) -> Result<String, String> {
+        let view = [opts.min_x, opts.min_y, opts.width, opts.height];
+        let (min_x, min_y, width, height) =
+            if opts.width > 0.0 && opts.height > 0.0 && view.iter().all(|v| v.is_finite()) {
+                (opts.min_x, opts.min_y, opts.width, opts.height)
+            } else {
+                let (x0, y0, x1, y1) = self.bounds(opts.include_hidden).unwrap_or_default();
+                (x0, y0, (x1 - x0).max(1.0), (y1 - y0).max(1.0))
+            };
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+            min_x, min_y, width, height
+        );
+        svg.push('\n');
+
+        // One buffer per layer, in the order the layers first appear
+        let mut groups: Vec<(u32, String)> = Vec::new();
+        for item in &self.items {
+            if !self.is_visible(item.layer) && !opts.include_hidden {
+                continue;
+            }
+            let index = match groups.iter().position(|(layer, _)| *layer == item.layer) {
+                Some(index) => index,
+                None => {
+                    groups.push((item.layer, String::new()));
+                    groups.len() - 1
+                }
+            };
+            write_item(&mut groups[index].1, item, opts)?;
+        }
+        for (layer, items) in groups {
+            let display = if self.is_visible(layer) {
+                ""
+            } else {
+                r#" display="none""#
+            };
+            let _ = writeln!(
+                svg,
+                r#"<g class="layer" data-layer="{}"{}>"#,
+                layer, display
+            );
+            svg.push_str(&items);
+            svg.push_str("</g>\n");
+        }
+        svg.push_str("</svg>\n");
+        Ok(svg)
+    }
+}
+
+/// Write a display list to an SVG file
+///
+/// # Safety
+/// - `list` must be a valid pointer returned by `minifb_display_list_new`
+/// - `path` must be a valid null-terminated C string
+/// - `opts` must be a valid pointer to MiniFBSvgOptions
+///
+/// Returns 0 on success, -1 on error (see `minifb_get_last_error`)
+#[no_mangle]
+pub unsafe extern "C" fn minifb_display_list_save_svg(
+    list: *const MiniFBDisplayList,
+    path: *const c_char,
+    opts: *const MiniFBSvgOptions,
+) -> i32 {
+    if list.is_null() || path.is_null() || opts.is_null() {
+        return -1;
+    }
+    let path = match CStr::from_ptr(path).to_str() {
+        Ok(s) => s,
+        Err(_) => return -1,
+    };
+    match (*list)
+        .to_svg(&*opts)
+        .and_then(|svg| std::fs::write(path, svg).map_err(|e| e.to_string()))
+    {
+        Ok(()) => 0,
+        Err(e) => {
+            set_last_error(&format!("{}: {}", path, e));
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display_list::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_layers_become_groups() {
+        let mut list = MiniFBDisplayList::default();
+        unsafe {
+            let points = [0.0f32, 0.0, 4.0, 2.0];
+            minifb_display_list_add_segments(&mut list, 3, points.as_ptr(), 1, 0xFF0000, 1.0);
+            let triangle = [0.0f32, 0.0, 4.0, 0.0, 4.0, 2.0];
+            minifb_display_list_add_polygon(
+                &mut list,
+                7,
+                triangle.as_ptr(),
+                [3usize].as_ptr(),
+                1,
+                0x00FF00,
+                0.5,
+            );
+            minifb_display_list_set_layer_visible(&mut list, 7, false);
+        }
+        let mut opts = minifb_svg_options_default();
+        let svg = list.to_svg(&opts).unwrap();
+        assert!(svg.contains(r#"viewBox="0 0 4 2""#));
+        assert!(svg.contains(r#"<g class="layer" data-layer="3">"#));
+        assert!(svg.contains(r##"<path d="M0 0 L4 2" fill="none" stroke="#ff0000""##));
+        assert!(!svg.contains("data-layer=\"7\""));
+
+        opts.include_hidden = true;
+        let svg = list.to_svg(&opts).unwrap();
+        assert!(svg.contains(r#"<g class="layer" data-layer="7" display="none">"#));
+        assert!(svg.contains(r##"d="M0 0 L4 0 L4 2 Z" fill="#00ff00" fill-opacity="0.5""##));
+    }
+
+    #[test]
+    fn test_one_group_per_layer() {
+        let mut list = MiniFBDisplayList::default();
+        unsafe {
+            let points = [0.0f32, 0.0, 4.0, 2.0, f32::NAN, 0.0, 1.0, 1.0];
+            minifb_display_list_add_segments(&mut list, 1, points.as_ptr(), 1, 0xFF0000, 1.0);
+            minifb_display_list_add_segments(&mut list, 2, points.as_ptr(), 1, 0x00FF00, 1.0);
+            minifb_display_list_add_segments(&mut list, 1, points.as_ptr(), 2, 0x0000FF, 1.0);
+            let mut style = crate::text::minifb_text_style_default();
+            style.background = 0x102030;
+            style.background_opacity = 0.5;
+            minifb_display_list_add_text(&mut list, 1, c"ab".as_ptr(), 10.0, 20.0, &style);
+            minifb_display_list_add_text(&mut list, 2, c"x".as_ptr(), f64::NAN, 0.0, &style);
+        }
+        let svg = list.to_svg(&minifb_svg_options_default()).unwrap();
+        assert_eq!(svg.matches(r#"data-layer="1""#).count(), 1);
+        assert_eq!(svg.matches(r#"data-layer="2""#).count(), 1);
+        assert!(svg.find(r#"data-layer="1""#) < svg.find(r#"data-layer="2""#));
+        assert!(!svg.contains("NaN"));
+        assert_eq!(svg.matches(r#"<path d="M0 0 L4 2""#).count(), 3);
+        assert!(svg.contains(
+            r##"<rect x="8" y="18" width="16" height="13" fill="#102030" fill-opacity="0.5" />"##
+        ));
+        assert_eq!(svg.matches("<text").count(), 1);
+    }
+}
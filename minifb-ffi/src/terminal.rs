@@ -0,0 +1,856 @@
+//! Terminal backend: windows drawn with truecolor half-blocks or sixel
+//!
+//! Without an X server, for example over SSH, a window can live in the
+//! terminal instead. Presented buffers are scaled down to fit and drawn
+//! either as `▀` cells with a 24-bit foreground and background color (two
+//! pixels per cell, only changed cells are rewritten), or as sixel graphics
+//! on terminals that announce support for them. Keys are read from the TTY in
+//! raw mode and mouse reports in SGR mode, then mapped onto minifb keys and
+//! buttons in window coordinates. Terminals do not report key releases, so a
+//! key counts as held until it has not repeated for `KEY_HOLD`.
+
+use crate::input::{char_key, InputState};
+use crate::{set_last_error, MiniFBWindow};
+use minifb::{Key, MouseButton};
+use std::ffi::CStr;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::os::raw::c_char;
+use std::time::{Duration, Instant};
+
+/// How long a key stays down after its last press or repeat
+const KEY_HOLD: Duration = Duration::from_millis(150);
+/// How long to wait for the terminal to answer the sixel capability query
+const QUERY_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Terminal graphics mode
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiniFBTerminalMode {
+    /// Sixel if the terminal reports support for it, half-blocks otherwise
+    TerminalAuto = 0,
+    /// `▀` characters with 24-bit colors, two pixels per cell
+    TerminalHalfBlocks = 1,
+    /// Sixel graphics at the terminal's pixel resolution
+    TerminalSixel = 2,
+}
+
+/// Terminal window options
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MiniFBTerminalOptions {
+    pub mode: MiniFBTerminalMode,
+    /// Limit the image to this many columns and rows, 0 for the whole terminal
+    pub max_columns: usize,
+    pub max_rows: usize,
+    /// Enable mouse reporting (motion, buttons and wheel)
+    pub mouse: bool,
+}
+
+/// Create default terminal options (auto mode, whole terminal, mouse enabled)
+#[no_mangle]
+pub extern "C" fn minifb_terminal_options_default() -> MiniFBTerminalOptions {
+    MiniFBTerminalOptions {
+        mode: MiniFBTerminalMode::TerminalAuto,
+        max_columns: 0,
+        max_rows: 0,
+        mouse: true,
+    }
+}
+
+#[cfg(unix)]
+mod tty {
+    use std::time::Duration;
+
+    /// Raw input mode on stdin, restored on drop
+    pub(super) struct RawMode(libc::termios);
+
+    impl RawMode {
+        pub(super) fn enable() -> Option<RawMode> {
+            unsafe {
+                let mut saved: libc::termios = std::mem::zeroed();
+                if libc::isatty(libc::STDIN_FILENO) == 0
+                    || libc::tcgetattr(libc::STDIN_FILENO, &mut saved) != 0
+                {
+                    return None;
+                }
+                let mut raw = saved;
+                raw.c_iflag &= !(libc::ICRNL | libc::IXON | libc::BRKINT | libc::ISTRIP);
+                raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+                // Reads return immediately with whatever is available
+                raw.c_cc[libc::VMIN] = 0;
+                raw.c_cc[libc::VTIME] = 0;
+                if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                    return None;
+                }
+                Some(RawMode(saved))
+            }
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.0);
+            }
+        }
+    }
+
+    pub(super) fn read(buf: &mut [u8]) -> usize {
+        let n = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+        n.max(0) as usize
+    }
+
+    pub(super) fn wait_readable(timeout: Duration) -> bool {
+        let mut fd = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        unsafe { libc::poll(&mut fd, 1, timeout.as_millis() as i32) > 0 }
+    }
+
+    /// Columns, rows and size in pixels (0 if unknown) of the terminal
+    pub(super) fn size() -> Option<(usize, usize, usize, usize)> {
+        let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+        let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) } == 0;
+        (ok && ws.ws_col > 0 && ws.ws_row > 0).then_some((
+            ws.ws_col as usize,
+            ws.ws_row as usize,
+            ws.ws_xpixel as usize,
+            ws.ws_ypixel as usize,
+        ))
+    }
+}
+
+#[cfg(not(unix))]
+mod tty {
+    use std::time::Duration;
+
+    pub(super) struct RawMode;
+
+    impl RawMode {
+        pub(super) fn enable() -> Option<RawMode> {
+            None
+        }
+    }
+
+    pub(super) fn read(_buf: &mut [u8]) -> usize {
+        0
+    }
+
+    pub(super) fn wait_readable(_timeout: Duration) -> bool {
+        false
+    }
+
+    pub(super) fn size() -> Option<(usize, usize, usize, usize)> {
+        None
+    }
+}
+
+/// Wait up to `timeout` for stdin to have data to read
+pub(crate) fn wait_stdin(timeout: Duration) -> bool {
+    tty::wait_readable(timeout)
+}
+
+/// Input decoded from the TTY byte stream
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Event {
+    Key {
+        key: Key,
+        shift: bool,
+        ctrl: bool,
+        alt: bool,
+    },
+    /// SGR mouse report: button code, 1-based cell, press or release
+    Mouse {
+        code: u32,
+        column: u32,
+        row: u32,
+        press: bool,
+    },
+    /// Ctrl-C, treated like closing the window
+    Interrupt,
+}
+
+/// Key and shift/ctrl state for a byte typed on a US layout
+fn byte_key(b: u8) -> Option<(Key, bool, bool)> {
+    match b {
+        b'\r' | b'\n' => Some((Key::Enter, false, false)),
+        b'\t' => Some((Key::Tab, false, false)),
+        0x7f | 0x08 => Some((Key::Backspace, false, false)),
+        0x01..=0x1a => Some((char_key(b'a' + b - 1)?.0, false, true)),
+        _ => char_key(b).map(|(key, shift)| (key, shift, false)),
+    }
+}
+
+/// Key for the final byte of an `ESC [` or `ESC O` sequence without parameters
+fn final_key(f: u8) -> Option<Key> {
+    Some(match f {
+        b'A' => Key::Up,
+        b'B' => Key::Down,
+        b'C' => Key::Right,
+        b'D' => Key::Left,
+        b'H' => Key::Home,
+        b'F' => Key::End,
+        b'P' => Key::F1,
+        b'Q' => Key::F2,
+        b'R' => Key::F3,
+        b'S' => Key::F4,
+        b'M' => Key::NumPadEnter,
+        _ => return None,
+    })
+}
+
+/// Key for an `ESC [ n ~` sequence
+fn tilde_key(n: u32) -> Option<Key> {
+    Some(match n {
+        1 | 7 => Key::Home,
+        2 => Key::Insert,
+        3 => Key::Delete,
+        4 | 8 => Key::End,
+        5 => Key::PageUp,
+        6 => Key::PageDown,
+        11 => Key::F1,
+        12 => Key::F2,
+        13 => Key::F3,
+        14 => Key::F4,
+        15 => Key::F5,
+        17 => Key::F6,
+        18 => Key::F7,
+        19 => Key::F8,
+        20 => Key::F9,
+        21 => Key::F10,
+        23 => Key::F11,
+        24 => Key::F12,
+        25 => Key::F13,
+        26 => Key::F14,
+        28 => Key::F15,
+        _ => return None,
+    })
+}
+
+fn csi_event(params: &[u8], f: u8) -> Option<Event> {
+    let text = std::str::from_utf8(params).ok()?;
+    if let Some(mouse) = text.strip_prefix('<') {
+        let mut fields = mouse.split(';').map(|v| v.parse::<u32>().ok());
+        let (code, column, row) = (fields.next()??, fields.next()??, fields.next()??);
+        return matches!(f, b'M' | b'm').then_some(Event::Mouse {
+            code,
+            column,
+            row,
+            press: f == b'M',
+        });
+    }
+    let numbers: Vec<u32> = text.split(';').map(|v| v.parse().unwrap_or(1)).collect();
+    // xterm modifier parameter: 1 + (shift | alt << 1 | ctrl << 2)
+    let mods = numbers.get(1).copied().unwrap_or(1).saturating_sub(1);
+    let (key, back_tab) = match f {
+        b'~' => (tilde_key(numbers[0])?, false),
+        b'Z' => (Key::Tab, true),
+        _ => (final_key(f)?, false),
+    };
+    Some(Event::Key {
+        key,
+        shift: back_tab || mods & 1 != 0,
+        alt: mods & 2 != 0,
+        ctrl: mods & 4 != 0,
+    })
+}
+
+/// Decode complete events from `bytes`, returning how many bytes were used;
+/// an unfinished escape sequence at the end is left for the next read
+fn parse_input(bytes: &[u8], events: &mut Vec<Event>) -> usize {
+    let key_event = |b: u8, alt: bool| {
+        byte_key(b).map(|(key, shift, ctrl)| Event::Key {
+            key,
+            shift,
+            ctrl,
+            alt,
+        })
+    };
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], bytes.get(i + 1)) {
+            (0x1b, None) => {
+                events.push(Event::Key {
+                    key: Key::Escape,
+                    shift: false,
+                    ctrl: false,
+                    alt: false,
+                });
+                i += 1;
+            }
+            (0x1b, Some(b'[')) => {
+                let rest = &bytes[i + 2..];
+                let Some(len) = rest.iter().position(|b| (0x40..=0x7e).contains(b)) else {
+                    break;
+                };
+                events.extend(csi_event(&rest[..len], rest[len]));
+                i += 3 + len;
+            }
+            (0x1b, Some(b'O')) => {
+                let Some(&f) = bytes.get(i + 2) else {
+                    break;
+                };
+                events.extend(final_key(f).map(|key| Event::Key {
+                    key,
+                    shift: false,
+                    ctrl: false,
+                    alt: false,
+                }));
+                i += 3;
+            }
+            (0x1b, Some(&b)) => {
+                events.extend(key_event(b, true));
+                i += 2;
+            }
+            (0x03, _) => {
+                events.push(Event::Interrupt);
+                i += 1;
+            }
+            (b, _) => {
+                events.extend(key_event(b, false));
+                i += 1;
+            }
+        }
+    }
+    i
+}
+
+/// Box-filter `src` down to `dw` x `dh`
+fn downscale(src: &[u32], sw: usize, sh: usize, dw: usize, dh: usize) -> Vec<u32> {
+    if (sw, sh) == (dw, dh) {
+        return src[..sw * sh].to_vec();
+    }
+    let span = |i: usize, s: usize, d: usize| {
+        let start = i * s / d;
+        start..((i + 1) * s / d).max(start + 1)
+    };
+    let mut out = Vec::with_capacity(dw * dh);
+    for y in 0..dh {
+        let rows = span(y, sh, dh);
+        for x in 0..dw {
+            let columns = span(x, sw, dw);
+            let mut sum = [0u32; 3];
+            for row in rows.clone() {
+                for &p in &src[row * sw + columns.start..row * sw + columns.end] {
+                    sum[0] += (p >> 16) & 0xFF;
+                    sum[1] += (p >> 8) & 0xFF;
+                    sum[2] += p & 0xFF;
+                }
+            }
+            let n = (rows.len() * columns.len()) as u32;
+            out.push(((sum[0] / n) << 16) | ((sum[1] / n) << 8) | (sum[2] / n));
+        }
+    }
+    out
+}
+
+fn sgr_color(out: &mut String, layer: u8, color: u32) {
+    let _ = write!(
+        out,
+        "\x1b[{};2;{};{};{}m",
+        layer,
+        (color >> 16) & 0xFF,
+        (color >> 8) & 0xFF,
+        color & 0xFF
+    );
+}
+
+/// Draw an image as half-block cells, skipping cells equal in `previous`
+fn encode_half_blocks(
+    out: &mut String,
+    image: &[u32],
+    w: usize,
+    h: usize,
+    previous: Option<&[u32]>,
+) {
+    let cell = |pixels: &[u32], column: usize, row: usize| {
+        let top = pixels[2 * row * w + column];
+        let bottom = if 2 * row + 1 < h {
+            pixels[(2 * row + 1) * w + column]
+        } else {
+            0
+        };
+        (top, bottom)
+    };
+    let (mut fg, mut bg) = (None, None);
+    for row in 0..h.div_ceil(2) {
+        let mut cursor_here = false;
+        for column in 0..w {
+            let (top, bottom) = cell(image, column, row);
+            if previous.is_some_and(|p| cell(p, column, row) == (top, bottom)) {
+                cursor_here = false;
+                continue;
+            }
+            if !cursor_here {
+                let _ = write!(out, "\x1b[{};{}H", row + 1, column + 1);
+                cursor_here = true;
+            }
+            if bg != Some(bottom) {
+                sgr_color(out, 48, bottom);
+                bg = Some(bottom);
+            }
+            if top == bottom {
+                out.push(' ');
+            } else {
+                if fg != Some(top) {
+                    sgr_color(out, 38, top);
+                    fg = Some(top);
+                }
+                out.push('▀');
+            }
+        }
+    }
+    out.push_str("\x1b[0m");
+}
+
+/// Draw an image as sixel graphics at the cursor, with colors on a 6x6x6 cube
+fn encode_sixel(out: &mut String, image: &[u32], w: usize, h: usize) {
+    let level = |c: u32| (((c & 0xFF) * 5 + 127) / 255) as usize;
+    let indices: Vec<u8> = image
+        .iter()
+        .map(|&p| (level(p >> 16) * 36 + level(p >> 8) * 6 + level(p)) as u8)
+        .collect();
+    let mut used = [false; 216];
+    for &i in &indices {
+        used[i as usize] = true;
+    }
+    let _ = write!(out, "\x1bP0;0;0q\"1;1;{};{}", w, h);
+    for (i, _) in used.iter().enumerate().filter(|(_, &u)| u) {
+        let _ = write!(
+            out,
+            "#{};2;{};{};{}",
+            i,
+            i / 36 * 20,
+            i / 6 % 6 * 20,
+            i % 6 * 20
+        );
+    }
+    let mut bits = vec![0u8; w];
+    for band in (0..h).step_by(6) {
+        let rows = band..(band + 6).min(h);
+        let mut colors = [false; 216];
+        for y in rows.clone() {
+            for &i in &indices[y * w..(y + 1) * w] {
+                colors[i as usize] = true;
+            }
+        }
+        let mut first = true;
+        for color in (0..216).filter(|&c| colors[c]) {
+            bits.fill(0);
+            for y in rows.clone() {
+                for (x, &i) in indices[y * w..(y + 1) * w].iter().enumerate() {
+                    if i as usize == color {
+                        bits[x] |= 1 << (y - band);
+                    }
+                }
+            }
+            if !first {
+                out.push('$');
+            }
+            first = false;
+            let _ = write!(out, "#{}", color);
+            // Run-length encode columns with the same six bits
+            let mut x = 0;
+            while x < w {
+                let run = bits[x..].iter().take_while(|&&b| b == bits[x]).count();
+                let ch = (63 + bits[x]) as char;
+                if run > 3 {
+                    let _ = write!(out, "!{}{}", run, ch);
+                } else {
+                    (0..run).for_each(|_| out.push(ch));
+                }
+                x += run;
+            }
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+}
+
+/// What is currently on screen, for mouse mapping and redraw decisions
+struct Screen {
+    terminal: (usize, usize),
+    image: Vec<u32>,
+    image_size: (usize, usize),
+    /// Size of one terminal cell in image pixels
+    cell: (f32, f32),
+}
+
+pub(crate) struct Terminal {
+    /// Logical window size; mouse positions are reported in this space
+    pub(crate) size: (usize, usize),
+    opts: MiniFBTerminalOptions,
+    sixel: bool,
+    raw: Option<tty::RawMode>,
+    open: bool,
+    pending: Vec<u8>,
+    held: Vec<(Key, Instant)>,
+    screen: Option<Screen>,
+}
+
+fn write_out(text: &str) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(text.as_bytes())?;
+    stdout.flush()
+}
+
+impl Terminal {
+    pub(crate) fn new(
+        title: &str,
+        width: usize,
+        height: usize,
+        opts: MiniFBTerminalOptions,
+    ) -> Self {
+        let raw = tty::RawMode::enable();
+        let mut terminal = Terminal {
+            size: (width, height),
+            opts,
+            sixel: opts.mode == MiniFBTerminalMode::TerminalSixel,
+            open: true,
+            pending: Vec::new(),
+            held: Vec::new(),
+            screen: None,
+            raw,
+        };
+        if opts.mode == MiniFBTerminalMode::TerminalAuto && terminal.raw.is_some() {
+            terminal.sixel = terminal.query_sixel();
+        }
+        // Alternate screen, hidden cursor, optional SGR mouse reporting
+        let mut setup = String::from("\x1b[?1049h\x1b[?25l\x1b[2J");
+        if opts.mouse && terminal.raw.is_some() {
+            setup.push_str("\x1b[?1003h\x1b[?1006h");
+        }
+        let _ = write_out(&setup);
+        terminal.set_title(title);
+        terminal
+    }
+
+    /// Ask for the primary device attributes; attribute 4 means sixel
+    fn query_sixel(&mut self) -> bool {
+        if write_out("\x1b[c").is_err() {
+            return false;
+        }
+        let deadline = Instant::now() + QUERY_TIMEOUT;
+        let mut reply = Vec::new();
+        let mut buf = [0u8; 256];
+        let found = loop {
+            if let Some(start) = reply.windows(3).position(|w| w == b"\x1b[?") {
+                if let Some(len) = reply[start..].iter().position(|&b| b == b'c') {
+                    break Some((start, start + len + 1));
+                }
+            }
+            let now = Instant::now();
+            if now >= deadline || !tty::wait_readable(deadline - now) {
+                break None;
+            }
+            let n = tty::read(&mut buf);
+            reply.extend_from_slice(&buf[..n]);
+        };
+        let Some((start, end)) = found else {
+            self.pending.extend_from_slice(&reply);
+            return false;
+        };
+        let sixel = reply[start + 3..end - 1]
+            .split(|&b| b == b';')
+            .any(|p| p == b"4");
+        reply.drain(start..end);
+        self.pending.extend_from_slice(&reply);
+        sixel
+    }
+
+    pub(crate) fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Whether input is read from stdin, which is only done for a TTY
+    pub(crate) fn reads_stdin(&self) -> bool {
+        self.raw.is_some()
+    }
+
+    pub(crate) fn set_title(&mut self, title: &str) {
+        let title: String = title.chars().filter(|c| !c.is_control()).collect();
+        let _ = write_out(&format!("\x1b]2;{}\x07", title));
+    }
+
+    /// Map a 1-based cell to logical window coordinates
+    fn cell_to_window(&self, column: u32, row: u32) -> Option<(f32, f32)> {
+        let screen = self.screen.as_ref()?;
+        let x = (column as f32 - 0.5) * screen.cell.0;
+        let y = (row as f32 - 0.5) * screen.cell.1;
+        Some((
+            x * self.size.0 as f32 / screen.image_size.0 as f32,
+            y * self.size.1 as f32 / screen.image_size.1 as f32,
+        ))
+    }
+
+    fn apply(&mut self, input: &mut InputState, event: Event, now: Instant) {
+        match event {
+            Event::Key {
+                key,
+                shift,
+                ctrl,
+                alt,
+            } => {
+                let modifiers = [
+                    (shift, Key::LeftShift),
+                    (ctrl, Key::LeftCtrl),
+                    (alt, Key::LeftAlt),
+                ];
+                for key in modifiers.iter().filter(|m| m.0).map(|m| m.1).chain([key]) {
+                    input.key(key, true);
+                    self.held.retain(|&(k, _)| k != key);
+                    self.held.push((key, now));
+                }
+            }
+            Event::Mouse {
+                code,
+                column,
+                row,
+                press,
+            } => {
+                if let Some(pos) = self.cell_to_window(column, row) {
+                    input.mouse_move(Some(pos));
+                }
+                if code & 64 != 0 {
+                    match code & 3 {
+                        0 => input.scroll(0.0, 1.0),
+                        1 => input.scroll(0.0, -1.0),
+                        2 => input.scroll(1.0, 0.0),
+                        _ => input.scroll(-1.0, 0.0),
+                    }
+                } else if code & 32 == 0 {
+                    let button = match code & 3 {
+                        0 => MouseButton::Left,
+                        1 => MouseButton::Middle,
+                        2 => MouseButton::Right,
+                        _ => return,
+                    };
+                    input.button(button, press);
+                }
+            }
+            Event::Interrupt => self.open = false,
+        }
+    }
+
+    /// Feed keys and mouse reports typed since the last call into `input`
+    pub(crate) fn read_input(&mut self, input: &mut InputState) {
+        let now = Instant::now();
+        if self.raw.is_some() {
+            let mut buf = [0u8; 1024];
+            loop {
+                let n = tty::read(&mut buf);
+                if n == 0 {
+                    break;
+                }
+                self.pending.extend_from_slice(&buf[..n]);
+            }
+        }
+        let mut events = Vec::new();
+        let used = parse_input(&self.pending, &mut events);
+        self.pending.drain(..used);
+        for event in events {
+            self.apply(input, event, now);
+        }
+
+        let (held, released): (Vec<_>, Vec<_>) = self
+            .held
+            .drain(..)
+            .partition(|&(_, t)| now.duration_since(t) < KEY_HOLD);
+        self.held = held;
+        for (key, _) in released {
+            input.key(key, false);
+        }
+    }
+
+    /// Draw a buffer scaled down to fit the terminal
+    pub(crate) fn draw(
+        &mut self,
+        buffer: &[u32],
+        width: usize,
+        height: usize,
+    ) -> std::io::Result<()> {
+        let (mut columns, mut rows, xpixel, ypixel) = tty::size().unwrap_or((80, 24, 0, 0));
+        if self.opts.max_columns > 0 {
+            columns = columns.min(self.opts.max_columns);
+        }
+        if self.opts.max_rows > 0 {
+            rows = rows.min(self.opts.max_rows);
+        }
+        let cell = if !self.sixel {
+            (1.0, 2.0)
+        } else if xpixel > 0 && ypixel > 0 {
+            (xpixel as f32 / columns as f32, ypixel as f32 / rows as f32)
+        } else {
+            (10.0, 20.0)
+        };
+        // Sixel images scroll the screen when they touch the last row
+        let rows_used = if self.sixel {
+            rows.saturating_sub(1).max(1)
+        } else {
+            rows
+        };
+        let target_w = columns as f32 * cell.0;
+        let target_h = rows_used as f32 * cell.1;
+        let scale = (target_w / width as f32)
+            .min(target_h / height as f32)
+            .min(1.0);
+        let w = ((width as f32 * scale) as usize).max(1);
+        let h = ((height as f32 * scale) as usize).max(1);
+        let image = downscale(buffer, width, height, w, h);
+
+        let previous = self
+            .screen
+            .take()
+            .filter(|s| s.terminal == (columns, rows) && s.image_size == (w, h));
+        let mut out = String::new();
+        if previous.is_none() {
+            out.push_str("\x1b[0m\x1b[2J");
+        }
+        if self.sixel {
+            if previous.as_ref().is_none_or(|p| p.image != image) {
+                out.push_str("\x1b[H");
+                encode_sixel(&mut out, &image, w, h);
+            }
+        } else {
+            encode_half_blocks(
+                &mut out,
+                &image,
+                w,
+                h,
+                previous.as_ref().map(|p| &p.image[..]),
+            );
+        }
+        self.screen = Some(Screen {
+            terminal: (columns, rows),
+            image,
+            image_size: (w, h),
+            cell,
+        });
+        write_out(&out)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = write_out("\x1b[?1003l\x1b[?1006l\x1b[0m\x1b[?25h\x1b[?1049l");
+    }
+}
+
+/// Create a window drawn in the terminal on stdout, reading input from stdin
+///
+/// `width` and `height` are the logical window size: `minifb_window_get_size`
+/// returns it and mouse positions are reported in it, while presented
+/// buffers are scaled down to fit the terminal. All window functions work on
+/// the returned handle; the ones that move or style a desktop window do
+/// nothing. Ctrl-C closes the window instead of sending SIGINT.
+///
+/// Setting the environment variable `MINIFB_FFI_BACKEND=terminal` makes
+/// `minifb_window_new` create terminal windows with default options.
+///
+/// # Safety
+/// - `name` must be a valid null-terminated C string
+/// - `opts` must be a valid pointer to MiniFBTerminalOptions
+///
+/// Returns null on failure
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_new_terminal(
+    name: *const c_char,
+    width: usize,
+    height: usize,
+    opts: *const MiniFBTerminalOptions,
+) -> *mut MiniFBWindow {
+    if name.is_null() || opts.is_null() {
+        return std::ptr::null_mut();
+    }
+    let name = match CStr::from_ptr(name).to_str() {
+        Ok(s) => s,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    if width == 0 || height == 0 {
+        set_last_error("window dimensions must be non-zero");
+        return std::ptr::null_mut();
+    }
+    let terminal = Terminal::new(name, width, height, *opts);
+    Box::into_raw(Box::new(MiniFBWindow::with_backend(
+        crate::backend::Backend::new(crate::backend::Device::Terminal(terminal)),
+        width,
+        height,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_keys_and_mouse() {
+        let mut events = Vec::new();
+        let input = b"aQ\x1b[A\x1b[1;5C\x1b[5~\x1bOP\x1bx\x1b[<0;3;2M\x1b[<64;3;2M\x1b[1;";
+        let used = parse_input(input, &mut events);
+        // The unfinished sequence at the end is kept for the next read
+        assert_eq!(used, input.len() - 4);
+        let key = |key, shift, ctrl, alt| Event::Key {
+            key,
+            shift,
+            ctrl,
+            alt,
+        };
+        assert_eq!(
+            events,
+            [
+                key(Key::A, false, false, false),
+                key(Key::Q, true, false, false),
+                key(Key::Up, false, false, false),
+                key(Key::Right, false, true, false),
+                key(Key::PageUp, false, false, false),
+                key(Key::F1, false, false, false),
+                key(Key::X, false, false, true),
+                Event::Mouse {
+                    code: 0,
+                    column: 3,
+                    row: 2,
+                    press: true
+                },
+                Event::Mouse {
+                    code: 64,
+                    column: 3,
+                    row: 2,
+                    press: true
+                },
+            ]
+        );
+        events.clear();
+        parse_input(b"\x03\x1b", &mut events);
+        assert_eq!(
+            events,
+            [Event::Interrupt, key(Key::Escape, false, false, false)]
+        );
+    }
+
+    #[test]
+    fn test_half_blocks_and_sixel() {
+        let image = [0xFF0000, 0x0000FF, 0xFF0000, 0x00FF00];
+        let mut out = String::new();
+        encode_half_blocks(&mut out, &image, 2, 2, None);
+        assert_eq!(
+            out,
+            "\x1b[1;1H\x1b[48;2;255;0;0m \x1b[48;2;0;255;0m\x1b[38;2;0;0;255m▀\x1b[0m"
+        );
+        // Only the changed cell is redrawn
+        let changed = [0xFF0000, 0x0000FF, 0xFF0000, 0x0000FF];
+        out.clear();
+        encode_half_blocks(&mut out, &changed, 2, 2, Some(&image));
+        assert_eq!(out, "\x1b[1;2H\x1b[48;2;0;0;255m \x1b[0m");
+
+        out.clear();
+        encode_sixel(&mut out, &[0xFFFFFF; 8], 8, 1);
+        assert_eq!(out, "\x1bP0;0;0q\"1;1;8;1#215;2;100;100;100#215!8@-\x1b\\");
+        assert_eq!(
+            downscale(&[0, 0xFEFEFE, 0, 0xFEFEFE], 2, 2, 1, 1),
+            [0x7F7F7F]
+        );
+    }
+}
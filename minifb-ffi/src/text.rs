@@ -0,0 +1,415 @@
+//! Text rendering into canvases for HUD overlays
+//!
+//! The embedded bitmap font needs no files; TrueType fonts are loaded with
+//! fontdue (behind the `ttf` feature) and rasterized glyphs are cached per
+//! font. Strings may contain newlines; each line is aligned independently.
+
+use crate::bitmap_font;
+use crate::canvas::MiniFBCanvas;
+use crate::set_last_error;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+#[cfg(feature = "ttf")]
+use std::collections::HashMap;
+
+/// Horizontal alignment relative to the anchor point
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiniFBTextAlign {
+    AlignLeft = 0,
+    AlignCenter = 1,
+    AlignRight = 2,
+}
+
+/// Vertical alignment of the text block relative to the anchor point
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiniFBTextBaseline {
+    BaselineTop = 0,
+    BaselineMiddle = 1,
+    BaselineBottom = 2,
+}
+
+/// Text drawing style
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MiniFBTextStyle {
+    /// 0RGB text color
+    pub color: u32,
+    /// 0RGB color of the box drawn behind the text
+    pub background: u32,
+    /// Opacity of the background box, 0.0 disables it
+    pub background_opacity: f32,
+    /// Padding around the text inside the background box, in pixels
+    pub padding: i32,
+    pub align: MiniFBTextAlign,
+    pub baseline: MiniFBTextBaseline,
+}
+
+/// Create the default text style (white, top-left anchored, no background)
+#[no_mangle]
+pub extern "C" fn minifb_text_style_default() -> MiniFBTextStyle {
+    MiniFBTextStyle {
+        color: 0x00FFFFFF,
+        background: 0x00000000,
+        background_opacity: 0.0,
+        padding: 2,
+        align: MiniFBTextAlign::AlignLeft,
+        baseline: MiniFBTextBaseline::BaselineTop,
+    }
+}
+
+#[cfg(feature = "ttf")]
+struct TtfFont {
+    font: fontdue::Font,
+    px: f32,
+    cache: HashMap<char, (fontdue::Metrics, Vec<u8>)>,
+}
+
+enum FontKind {
+    Bitmap {
+        scale: i64,
+    },
+    #[cfg(feature = "ttf")]
+    Ttf(Box<TtfFont>),
+}
+
+/// Opaque font handle
+pub struct MiniFBFont {
+    kind: FontKind,
+}
+
+impl MiniFBFont {
+    pub(crate) fn builtin(scale: u32) -> Self {
+        MiniFBFont {
+            kind: FontKind::Bitmap {
+                scale: scale.max(1) as i64,
+            },
+        }
+    }
+
+    /// Distance between consecutive baselines
+    pub(crate) fn line_height(&self) -> i64 {
+        match &self.kind {
+            FontKind::Bitmap { scale } => (bitmap_font::GLYPH_HEIGHT as i64 + 1) * scale,
+            #[cfg(feature = "ttf")]
+            FontKind::Ttf(ttf) => ttf
+                .font
+                .horizontal_line_metrics(ttf.px)
+                .map(|m| m.new_line_size.ceil() as i64)
+                .unwrap_or(ttf.px.ceil() as i64),
+        }
+    }
+
+    /// Distance from the top of a line to its baseline
+    fn ascent(&self) -> i64 {
+        match &self.kind {
+            FontKind::Bitmap { scale } => (bitmap_font::GLYPH_HEIGHT as i64 - 1) * scale,
+            #[cfg(feature = "ttf")]
+            FontKind::Ttf(ttf) => ttf
+                .font
+                .horizontal_line_metrics(ttf.px)
+                .map(|m| m.ascent.ceil() as i64)
+                .unwrap_or(ttf.px.ceil() as i64),
+        }
+    }
+
+    #[cfg_attr(not(feature = "ttf"), allow(unused_variables))]
+    fn advance(&self, ch: char) -> f32 {
+        match &self.kind {
+            FontKind::Bitmap { scale } => ((bitmap_font::GLYPH_WIDTH as i64 + 1) * scale) as f32,
+            #[cfg(feature = "ttf")]
+            FontKind::Ttf(ttf) => match ttf.cache.get(&ch) {
+                Some((metrics, _)) => metrics.advance_width,
+                None => ttf.font.metrics(ch, ttf.px).advance_width,
+            },
+        }
+    }
+
+    pub(crate) fn line_width(&self, line: &str) -> i64 {
+        line.chars().map(|ch| self.advance(ch)).sum::<f32>().ceil() as i64
+    }
+
+    /// Width and height of a possibly multi-line string
+    pub(crate) fn measure(&self, text: &str) -> (i64, i64) {
+        let width = text
+            .split('\n')
+            .map(|l| self.line_width(l))
+            .max()
+            .unwrap_or(0);
+        let lines = text.split('\n').count() as i64;
+        (width, lines * self.line_height())
+    }
+
+    fn draw_glyph(
+        &mut self,
+        canvas: &mut MiniFBCanvas,
+        ch: char,
+        x: f32,
+        baseline: i64,
+        color: u32,
+    ) {
+        match &mut self.kind {
+            FontKind::Bitmap { scale } => {
+                let scale = *scale;
+                let top = baseline - (bitmap_font::GLYPH_HEIGHT as i64 - 1) * scale;
+                let x = x.round() as i64;
+                for (row, bits) in bitmap_font::glyph(ch).iter().enumerate() {
+                    for col in 0..bitmap_font::GLYPH_WIDTH {
+                        if bits & (1 << (bitmap_font::GLYPH_WIDTH - 1 - col)) != 0 {
+                            canvas.fill_rect(
+                                x + col as i64 * scale,
+                                top + row as i64 * scale,
+                                scale,
+                                scale,
+                                color,
+                                255,
+                            );
+                        }
+                    }
+                }
+            }
+            #[cfg(feature = "ttf")]
+            FontKind::Ttf(ttf) => {
+                let TtfFont { font, px, cache } = &mut **ttf;
+                let (metrics, coverage) =
+                    cache.entry(ch).or_insert_with(|| font.rasterize(ch, *px));
+                let left = (x + metrics.xmin as f32).round() as i64;
+                let top = baseline - metrics.height as i64 - metrics.ymin as i64;
+                for row in 0..metrics.height {
+                    for col in 0..metrics.width {
+                        let alpha = coverage[row * metrics.width + col];
+                        canvas.blend(left + col as i64, top + row as i64, color, alpha);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Draw text anchored at (x, y) according to the style's alignment
+    pub(crate) fn draw(
+        &mut self,
+        canvas: &mut MiniFBCanvas,
+        text: &str,
+        x: i64,
+        y: i64,
+        style: &MiniFBTextStyle,
+    ) {
+        let (width, height) = self.measure(text);
+        let top = match style.baseline {
+            MiniFBTextBaseline::BaselineTop => y,
+            MiniFBTextBaseline::BaselineMiddle => y - height / 2,
+            MiniFBTextBaseline::BaselineBottom => y - height,
+        };
+        let block_left = match style.align {
+            MiniFBTextAlign::AlignLeft => x,
+            MiniFBTextAlign::AlignCenter => x - width / 2,
+            MiniFBTextAlign::AlignRight => x - width,
+        };
+
+        if style.background_opacity > 0.0 {
+            let alpha = (style.background_opacity.clamp(0.0, 1.0) * 255.0).round() as u8;
+            let pad = style.padding as i64;
+            canvas.fill_rect(
+                block_left - pad,
+                top - pad,
+                width + 2 * pad,
+                height + 2 * pad,
+                style.background,
+                alpha,
+            );
+        }
+
+        let line_height = self.line_height();
+        let ascent = self.ascent();
+        for (i, line) in text.split('\n').enumerate() {
+            let line_width = self.line_width(line);
+            let mut pen = match style.align {
+                MiniFBTextAlign::AlignLeft => block_left,
+                MiniFBTextAlign::AlignCenter => block_left + (width - line_width) / 2,
+                MiniFBTextAlign::AlignRight => block_left + width - line_width,
+            } as f32;
+            let baseline = top + i as i64 * line_height + ascent;
+            for ch in line.chars() {
+                if !ch.is_whitespace() {
+                    self.draw_glyph(canvas, ch, pen, baseline, style.color);
+                }
+                pen += self.advance(ch);
+            }
+        }
+    }
+}
+
+/// Create a font using the embedded 5x8 bitmap glyphs, magnified by an integer `scale`
+#[no_mangle]
+pub extern "C" fn minifb_font_builtin(scale: u32) -> *mut MiniFBFont {
+    Box::into_raw(Box::new(MiniFBFont::builtin(scale)))
+}
+
+/// Load a TrueType/OpenType font from a file, rendered at `size_px` pixels
+///
+/// # Safety
+/// - `path` must be a valid null-terminated C string
+///
+/// Returns null on failure (see `minifb_get_last_error`)
+#[no_mangle]
+pub unsafe extern "C" fn minifb_font_load_ttf(
+    path: *const c_char,
+    size_px: f32,
+) -> *mut MiniFBFont {
+    if path.is_null() {
+        return std::ptr::null_mut();
+    }
+    let path = match CStr::from_ptr(path).to_str() {
+        Ok(s) => s,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    load_ttf(path, size_px)
+}
+
+#[cfg(feature = "ttf")]
+fn load_ttf(path: &str, size_px: f32) -> *mut MiniFBFont {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) => {
+            set_last_error(&format!("{}: {}", path, e));
+            return std::ptr::null_mut();
+        }
+    };
+    match fontdue::Font::from_bytes(data, fontdue::FontSettings::default()) {
+        Ok(font) => Box::into_raw(Box::new(MiniFBFont {
+            kind: FontKind::Ttf(Box::new(TtfFont {
+                font,
+                px: size_px,
+                cache: HashMap::new(),
+            })),
+        })),
+        Err(e) => {
+            set_last_error(&format!("{}: {}", path, e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[cfg(not(feature = "ttf"))]
+fn load_ttf(_path: &str, _size_px: f32) -> *mut MiniFBFont {
+    set_last_error("minifb-ffi was built without the `ttf` feature");
+    std::ptr::null_mut()
+}
+
+/// Free a font
+///
+/// # Safety
+/// - `font` must be a valid pointer returned by `minifb_font_builtin` or
+///   `minifb_font_load_ttf`, or null
+/// - After calling this function, the pointer is invalid and must not be used
+#[no_mangle]
+pub unsafe extern "C" fn minifb_font_free(font: *mut MiniFBFont) {
+    if !font.is_null() {
+        drop(Box::from_raw(font));
+    }
+}
+
+/// Draw a UTF-8 string into a canvas
+///
+/// # Safety
+/// - `canvas` must be a valid pointer returned by `minifb_canvas_new`
+/// - `font` must be a valid font pointer, or null for the built-in font at scale 1
+/// - `text` must be a valid null-terminated UTF-8 C string
+/// - `style` must be a valid pointer to MiniFBTextStyle
+///
+/// Returns 0 on success, -1 on error
+#[no_mangle]
+pub unsafe extern "C" fn minifb_canvas_draw_text(
+    canvas: *mut MiniFBCanvas,
+    font: *mut MiniFBFont,
+    text: *const c_char,
+    x: i32,
+    y: i32,
+    style: *const MiniFBTextStyle,
+) -> i32 {
+    if canvas.is_null() || text.is_null() || style.is_null() {
+        return -1;
+    }
+    let text = match CStr::from_ptr(text).to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error("text is not valid UTF-8");
+            return -1;
+        }
+    };
+    let mut builtin;
+    let font = if font.is_null() {
+        builtin = MiniFBFont::builtin(1);
+        &mut builtin
+    } else {
+        &mut *font
+    };
+    font.draw(&mut *canvas, text, x as i64, y as i64, &*style);
+    0
+}
+
+/// Measure the extents of a UTF-8 string without drawing it
+///
+/// # Safety
+/// - `font` must be a valid font pointer, or null for the built-in font at scale 1
+/// - `text` must be a valid null-terminated UTF-8 C string
+/// - `out_width` and `out_height` must be valid pointers
+///
+/// Returns 0 on success, -1 on error
+#[no_mangle]
+pub unsafe extern "C" fn minifb_text_measure(
+    font: *const MiniFBFont,
+    text: *const c_char,
+    out_width: *mut i32,
+    out_height: *mut i32,
+) -> i32 {
+    if text.is_null() || out_width.is_null() || out_height.is_null() {
+        return -1;
+    }
+    let text = match CStr::from_ptr(text).to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error("text is not valid UTF-8");
+            return -1;
+        }
+    };
+    let builtin;
+    let font = if font.is_null() {
+        builtin = MiniFBFont::builtin(1);
+        &builtin
+    } else {
+        &*font
+    };
+    let (w, h) = font.measure(text);
+    *out_width = w as i32;
+    *out_height = h as i32;
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_measure() {
+        let font = MiniFBFont::builtin(2);
+        assert_eq!(font.measure("Hi"), (24, 18));
+        assert_eq!(font.measure("abc\nd"), (36, 36));
+    }
+
+    #[test]
+    fn test_builtin_draw_and_align() {
+        let mut canvas = MiniFBCanvas::new(16, 12);
+        let mut font = MiniFBFont::builtin(1);
+        let style = MiniFBTextStyle {
+            align: MiniFBTextAlign::AlignRight,
+            ..minifb_text_style_default()
+        };
+        // 'I' is ".###." on its top row; right-aligned its cell ends at x = 16
+        font.draw(&mut canvas, "I", 16, 0, &style);
+        let row: Vec<bool> = canvas.pixels[..16].iter().map(|&p| p != 0).collect();
+        assert_eq!(&row[10..16], &[false, true, true, true, false, false]);
+    }
+}
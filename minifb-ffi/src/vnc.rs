@@ -0,0 +1,888 @@
+//! RFB (VNC) server for watching and driving windows remotely
+//!
+//! A window of any backend can be served on a TCP port. Every presented
+//! frame is scaled to the framebuffer size fixed when serving starts (the
+//! window size at that time) and handed to the connection threads, which
+//! answer update requests with the changed region in raw, zlib or tight
+//! encoding, whichever the client lists first. Key and pointer events from
+//! clients are queued and fed into the window's input at its next update, so
+//! the usual query functions see them.
+//!
+//! Only the "None" security type is offered, which is why the listener binds
+//! to localhost by default; use an SSH tunnel to reach it from elsewhere.
+
+use crate::input::{char_key, InputEvent};
+use crate::letterbox::{compose, Viewport};
+use crate::wakeup::Waker;
+use crate::{set_last_error, MiniFBWindow};
+use flate2::{Compress, Compression, FlushCompress};
+use minifb::{Key, MouseButton};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+const ENCODING_RAW: i32 = 0;
+const ENCODING_ZLIB: i32 = 6;
+const ENCODING_TIGHT: i32 = 7;
+/// Tight rectangles may not be wider than this
+const TIGHT_MAX_WIDTH: usize = 2048;
+const TIGHT_MAX_PIXELS: usize = 65536;
+
+/// VNC server options
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MiniFBVncOptions {
+    /// TCP port, 0 to let the system pick one
+    pub port: u16,
+    /// Listen on 127.0.0.1 only instead of all interfaces
+    pub localhost_only: bool,
+    /// Feed client key and pointer events into the window; false is view-only
+    pub allow_input: bool,
+}
+
+/// Create default VNC options (port 5900, localhost only, input allowed)
+#[no_mangle]
+pub extern "C" fn minifb_vnc_options_default() -> MiniFBVncOptions {
+    MiniFBVncOptions {
+        port: 5900,
+        localhost_only: true,
+        allow_input: true,
+    }
+}
+
+/// Client pixel format, always true color
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PixelFormat {
+    bits_per_pixel: u8,
+    depth: u8,
+    big_endian: bool,
+    max: [u16; 3],
+    shift: [u8; 3],
+}
+
+/// 0RGB as little-endian 32-bit words, same as the window buffers
+const SERVER_FORMAT: PixelFormat = PixelFormat {
+    bits_per_pixel: 32,
+    depth: 24,
+    big_endian: false,
+    max: [255; 3],
+    shift: [16, 8, 0],
+};
+
+impl PixelFormat {
+    fn parse(b: &[u8]) -> Self {
+        let u16_at = |i: usize| u16::from_be_bytes([b[i], b[i + 1]]);
+        PixelFormat {
+            bits_per_pixel: b[0],
+            depth: b[1],
+            big_endian: b[2] != 0,
+            max: [u16_at(4), u16_at(6), u16_at(8)],
+            shift: [b[10], b[11], b[12]],
+        }
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend([self.bits_per_pixel, self.depth, self.big_endian as u8, 1]);
+        for max in self.max {
+            out.extend(max.to_be_bytes());
+        }
+        out.extend(self.shift);
+        out.extend([0; 3]);
+    }
+
+    fn put(&self, p: u32, out: &mut Vec<u8>) {
+        let channel =
+            |i: usize, v: u32| (((v & 0xFF) * self.max[i] as u32 + 127) / 255) << self.shift[i];
+        let v = channel(0, p >> 16) | channel(1, p >> 8) | channel(2, p);
+        match (self.bits_per_pixel, self.big_endian) {
+            (8, _) => out.push(v as u8),
+            (16, false) => out.extend((v as u16).to_le_bytes()),
+            (16, true) => out.extend((v as u16).to_be_bytes()),
+            (_, false) => out.extend(v.to_le_bytes()),
+            (_, true) => out.extend(v.to_be_bytes()),
+        }
+    }
+
+    /// Tight sends 24-bit colors as three bytes instead of a full pixel
+    fn put_tight(&self, p: u32, out: &mut Vec<u8>) {
+        if self.bits_per_pixel == 32 && self.depth == 24 && self.max == [255; 3] {
+            out.extend([(p >> 16) as u8, (p >> 8) as u8, p as u8]);
+        } else {
+            self.put(p, out);
+        }
+    }
+}
+
+/// Key for an X11 keysym
+fn keysym_key(keysym: u32) -> Option<Key> {
+    if (0x20..=0x7e).contains(&keysym) {
+        return char_key(keysym as u8).map(|(key, _)| key);
+    }
+    Some(match keysym {
+        0xff08 => Key::Backspace,
+        0xff09 => Key::Tab,
+        0xff0d => Key::Enter,
+        0xff13 => Key::Pause,
+        0xff14 => Key::ScrollLock,
+        0xff1b => Key::Escape,
+        0xff50 => Key::Home,
+        0xff51 => Key::Left,
+        0xff52 => Key::Up,
+        0xff53 => Key::Right,
+        0xff54 => Key::Down,
+        0xff55 => Key::PageUp,
+        0xff56 => Key::PageDown,
+        0xff57 => Key::End,
+        0xff63 => Key::Insert,
+        0xff67 => Key::Menu,
+        0xff7f => Key::NumLock,
+        0xff8d => Key::NumPadEnter,
+        0xffaa => Key::NumPadAsterisk,
+        0xffab => Key::NumPadPlus,
+        0xffad => Key::NumPadMinus,
+        0xffae => Key::NumPadDot,
+        0xffaf => Key::NumPadSlash,
+        0xffb0 => Key::NumPad0,
+        0xffb1 => Key::NumPad1,
+        0xffb2 => Key::NumPad2,
+        0xffb3 => Key::NumPad3,
+        0xffb4 => Key::NumPad4,
+        0xffb5 => Key::NumPad5,
+        0xffb6 => Key::NumPad6,
+        0xffb7 => Key::NumPad7,
+        0xffb8 => Key::NumPad8,
+        0xffb9 => Key::NumPad9,
+        0xffbe => Key::F1,
+        0xffbf => Key::F2,
+        0xffc0 => Key::F3,
+        0xffc1 => Key::F4,
+        0xffc2 => Key::F5,
+        0xffc3 => Key::F6,
+        0xffc4 => Key::F7,
+        0xffc5 => Key::F8,
+        0xffc6 => Key::F9,
+        0xffc7 => Key::F10,
+        0xffc8 => Key::F11,
+        0xffc9 => Key::F12,
+        0xffca => Key::F13,
+        0xffcb => Key::F14,
+        0xffcc => Key::F15,
+        0xffe1 => Key::LeftShift,
+        0xffe2 => Key::RightShift,
+        0xffe3 => Key::LeftCtrl,
+        0xffe4 => Key::RightCtrl,
+        0xffe5 => Key::CapsLock,
+        0xffe7 | 0xffe9 => Key::LeftAlt,
+        0xffe8 | 0xffea => Key::RightAlt,
+        0xffeb => Key::LeftSuper,
+        0xffec => Key::RightSuper,
+        0xffff => Key::Delete,
+        _ => return None,
+    })
+}
+
+/// Rectangle: x, y, width, height
+type Rect = (usize, usize, usize, usize);
+
+#[derive(Debug, Clone, Copy)]
+struct Request {
+    incremental: bool,
+    area: Rect,
+}
+
+struct Client {
+    format: PixelFormat,
+    encodings: Vec<i32>,
+    request: Option<Request>,
+    closed: bool,
+}
+
+struct State {
+    frame: Vec<u32>,
+    /// Framebuffer size, fixed for the server's lifetime
+    size: (usize, usize),
+    /// Window size at the last present, for mapping pointer positions
+    window_size: (usize, usize),
+    serial: u64,
+    events: Vec<InputEvent>,
+    clients: HashMap<u64, Client>,
+    stop: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+    allow_input: bool,
+    /// Signalled whenever client input is queued
+    waker: Arc<Waker>,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A running server; stops and disconnects its clients when dropped
+pub(crate) struct VncServer {
+    shared: Arc<Shared>,
+    accept: Option<JoinHandle<()>>,
+    port: u16,
+}
+
+impl VncServer {
+    fn start(opts: &MiniFBVncOptions, size: (usize, usize), waker: Arc<Waker>) -> io::Result<Self> {
+        let host = if opts.localhost_only {
+            "127.0.0.1"
+        } else {
+            "0.0.0.0"
+        };
+        let listener = TcpListener::bind((host, opts.port))?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                frame: vec![0; size.0 * size.1],
+                size,
+                window_size: size,
+                serial: 0,
+                events: Vec::new(),
+                clients: HashMap::new(),
+                stop: false,
+            }),
+            changed: Condvar::new(),
+            allow_input: opts.allow_input,
+            waker,
+        });
+        let accept_shared = shared.clone();
+        let accept = std::thread::spawn(move || accept_loop(listener, accept_shared));
+        Ok(VncServer {
+            shared,
+            accept: Some(accept),
+            port,
+        })
+    }
+
+    /// Show a presented buffer to clients, stretched to the framebuffer size
+    pub(crate) fn publish(
+        &self,
+        buffer: &[u32],
+        width: usize,
+        height: usize,
+        window_size: (usize, usize),
+    ) {
+        let mut state = self.shared.lock();
+        let viewport = Viewport {
+            buffer: (width, height),
+            window: state.size,
+            image: (0, 0, state.size.0, state.size.1),
+        };
+        compose(&mut state.frame, buffer, &viewport, 0);
+        state.window_size = window_size;
+        state.serial += 1;
+        self.shared.changed.notify_all();
+    }
+
+    /// Input received since the last call
+    pub(crate) fn take_events(&self) -> Vec<InputEvent> {
+        std::mem::take(&mut self.shared.lock().events)
+    }
+}
+
+impl Drop for VncServer {
+    fn drop(&mut self) {
+        self.shared.lock().stop = true;
+        self.shared.changed.notify_all();
+        if let Some(accept) = self.accept.take() {
+            let _ = accept.join();
+        }
+    }
+}
+
+fn accept_loop(listener: TcpListener, shared: Arc<Shared>) {
+    static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(0);
+    while !shared.lock().stop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let shared = shared.clone();
+                let id = NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed);
+                std::thread::spawn(move || {
+                    let _ = serve_client(stream, &shared, id);
+                    shared.lock().clients.remove(&id);
+                });
+            }
+            Err(_) => std::thread::sleep(Duration::from_millis(50)),
+        }
+    }
+}
+
+fn read_bytes<const N: usize>(stream: &mut TcpStream) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    stream.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Protocol version and security handshake, then the initialization messages
+fn handshake(stream: &mut TcpStream, shared: &Shared) -> io::Result<()> {
+    stream.write_all(b"RFB 003.008\n")?;
+    let version: [u8; 12] = read_bytes(stream)?;
+    let minor = std::str::from_utf8(&version[8..11])
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(3);
+    if minor >= 7 {
+        // One security type: None
+        stream.write_all(&[1, 1])?;
+        if read_bytes::<1>(stream)?[0] != 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unsupported security type",
+            ));
+        }
+        if minor >= 8 {
+            stream.write_all(&0u32.to_be_bytes())?;
+        }
+    } else {
+        stream.write_all(&1u32.to_be_bytes())?;
+    }
+    let _shared_flag: [u8; 1] = read_bytes(stream)?;
+
+    let (width, height) = shared.lock().size;
+    let name = b"minifb";
+    let mut init = Vec::new();
+    init.extend((width as u16).to_be_bytes());
+    init.extend((height as u16).to_be_bytes());
+    SERVER_FORMAT.write(&mut init);
+    init.extend((name.len() as u32).to_be_bytes());
+    init.extend(name);
+    stream.write_all(&init)
+}
+
+fn serve_client(mut stream: TcpStream, shared: &Arc<Shared>, id: u64) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_nodelay(true)?;
+    handshake(&mut stream, shared)?;
+    shared.lock().clients.insert(
+        id,
+        Client {
+            format: SERVER_FORMAT,
+            encodings: vec![ENCODING_RAW],
+            request: None,
+            closed: false,
+        },
+    );
+    let mut reader = stream.try_clone()?;
+    let reader_shared = shared.clone();
+    let reader = std::thread::spawn(move || {
+        let _ = read_messages(&mut reader, &reader_shared, id);
+        if let Some(client) = reader_shared.lock().clients.get_mut(&id) {
+            client.closed = true;
+        }
+        reader_shared.changed.notify_all();
+    });
+    let result = write_updates(&mut stream, shared, id);
+    let _ = stream.shutdown(Shutdown::Both);
+    let _ = reader.join();
+    result
+}
+
+/// Handle client-to-server messages until the connection ends
+fn read_messages(stream: &mut TcpStream, shared: &Shared, id: u64) -> io::Result<()> {
+    let mut buttons = 0u8;
+    let mut pointer = None;
+    loop {
+        let [kind] = read_bytes::<1>(stream)?;
+        match kind {
+            0 => {
+                let b: [u8; 19] = read_bytes(stream)?;
+                if let Some(client) = shared.lock().clients.get_mut(&id) {
+                    client.format = PixelFormat::parse(&b[3..]);
+                    // Everything the client has is in the old format now
+                    client.request = None;
+                }
+            }
+            2 => {
+                let b: [u8; 3] = read_bytes(stream)?;
+                let mut list = vec![0; 4 * u16::from_be_bytes([b[1], b[2]]) as usize];
+                stream.read_exact(&mut list)?;
+                let encodings = list
+                    .chunks(4)
+                    .map(|e| i32::from_be_bytes([e[0], e[1], e[2], e[3]]))
+                    .collect();
+                if let Some(client) = shared.lock().clients.get_mut(&id) {
+                    client.encodings = encodings;
+                }
+            }
+            3 => {
+                let b: [u8; 9] = read_bytes(stream)?;
+                let u16_at = |i: usize| u16::from_be_bytes([b[i], b[i + 1]]) as usize;
+                let request = Request {
+                    incremental: b[0] != 0,
+                    area: (u16_at(1), u16_at(3), u16_at(5), u16_at(7)),
+                };
+                if let Some(client) = shared.lock().clients.get_mut(&id) {
+                    // A full request wins over an incremental one still pending
+                    if client.request.is_none_or(|r| r.incremental) {
+                        client.request = Some(request);
+                    }
+                }
+                shared.changed.notify_all();
+            }
+            4 => {
+                let b: [u8; 7] = read_bytes(stream)?;
+                let keysym = u32::from_be_bytes([b[3], b[4], b[5], b[6]]);
+                if let (true, Some(key)) = (shared.allow_input, keysym_key(keysym)) {
+                    shared.lock().events.push(InputEvent::Key(key, b[0] != 0));
+                    shared.waker.wake();
+                }
+            }
+            5 => {
+                let b: [u8; 5] = read_bytes(stream)?;
+                let mask = b[0];
+                let (x, y) = (
+                    u16::from_be_bytes([b[1], b[2]]) as f32,
+                    u16::from_be_bytes([b[3], b[4]]) as f32,
+                );
+                if !shared.allow_input {
+                    continue;
+                }
+                let mut state = shared.lock();
+                let scale_x = state.window_size.0 as f32 / state.size.0.max(1) as f32;
+                let scale_y = state.window_size.1 as f32 / state.size.1.max(1) as f32;
+                let pos = Some((x * scale_x, y * scale_y));
+                if pos != pointer {
+                    state.events.push(InputEvent::MouseMove(pos));
+                    pointer = pos;
+                }
+                let buttons_changed = [
+                    (1, MouseButton::Left),
+                    (2, MouseButton::Middle),
+                    (4, MouseButton::Right),
+                ];
+                for (bit, button) in buttons_changed {
+                    if (mask ^ buttons) & bit != 0 {
+                        state
+                            .events
+                            .push(InputEvent::Button(button, mask & bit != 0));
+                    }
+                }
+                // Wheel "buttons" scroll once per press
+                let wheel = [
+                    (8, 0.0, 1.0),
+                    (16, 0.0, -1.0),
+                    (32, 1.0, 0.0),
+                    (64, -1.0, 0.0),
+                ];
+                for (bit, dx, dy) in wheel {
+                    if mask & bit != 0 && buttons & bit == 0 {
+                        state.events.push(InputEvent::Scroll(dx, dy));
+                    }
+                }
+                buttons = mask;
+                if !state.events.is_empty() {
+                    shared.waker.wake();
+                }
+            }
+            6 => {
+                let b: [u8; 7] = read_bytes(stream)?;
+                let len = u32::from_be_bytes([b[3], b[4], b[5], b[6]]) as u64;
+                io::copy(&mut (&mut *stream).take(len), &mut io::sink())?;
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unknown message type",
+                ));
+            }
+        }
+    }
+}
+
+/// Compress with a persistent zlib stream, flushing so the client can decode
+fn deflate(z: &mut Compress, data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() / 2 + 64);
+    let mut input = data;
+    loop {
+        out.reserve(4096);
+        let before = z.total_in();
+        if z.compress_vec(input, &mut out, FlushCompress::Sync)
+            .is_err()
+        {
+            break;
+        }
+        input = &input[(z.total_in() - before) as usize..];
+        if input.is_empty() && out.len() < out.capacity() {
+            break;
+        }
+    }
+    out
+}
+
+/// Per-connection encoder state
+struct Encoder {
+    format: PixelFormat,
+    zlib: Compress,
+    tight: Compress,
+}
+
+impl Encoder {
+    fn pixels(&self, frame: &[u32], stride: usize, rect: Rect, tight: bool) -> Vec<u8> {
+        let (x, y, w, h) = rect;
+        let mut out = Vec::with_capacity(w * h * 4);
+        for row in y..y + h {
+            for &p in &frame[row * stride + x..][..w] {
+                if tight {
+                    self.format.put_tight(p, &mut out);
+                } else {
+                    self.format.put(p, &mut out);
+                }
+            }
+        }
+        out
+    }
+
+    /// Append one or more encoded rectangles covering `rect`, returning the count
+    fn encode(
+        &mut self,
+        out: &mut Vec<u8>,
+        frame: &[u32],
+        stride: usize,
+        rect: Rect,
+        encoding: i32,
+    ) -> u16 {
+        let header = |out: &mut Vec<u8>, (x, y, w, h): Rect, encoding: i32| {
+            for v in [x, y, w, h] {
+                out.extend((v as u16).to_be_bytes());
+            }
+            out.extend(encoding.to_be_bytes());
+        };
+        match encoding {
+            ENCODING_TIGHT => {
+                let (x, y, w, h) = rect;
+                let tile_w = w.min(TIGHT_MAX_WIDTH);
+                let tile_h = (TIGHT_MAX_PIXELS / tile_w).max(1);
+                let mut count = 0;
+                for ty in (y..y + h).step_by(tile_h) {
+                    for tx in (x..x + w).step_by(tile_w) {
+                        let tile = (tx, ty, tile_w.min(x + w - tx), tile_h.min(y + h - ty));
+                        header(out, tile, ENCODING_TIGHT);
+                        self.encode_tight(out, frame, stride, tile);
+                        count += 1;
+                    }
+                }
+                count
+            }
+            ENCODING_ZLIB => {
+                header(out, rect, ENCODING_ZLIB);
+                let pixels = self.pixels(frame, stride, rect, false);
+                let data = deflate(&mut self.zlib, &pixels);
+                out.extend((data.len() as u32).to_be_bytes());
+                out.extend(data);
+                1
+            }
+            _ => {
+                header(out, rect, ENCODING_RAW);
+                out.extend(self.pixels(frame, stride, rect, false));
+                1
+            }
+        }
+    }
+
+    fn encode_tight(&mut self, out: &mut Vec<u8>, frame: &[u32], stride: usize, rect: Rect) {
+        let (x, y, w, h) = rect;
+        let first = frame[y * stride + x];
+        let uniform =
+            (y..y + h).all(|row| frame[row * stride + x..][..w].iter().all(|&p| p == first));
+        if uniform {
+            // Fill compression
+            out.push(0x80);
+            self.format.put_tight(first, out);
+            return;
+        }
+        // Basic compression on stream 0 without a filter
+        out.push(0x00);
+        let data = self.pixels(frame, stride, rect, true);
+        if data.len() < 12 {
+            out.extend(data);
+            return;
+        }
+        let compressed = deflate(&mut self.tight, &data);
+        let mut len = compressed.len();
+        loop {
+            let byte = (len & 0x7F) as u8;
+            len >>= 7;
+            if len == 0 {
+                out.push(byte);
+                break;
+            }
+            out.push(byte | 0x80);
+        }
+        out.extend(compressed);
+    }
+}
+
+/// Bounding box of the pixels that differ between two frames, within `area`
+fn changed_area(old: &[u32], new: &[u32], stride: usize, area: Rect) -> Option<Rect> {
+    let (ax, ay, aw, ah) = area;
+    let mut bounds: Option<(usize, usize, usize, usize)> = None;
+    for y in ay..ay + ah {
+        let (a, b) = (&old[y * stride + ax..][..aw], &new[y * stride + ax..][..aw]);
+        let Some(first) = a.iter().zip(b).position(|(p, q)| p != q) else {
+            continue;
+        };
+        let last = a.iter().zip(b).rposition(|(p, q)| p != q).unwrap_or(first);
+        let (x0, x1) = (ax + first, ax + last + 1);
+        bounds = Some(match bounds {
+            None => (x0, y, x1, y + 1),
+            Some((bx0, by0, bx1, _)) => (bx0.min(x0), by0, bx1.max(x1), y + 1),
+        });
+    }
+    bounds.map(|(x0, y0, x1, y1)| (x0, y0, x1 - x0, y1 - y0))
+}
+
+/// Answer update requests whenever the frame changes
+fn write_updates(stream: &mut TcpStream, shared: &Shared, id: u64) -> io::Result<()> {
+    let mut encoder = Encoder {
+        format: SERVER_FORMAT,
+        zlib: Compress::new(Compression::default(), true),
+        tight: Compress::new(Compression::default(), true),
+    };
+    let mut sent: Option<Vec<u32>> = None;
+    let mut sent_serial = 0;
+    loop {
+        let (frame, (width, height), serial, request, format, encodings) = {
+            let mut state = shared.lock();
+            loop {
+                if state.stop {
+                    return Ok(());
+                }
+                let serial = state.serial;
+                let client = state.clients.get(&id).ok_or(io::ErrorKind::NotConnected)?;
+                if client.closed {
+                    return Ok(());
+                }
+                match client.request {
+                    Some(r) if !r.incremental || serial != sent_serial => break,
+                    _ => {
+                        state = shared
+                            .changed
+                            .wait(state)
+                            .unwrap_or_else(|e| e.into_inner())
+                    }
+                }
+            }
+            let serial = state.serial;
+            let (frame, size) = (state.frame.clone(), state.size);
+            let client = state
+                .clients
+                .get_mut(&id)
+                .ok_or(io::ErrorKind::NotConnected)?;
+            let request = client.request.take().ok_or(io::ErrorKind::NotConnected)?;
+            (
+                frame,
+                size,
+                serial,
+                request,
+                client.format,
+                client.encodings.clone(),
+            )
+        };
+        sent_serial = serial;
+
+        let (ax, ay, aw, ah) = request.area;
+        let (ax, ay) = (ax.min(width), ay.min(height));
+        let area = (ax, ay, aw.min(width - ax), ah.min(height - ay));
+        let rect = match &sent {
+            Some(old) if request.incremental && format == encoder.format => {
+                changed_area(old, &frame, width, area)
+            }
+            _ => Some(area),
+        };
+        let Some(rect) = rect.filter(|r| r.2 > 0 && r.3 > 0) else {
+            if !request.incremental {
+                // The area is empty outright and never will have content
+                stream.write_all(&[0, 0, 0, 0])?;
+                continue;
+            }
+            // Nothing new in the requested area yet; wait for the next frame
+            if let Some(client) = shared.lock().clients.get_mut(&id) {
+                client.request.get_or_insert(request);
+            }
+            sent = Some(frame);
+            continue;
+        };
+        if format != encoder.format {
+            encoder.format = format;
+        }
+        let encoding = encodings
+            .iter()
+            .copied()
+            .find(|e| [ENCODING_RAW, ENCODING_ZLIB, ENCODING_TIGHT].contains(e))
+            .unwrap_or(ENCODING_RAW);
+        let mut body = Vec::new();
+        let count = encoder.encode(&mut body, &frame, width, rect, encoding);
+        let mut message = vec![0, 0];
+        message.extend(count.to_be_bytes());
+        message.extend(body);
+        stream.write_all(&message)?;
+        sent = Some(frame);
+    }
+}
+
+/// Serve a window over RFB (VNC)
+///
+/// The framebuffer has the window's current size; later presents are
+/// stretched to it. Clients see frames from the next present on and, if
+/// `allow_input` is set, their key and pointer events arrive at the
+/// window's following updates. Serving again replaces the running server.
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by one of the window constructors
+/// - `opts` must be a valid pointer to MiniFBVncOptions
+///
+/// Returns the TCP port listened on, or -1 on error (see `minifb_get_last_error`)
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_serve_vnc(
+    window: *mut MiniFBWindow,
+    opts: *const MiniFBVncOptions,
+) -> i32 {
+    if window.is_null() || opts.is_null() {
+        return -1;
+    }
+    let backend = &mut (*window).window;
+    backend.vnc = None;
+    let (width, height) = backend.get_size();
+    if width == 0 || height == 0 || width > u16::MAX as usize || height > u16::MAX as usize {
+        set_last_error("window size not representable in RFB");
+        return -1;
+    }
+    match VncServer::start(&*opts, (width, height), backend.waker.clone()) {
+        Ok(server) => {
+            let port = server.port as i32;
+            backend.vnc = Some(server);
+            port
+        }
+        Err(e) => {
+            set_last_error(&format!("VNC server: {}", e));
+            -1
+        }
+    }
+}
+
+/// Stop serving a window over VNC and disconnect all clients
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by one of the window constructors
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_stop_vnc(window: *mut MiniFBWindow) {
+    if window.is_null() {
+        return;
+    }
+    (*window).window.vnc = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Decompress, FlushDecompress};
+
+    fn connect(port: u16, encoding: i32) -> TcpStream {
+        let mut s = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        s.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        assert_eq!(&read_bytes::<12>(&mut s).unwrap(), b"RFB 003.008\n");
+        s.write_all(b"RFB 003.008\n").unwrap();
+        assert_eq!(read_bytes::<2>(&mut s).unwrap(), [1, 1]);
+        s.write_all(&[1]).unwrap();
+        assert_eq!(read_bytes::<4>(&mut s).unwrap(), [0; 4]);
+        s.write_all(&[1]).unwrap();
+        let init: [u8; 24] = read_bytes(&mut s).unwrap();
+        assert_eq!(&init[..4], &[0, 4, 0, 2]);
+        let _name: [u8; 6] = read_bytes(&mut s).unwrap();
+        let mut set_encodings = vec![2, 0, 0, 1];
+        set_encodings.extend(encoding.to_be_bytes());
+        s.write_all(&set_encodings).unwrap();
+        s
+    }
+
+    fn request(s: &mut TcpStream, incremental: bool) -> (Rect, i32) {
+        s.write_all(&[3, incremental as u8, 0, 0, 0, 0, 0, 4, 0, 2])
+            .unwrap();
+        let header: [u8; 16] = read_bytes(s).unwrap();
+        assert_eq!(&header[..4], &[0, 0, 0, 1]);
+        let u16_at = |i: usize| u16::from_be_bytes([header[i], header[i + 1]]) as usize;
+        let encoding = i32::from_be_bytes([header[12], header[13], header[14], header[15]]);
+        ((u16_at(4), u16_at(6), u16_at(8), u16_at(10)), encoding)
+    }
+
+    #[test]
+    fn test_raw_update_and_input() {
+        let opts = MiniFBVncOptions {
+            port: 0,
+            ..minifb_vnc_options_default()
+        };
+        let server = VncServer::start(&opts, (4, 2), Arc::default()).unwrap();
+        let mut s = connect(server.port, ENCODING_RAW);
+        server.publish(&[0x00112233; 8], 4, 2, (8, 4));
+        assert_eq!(request(&mut s, false), ((0, 0, 4, 2), ENCODING_RAW));
+        let pixels: [u8; 32] = read_bytes(&mut s).unwrap();
+        assert_eq!(&pixels[..4], &[0x33, 0x22, 0x11, 0]);
+
+        // Only the changed pixel is sent for an incremental request
+        let mut frame = [0x00112233; 8];
+        frame[6] = 0x00FF0000;
+        server.publish(&frame, 4, 2, (8, 4));
+        assert_eq!(request(&mut s, true), ((2, 1, 1, 1), ENCODING_RAW));
+        let _pixel: [u8; 4] = read_bytes(&mut s).unwrap();
+
+        // A full request for an area outside the framebuffer gets an empty update
+        s.write_all(&[3, 0, 0, 9, 0, 0, 0, 1, 0, 1]).unwrap();
+        assert_eq!(read_bytes::<4>(&mut s).unwrap(), [0; 4]);
+        assert_eq!(request(&mut s, false), ((0, 0, 4, 2), ENCODING_RAW));
+        let _pixels: [u8; 32] = read_bytes(&mut s).unwrap();
+
+        s.write_all(&[4, 1, 0, 0, 0, 0, 0, b'q']).unwrap();
+        s.write_all(&[5, 1, 0, 3, 0, 1]).unwrap();
+        let mut events = Vec::new();
+        for _ in 0..100 {
+            events.extend(server.take_events());
+            if events.len() >= 3 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(
+            events,
+            [
+                InputEvent::Key(Key::Q, true),
+                InputEvent::MouseMove(Some((6.0, 2.0))),
+                InputEvent::Button(MouseButton::Left, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_zlib_update() {
+        let opts = MiniFBVncOptions {
+            port: 0,
+            ..minifb_vnc_options_default()
+        };
+        let server = VncServer::start(&opts, (4, 2), Arc::default()).unwrap();
+        let mut s = connect(server.port, ENCODING_ZLIB);
+        let frame: Vec<u32> = (0..8).collect();
+        server.publish(&frame, 4, 2, (4, 2));
+        assert_eq!(request(&mut s, false), ((0, 0, 4, 2), ENCODING_ZLIB));
+        let len = u32::from_be_bytes(read_bytes(&mut s).unwrap()) as usize;
+        let mut data = vec![0; len];
+        s.read_exact(&mut data).unwrap();
+        let mut pixels = Vec::with_capacity(64);
+        Decompress::new(true)
+            .decompress_vec(&data, &mut pixels, FlushDecompress::Sync)
+            .unwrap();
+        assert_eq!(pixels.len(), 32);
+        assert_eq!(&pixels[28..], &[7, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,257 @@
+//! Wakeup descriptor for event-loop integration
+//!
+//! Instead of calling `minifb_window_update` at a fixed rate, a host with
+//! its own event loop can watch a window's wakeup descriptor (see
+//! `minifb_window_wakeup_fd`) and call `minifb_window_pump` only once it
+//! turns readable. It does so when input is waiting: injected events, VNC
+//! clients and close requests signal it directly. Native and terminal
+//! windows don't expose their event queues, so for those a watcher thread
+//! also signals it at least every `WATCH_TICK` (terminals right away once
+//! stdin has data), which is when the window may have events to process.
+//!
+//! The descriptor stays readable until the next pump.
+
+use crate::MiniFBWindow;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Longest a native or terminal window goes without signalling its descriptor
+#[cfg(unix)]
+const WATCH_TICK: Duration = Duration::from_millis(16);
+
+/// Non-blocking self-pipe; the read end is what hosts watch
+#[cfg(unix)]
+struct Pipe {
+    read: i32,
+    write: i32,
+}
+
+#[cfg(unix)]
+impl Pipe {
+    fn new() -> std::io::Result<Pipe> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let pipe = Pipe {
+            read: fds[0],
+            write: fds[1],
+        };
+        for fd in fds {
+            unsafe {
+                let flags = libc::fcntl(fd, libc::F_GETFL);
+                libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK);
+                libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+        }
+        Ok(pipe)
+    }
+
+    fn signal(&self) {
+        // A full pipe is readable already, so a failed write needs no retry
+        unsafe { libc::write(self.write, [1u8].as_ptr().cast(), 1) };
+    }
+
+    fn drain(&self) {
+        let mut buf = [0u8; 64];
+        while unsafe { libc::read(self.read, buf.as_mut_ptr().cast(), buf.len()) } > 0 {}
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.read);
+            libc::close(self.write);
+        }
+    }
+}
+
+/// What a watcher thread waits for between signals
+#[cfg(unix)]
+#[derive(Clone, Copy)]
+pub(crate) enum Watch {
+    /// Nothing observable, only time passing
+    Tick,
+    /// Data on stdin, or time passing
+    Stdin,
+}
+
+#[derive(Default)]
+struct State {
+    #[cfg(unix)]
+    pipe: Option<Pipe>,
+    /// The descriptor is readable and stays so until cleared
+    signaled: bool,
+    /// The window is gone; watcher threads exit
+    closed: bool,
+}
+
+/// Signals a window's wakeup descriptor; shared with the threads that
+/// produce its input
+#[derive(Default)]
+pub(crate) struct Waker {
+    state: Mutex<State>,
+    cleared: Condvar,
+}
+
+impl Waker {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Make the descriptor readable, if it was ever requested
+    pub(crate) fn wake(&self) {
+        #[cfg(unix)]
+        {
+            let mut state = self.lock();
+            if let (false, Some(pipe)) = (state.signaled, &state.pipe) {
+                pipe.signal();
+                state.signaled = true;
+            }
+        }
+    }
+
+    /// Reset the descriptor before the window processes its input
+    pub(crate) fn clear(&self) {
+        let mut state = self.lock();
+        if !state.signaled {
+            return;
+        }
+        #[cfg(unix)]
+        if let Some(pipe) = &state.pipe {
+            pipe.drain();
+        }
+        state.signaled = false;
+        self.cleared.notify_all();
+    }
+
+    /// Stop the watcher thread, if any
+    pub(crate) fn close(&self) {
+        self.lock().closed = true;
+        self.cleared.notify_all();
+    }
+
+    /// The descriptor to watch, created on first use along with a watcher
+    /// thread if the window needs one
+    #[cfg(unix)]
+    fn fd(self: &std::sync::Arc<Self>, watch: Option<Watch>) -> std::io::Result<i32> {
+        let mut state = self.lock();
+        if let Some(pipe) = &state.pipe {
+            return Ok(pipe.read);
+        }
+        let pipe = Pipe::new()?;
+        let fd = pipe.read;
+        state.pipe = Some(pipe);
+        if let Some(watch) = watch {
+            let waker = self.clone();
+            std::thread::spawn(move || waker.watch(watch));
+        }
+        Ok(fd)
+    }
+
+    #[cfg(unix)]
+    fn watch(&self, watch: Watch) {
+        loop {
+            {
+                let mut state = self.lock();
+                while state.signaled && !state.closed {
+                    state = self.cleared.wait(state).unwrap_or_else(|e| e.into_inner());
+                }
+                if state.closed {
+                    return;
+                }
+            }
+            match watch {
+                Watch::Tick => std::thread::sleep(WATCH_TICK),
+                Watch::Stdin => {
+                    crate::terminal::wait_stdin(WATCH_TICK);
+                }
+            }
+            self.wake();
+        }
+    }
+}
+
+/// Get a descriptor that becomes readable when the window has input to process
+///
+/// The descriptor is created on the first call and owned by the window:
+/// don't read from or close it. It is readable while input events are
+/// waiting or a close was requested. Native and terminal windows also
+/// signal it at least every 16 ms, as their own event queues can't be
+/// watched. Call `minifb_window_pump` once it is readable; that resets it.
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by `minifb_window_new`
+///
+/// Returns the descriptor, or -1 on failure (see `minifb_get_last_error`)
+#[cfg(unix)]
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_wakeup_fd(window: *mut MiniFBWindow) -> i32 {
+    if window.is_null() {
+        return -1;
+    }
+    let backend = &(*window).window;
+    match backend.waker.fd(backend.watch()) {
+        Ok(fd) => fd,
+        Err(e) => {
+            crate::set_last_error(&format!("cannot create wakeup descriptor: {}", e));
+            -1
+        }
+    }
+}
+
+/// Process pending window events without presenting or waiting
+///
+/// Does what `minifb_window_update` does, including gathering injected and
+/// remote input, but ignores the target frame rate, so it returns at once.
+/// The window's wakeup descriptor is reset first, so events arriving while
+/// it runs signal it again.
+///
+/// # Safety
+/// - `window` must be a valid pointer returned by `minifb_window_new`
+///
+/// Returns 0 on success, -1 if the window is null
+#[no_mangle]
+pub unsafe extern "C" fn minifb_window_pump(window: *mut MiniFBWindow) -> i32 {
+    if window.is_null() {
+        return -1;
+    }
+    (*window).window.pump();
+    0
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::input::InputEvent;
+    use minifb::Key;
+
+    fn readable(fd: i32) -> bool {
+        let mut pfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        unsafe { libc::poll(&mut pfd, 1, 0) > 0 }
+    }
+
+    #[test]
+    fn test_injected_input_signals_until_pump() {
+        let window = crate::backend::minifb_window_new_headless(4, 4);
+        unsafe {
+            let fd = minifb_window_wakeup_fd(window);
+            assert!(fd >= 0);
+            assert_eq!(minifb_window_wakeup_fd(window), fd);
+            assert!(!readable(fd));
+
+            (*window).window.inject(InputEvent::Key(Key::A, true));
+            assert!(readable(fd));
+            assert_eq!(minifb_window_pump(window), 0);
+            assert!(!readable(fd));
+            assert!((*window).window.is_key_down(Key::A));
+            crate::minifb_window_free(window);
+        }
+    }
+}